@@ -26,6 +26,9 @@ pub(crate) const SYMTAB_ENTRY: Alignment = Alignment { exponent: 3 };
 /// Alignment for entries in the global offset table.
 pub(crate) const GOT_ENTRY: Alignment = Alignment { exponent: 3 };
 
+/// Alignment for entries in the .hash section.
+pub(crate) const HASH_ENTRY: Alignment = Alignment { exponent: 2 };
+
 /// The minimum alignment of a rela entry.
 pub(crate) const RELA_ENTRY: Alignment = Alignment { exponent: 3 };
 
@@ -2,7 +2,10 @@
 //! order is important for some arguments and it's not clear how easy it would be to get that
 //! correct with something like clap.
 
+use crate::diagnostics::ColorChoice;
+use crate::diagnostics::Diagnostics;
 use crate::error::Result;
+use crate::jobserver;
 use crate::save_dir::SaveDir;
 use anyhow::anyhow;
 use anyhow::bail;
@@ -14,19 +17,107 @@ use std::sync::atomic::AtomicI64;
 use std::sync::Arc;
 
 pub(crate) struct Args {
+    /// Directories to search for libraries. Any sysroot prefixes have already been applied. If a
+    /// sysroot was given, then unless `-nostdlib` was also given, the default directories within
+    /// the sysroot are included at the end.
     pub(crate) lib_search_path: Vec<Box<Path>>,
+    /// The directory given to `--sysroot`, if any.
+    pub(crate) sysroot: Option<Box<Path>>,
     pub(crate) inputs: Vec<Input>,
     pub(crate) output: Arc<Path>,
     pub(crate) dynamic_linker: Option<Box<Path>>,
     pub(crate) link_static: bool,
-    pub(crate) num_threads: NonZeroUsize,
+    /// The number of threads given to `--threads`. If not given, we use as many threads as we have
+    /// cores, unless a jobserver limits us to fewer.
+    pub(crate) num_threads: Option<NonZeroUsize>,
     pub(crate) strip_all: bool,
     pub(crate) prepopulate_maps: bool,
     pub(crate) sym_info: Option<String>,
     pub(crate) merge_strings: bool,
+    /// Whether a string that's a suffix of another string should share storage with it. Enabled by
+    /// `-O2` or higher.
+    pub(crate) tail_merge_strings: bool,
     pub(crate) debug_fuel: Option<AtomicI64>,
     pub(crate) time_phases: bool,
     pub(crate) pie: bool,
+    /// Whether we're producing a shared object rather than an executable.
+    pub(crate) shared: bool,
+    /// The value to put in DT_SONAME when producing a shared object.
+    pub(crate) soname: Option<String>,
+    /// A script that controls which symbols are exported when producing a shared object.
+    pub(crate) version_script: Option<PathBuf>,
+    /// Whether references from within a shared object to its own exported symbols should bind
+    /// directly to those definitions rather than allowing them to be interposed. Set by
+    /// `-Bsymbolic`.
+    pub(crate) b_symbolic: bool,
+    /// Whether to emit a PT_GNU_RELRO segment so that the dynamic loader can make data that's only
+    /// written during relocation read-only afterwards.
+    pub(crate) relro: bool,
+    /// Whether to silently use the first definition when a symbol is strongly defined more than
+    /// once, rather than reporting an error.
+    pub(crate) allow_multiple_definitions: bool,
+    /// The symbol (or address) at which execution should start, if overridden with `-e`.
+    pub(crate) entry: Option<String>,
+    /// Symbols defined on the command line with `--defsym`.
+    pub(crate) defsym: Vec<Defsym>,
+    /// Symbols given to `-u`. These are treated as undefined references, so will cause archive
+    /// entries that define them to be loaded.
+    pub(crate) undefined: Vec<String>,
+    /// Symbols given to `--require-defined`. As for `undefined`, except that it's an error if these
+    /// don't end up defined.
+    pub(crate) require_defined: Vec<String>,
+    /// Symbols given to `--wrap`. Undefined references to these resolve to `__wrap_<symbol>`,
+    /// while undefined references to `__real_<symbol>` resolve to the original symbol.
+    pub(crate) wrap: Vec<String>,
+    /// Warnings from all phases of the link, together with the flags that control how they're
+    /// reported.
+    pub(crate) diagnostics: Diagnostics,
+    /// Whether to do the link in a forked subprocess, so that we can exit without waiting for
+    /// cleanup. Disabled with `--no-fork`.
+    pub(crate) should_fork: bool,
+    /// Where to write a map file describing our output's layout, if anywhere.
+    pub(crate) map_file: Option<PathBuf>,
+    /// Whether to write a map file to stdout.
+    pub(crate) print_map: bool,
+    /// Whether to print each input file that we load and why. Set by `-t` or `--trace`.
+    pub(crate) trace: bool,
+    /// Glob patterns for symbols for which we should explain why the section that defines them
+    /// was kept.
+    pub(crate) why_live: Vec<String>,
+    /// Symbols for which we should print each file that defines or references them. Set by `-y`
+    /// or `--trace-symbol`.
+    pub(crate) trace_symbols: Vec<String>,
+    /// Whether to discard sections that nothing references. Disabled by `--no-gc-sections`.
+    pub(crate) gc_sections: bool,
+    /// Whether to print each section that was discarded because nothing referenced it.
+    pub(crate) print_gc_sections: bool,
+    /// Whether to print our version before linking. Set by `-v`.
+    pub(crate) print_version: bool,
+}
+
+/// What we've been asked to do.
+pub(crate) enum Action {
+    /// Link with the supplied arguments.
+    Link(Box<Args>),
+    /// Print our help. Set by `--help`.
+    PrintHelp,
+    /// Print our version. Set by `--version`, or by `-v` if there are no inputs.
+    PrintVersion,
+}
+
+/// A symbol definition from `--defsym=name=expression`.
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) struct Defsym {
+    pub(crate) name: String,
+    pub(crate) value: DefsymValue,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) enum DefsymValue {
+    /// An absolute value.
+    Value(u64),
+    /// The address of another symbol plus an offset.
+    Symbol(String, i64),
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -35,56 +126,260 @@ pub(crate) struct Input {
     /// A directory to search first. Only present when the input came from a linker script, in which
     /// case this is the directory containing the linker script.
     pub(crate) search_first: Option<PathBuf>,
+    pub(crate) modifiers: Modifiers,
+}
+
+/// Positional flags that affect how the inputs that follow them are treated. Can be saved and
+/// restored with `--push-state` and `--pop-state`.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub(crate) struct Modifiers {
+    /// Whether all members of archives should be loaded, not just those that define referenced
+    /// symbols.
+    pub(crate) whole_archive: bool,
+    /// Whether object files should be treated like archive members, i.e. only loaded if they
+    /// define a symbol that's referenced. Set between `--start-lib` and `--end-lib`.
+    pub(crate) archive_semantics: bool,
+    /// Whether `-l` should only look for static archives, ignoring shared objects. Set by
+    /// `-Bstatic` and cleared by `-Bdynamic`.
+    pub(crate) static_libs_only: bool,
+    /// Whether shared objects should only be added to DT_NEEDED if they're actually referenced.
+    /// Set by `--as-needed` and cleared by `--no-as-needed`. TODO: We don't yet act on this, so
+    /// all shared objects are currently added to DT_NEEDED.
+    pub(crate) as_needed: bool,
+}
+
+impl Modifiers {
+    /// Combines the modifiers from two occurrences of the same input file, keeping whichever of each
+    /// causes more to be loaded.
+    pub(crate) fn merge(self, other: Modifiers) -> Modifiers {
+        Modifiers {
+            whole_archive: self.whole_archive || other.whole_archive,
+            archive_semantics: self.archive_semantics && other.archive_semantics,
+            // By the time we've found the file, this has done its job.
+            static_libs_only: self.static_libs_only,
+            as_needed: self.as_needed && other.as_needed,
+        }
+    }
 }
 
 #[derive(Debug, Eq, PartialEq)]
 pub(crate) enum InputSpec {
     File(Box<Path>),
+    /// A library name given to `-l`. We'll look for `lib<name>.so` and/or `lib<name>.a`.
     Lib(Box<str>),
+    /// An exact filename to look for on the library search path, from `-l:filename`.
+    Search(Box<str>),
 }
 
-// These flags don't currently affect our behaviour. TODO: Assess whether we should error or warn if
-// these are given. This is tricky though. On the one hand we want to be a drop-in replacement for
-// other linkers. On the other, we should perhaps somehow let the user know that we don't support a
-// feature.
+/// The directories that ld searches for libraries after any given with `-L`, unless `-nostdlib`
+/// was specified. The leading `=` means that they're relative to the sysroot. We only search these
+/// when `--sysroot` is given. Otherwise, we rely on the compiler driver passing the directories that
+/// it wants searched with `-L`.
+const DEFAULT_SEARCH_PATH: &[&str] = &[
+    "=/usr/local/lib/x86_64-linux-gnu",
+    "=/lib/x86_64-linux-gnu",
+    "=/usr/lib/x86_64-linux-gnu",
+    "=/usr/lib/x86_64-linux-gnu64",
+    "=/usr/local/lib64",
+    "=/lib64",
+    "=/usr/lib64",
+    "=/usr/local/lib",
+    "=/lib",
+    "=/usr/lib",
+    "=/usr/x86_64-linux-gnu/lib64",
+    "=/usr/x86_64-linux-gnu/lib",
+];
+
+// These flags don't currently affect our behaviour, but our output is what the flag asks for anyway,
+// so we ignore them silently.
 const IGNORED_FLAGS: &[&str] = &[
-    // TODO: Support eh_frame and eh_frame_hdr
-    "--eh-frame-hdr",
-    // TODO: Support build-ids
-    "--build-id",
-    // TODO: We currently always GC sections. Support _not_ GCing them.
-    "--gc-sections",
-    // TODO: Support --as-needed and --no-as-needed. Requires dynamic linking.
-    "--as-needed",
-    "--no-as-needed",
     // TODO: Think about if anything is needed here. We don't need groups in order resolve cycles,
     // so perhaps ignoring these is the right thing to do.
     "--start-group",
     "--end-group",
-    // TODO: See if these flags need special treatment.
-    "-Bdynamic",
-    "-Bstatic",
-    // TODO: This is supposed to suppress built-in search paths, but I don't think we have any
-    // built-in search paths. Perhaps we should?
-    "-nostdlib",
-    // TODO: Implement
-    "--no-dynamic-linker",
     // TODO: Once we support linking debug info, make this flag skip linking it.
     "--strip-debug",
 ];
 
-impl Args {
+// These flags are accepted so that we remain a drop-in replacement for other linkers, but we don't
+// do what they ask, so we warn if they're given.
+const UNSUPPORTED_FLAGS: &[&str] = &[
+    // TODO: Support eh_frame_hdr
+    "--eh-frame-hdr",
+    // TODO: Support build-ids
+    "--build-id",
+    // TODO: Implement
+    "--no-dynamic-linker",
+];
+
+/// The flags that we support, together with a description of each, used to generate our `--help`
+/// output. Alternative spellings are separated by ", ".
+const HELP: &[(&str, &str)] = &[
+    ("@<file>", "Read further arguments from <file>"),
+    ("-o <file>", "Set the output file name"),
+    (
+        "-l<name>",
+        "Link against lib<name>.so or lib<name>.a. -l:<file> searches for <file> exactly",
+    ),
+    ("-L<dir>, -L <dir>", "Add <dir> to the library search path"),
+    (
+        "--sysroot=<dir>, --sysroot <dir>",
+        "Replace `=` or `$SYSROOT` at the start of search directories with <dir>. Also search \
+         the standard library directories within <dir>",
+    ),
+    (
+        "-nostdlib",
+        "Don't search the standard library directories within the sysroot",
+    ),
+    ("-static", "Produce a static executable. Implies -Bstatic"),
+    (
+        "-Bstatic, -dn, -non_shared",
+        "Only look for archives when searching for subsequent libraries",
+    ),
+    (
+        "-Bdynamic, -dy, -call_shared",
+        "Look for shared objects as well as archives for subsequent libraries",
+    ),
+    ("-pie", "Produce a position-independent executable"),
+    ("-shared, -Bshareable", "Produce a shared object"),
+    (
+        "-Bsymbolic",
+        "Bind references within a shared object to its own definitions of global symbols",
+    ),
+    (
+        "-soname <name>, -h <name>, -soname=<name>, --soname=<name>",
+        "Set DT_SONAME when producing a shared object",
+    ),
+    ("-dynamic-linker <file>", "Set the program interpreter"),
+    (
+        "-e <symbol>, --entry <symbol>, --entry=<symbol>",
+        "Start execution at <symbol>",
+    ),
+    (
+        "--defsym <symbol>=<expression>, --defsym=<symbol>=<expression>",
+        "Define <symbol> as a number, another symbol, or another symbol plus or minus a number",
+    ),
+    (
+        "-u <symbol>, --undefined <symbol>, --undefined=<symbol>",
+        "Treat <symbol> as undefined, so that archive members that define it get loaded",
+    ),
+    (
+        "--require-defined <symbol>, --require-defined=<symbol>",
+        "As for -u, but it's an error if <symbol> doesn't end up defined",
+    ),
+    (
+        "--wrap <symbol>, --wrap=<symbol>",
+        "Resolve references to <symbol> to __wrap_<symbol> and __real_<symbol> to <symbol>",
+    ),
+    ("--whole-archive", "Load all members of subsequent archives"),
+    ("--no-whole-archive", "Turn off --whole-archive"),
+    (
+        "--start-lib",
+        "Treat subsequent object files as if they were archive members",
+    ),
+    ("--end-lib", "End a --start-lib group"),
+    (
+        "--as-needed",
+        "Only add DT_NEEDED for subsequent shared objects that are referenced",
+    ),
+    ("--no-as-needed", "Turn off --as-needed"),
+    (
+        "--push-state",
+        "Save the state of positional flags such as --whole-archive and -Bstatic",
+    ),
+    ("--pop-state", "Restore the state saved by --push-state"),
+    (
+        "--allow-multiple-definition, -z muldefs",
+        "Use the first definition of symbols that are defined more than once",
+    ),
+    (
+        "-z relro, -z norelro",
+        "Whether to emit a PT_GNU_RELRO segment",
+    ),
+    (
+        "--version-script <file>, --version-script=<file>",
+        "Use <file> to control which symbols are exported",
+    ),
+    ("--strip-all", "Don't write a symbol table"),
+    (
+        "-Map=<file>, -Map <file>, --Map=<file>, --Map <file>",
+        "Write a map file describing the layout of the output to <file>",
+    ),
+    ("-M, --print-map", "Write a map file to stdout"),
+    (
+        "-t, --trace",
+        "Print each input file that is loaded and the symbol that caused it to be loaded",
+    ),
+    (
+        "--why-live=<glob>, --why-live <glob>",
+        "Print why the sections defining symbols matching <glob> were kept",
+    ),
+    (
+        "-y <symbol>, --trace-symbol=<symbol>, --trace-symbol <symbol>",
+        "Print each file that defines or references <symbol>",
+    ),
+    (
+        "--gc-sections, --no-gc-sections",
+        "Whether to discard unreferenced sections (default: discard)",
+    ),
+    (
+        "--print-gc-sections, --no-print-gc-sections",
+        "Whether to list sections that were discarded because nothing referenced them",
+    ),
+    ("--no-string-merge", "Don't merge identical strings"),
+    ("--threads=<num>", "Use <num> threads"),
+    ("--time", "Print how long each phase of the link takes"),
+    (
+        "--no-fork",
+        "Don't do the link in a subprocess. By default, we exit once our output is written, \
+         leaving a subprocess to clean up",
+    ),
+    ("--fork", "Turn off --no-fork"),
+    (
+        "--prepopulate-maps",
+        "Prepopulate memory maps of input files. Useful when profiling",
+    ),
+    (
+        "--sym-info <symbol>",
+        "Print information about <symbol>. Useful when debugging",
+    ),
+    (
+        "--debug-fuel=<num>",
+        "Limit how many times certain code paths are taken. Useful when debugging",
+    ),
+    ("--fatal-warnings", "Treat warnings as errors"),
+    ("--no-fatal-warnings", "Turn off --fatal-warnings"),
+    ("--no-warnings, -w", "Don't print warnings"),
+    (
+        "--error-limit=<num>",
+        "Report at most <num> errors. 0 means no limit",
+    ),
+    (
+        "--color-diagnostics, --color-diagnostics=<when>",
+        "Use colour in diagnostics. <when> is auto, always or never",
+    ),
+    ("--no-color-diagnostics", "Don't use colour in diagnostics"),
+    (
+        "-O<level>, -O <level>",
+        "Optimisation level. 2 or higher enables merging of strings that are suffixes of others",
+    ),
+    (
+        "-m <emulation>, --hash-style=<style>",
+        "Accepted for compatibility, but ignored",
+    ),
+    ("--help", "Print this help, then exit"),
+    ("--version", "Print version information, then exit"),
+    ("-v", "Print version information"),
+];
+
+impl Action {
     pub(crate) fn from_env() -> Result<Self> {
         let r = Self::parse(std::env::args());
 
-        // We want to be able to use our linker to link Rust programs, but we don't yet support
-        // dynamic linking, which is needed in order to link proc macros. So if we detect an error
-        // with the linker arguments, e.g. an unsupported option or that we're not statically
-        // linking, then we check if the environment variable WILD_FALLBACK_LINKER is set and if it
-        // is, use that instead. The idea is that we can link the proc macros with the system linker
-        // and use our linker for linking the final executable. TODO: Remove this once we support
-        // dynamic linking
-        if r.as_ref().map(|a| a.link_static).unwrap_or(true) {
+        // If we fail to parse our arguments, e.g. because we were given an option that we don't
+        // support, then the user can opt in to having some other linker used instead by setting
+        // WILD_FALLBACK_LINKER. We never fall back for arguments that we understand.
+        if r.is_err() {
             if let Ok(fallback) = std::env::var("WILD_FALLBACK_LINKER") {
                 let status = std::process::Command::new(&fallback)
                     .args(std::env::args().skip(1))
@@ -97,8 +392,10 @@ impl Args {
         r
     }
 
-    // Parse the supplied input arguments, which should not include the program name.
-    pub(crate) fn parse<S: AsRef<str>, I: Iterator<Item = S>>(mut input: I) -> Result<Self> {
+    // Parse the supplied input arguments, which should include the program name.
+    pub(crate) fn parse<S: AsRef<str>, I: Iterator<Item = S>>(input: I) -> Result<Self> {
+        let all_args = expand_response_files(input)?;
+        let mut input = all_args.iter();
         let mut lib_search_path = Vec::new();
         let mut inputs = Vec::new();
         let mut output = None;
@@ -111,31 +408,71 @@ impl Args {
         let mut save_dir = SaveDir::new()?;
         let mut sym_info = None;
         let mut merge_strings = true;
+        let mut tail_merge_strings = false;
         let mut debug_fuel = None;
         let mut pie = false;
+        let mut shared = false;
+        let mut soname = None;
+        let mut version_script = None;
+        let mut b_symbolic = false;
+        let mut relro = false;
+        let mut allow_multiple_definitions = false;
+        let mut entry = None;
+        let mut wrap = Vec::new();
+        let mut defsym = Vec::new();
+        let mut undefined = Vec::new();
+        let mut require_defined = Vec::new();
+        let mut modifiers = Modifiers::default();
+        let mut modifier_stack = Vec::new();
+        let mut sysroot = None;
+        let mut nostdlib = false;
+        let mut diagnostics = Diagnostics::default();
+        let mut print_version = false;
+        let mut should_fork = true;
+        let mut map_file = None;
+        let mut print_map = false;
+        let mut trace = false;
+        let mut why_live = Vec::new();
+        let mut trace_symbols = Vec::new();
+        let mut gc_sections = true;
+        let mut print_gc_sections = false;
         // Skip program name
         input.next();
         while let Some(arg) = input.next() {
-            let arg = arg.as_ref();
+            let arg = arg.as_str();
             if let Some(rest) = arg.strip_prefix("-L") {
                 if rest.is_empty() {
                     if let Some(next) = input.next() {
-                        lib_search_path.push(Box::from(Path::new(next.as_ref())));
+                        lib_search_path.push(next.as_str());
                     }
                 } else {
-                    lib_search_path.push(Box::from(Path::new(rest)));
+                    lib_search_path.push(rest);
                 }
             } else if let Some(rest) = arg.strip_prefix("-l") {
                 inputs.push(Input {
-                    spec: InputSpec::Lib(Box::from(rest)),
+                    spec: InputSpec::lib(rest),
                     search_first: None,
+                    modifiers,
                 });
             } else if arg == "-static" {
+                // As well as producing a static executable, this affects how subsequent `-l`
+                // arguments are searched, just like `-Bstatic`.
                 link_static = true;
+                modifiers.static_libs_only = true;
+            } else if ["-Bstatic", "-dn", "-non_shared"].contains(&arg) {
+                modifiers.static_libs_only = true;
+            } else if ["-Bdynamic", "-dy", "-call_shared"].contains(&arg) {
+                modifiers.static_libs_only = false;
+            } else if arg == "-nostdlib" {
+                nostdlib = true;
+            } else if arg == "--sysroot" {
+                sysroot = input.next().map(|a| Box::from(Path::new(a.as_str())));
+            } else if let Some(rest) = arg.strip_prefix("--sysroot=") {
+                sysroot = Some(Box::from(Path::new(rest)));
             } else if arg == "-o" {
-                output = input.next().map(|a| Arc::from(Path::new(a.as_ref())));
+                output = input.next().map(|a| Arc::from(Path::new(a.as_str())));
             } else if arg == "-dynamic-linker" {
-                dynamic_linker = input.next().map(|a| Box::from(Path::new(a.as_ref())));
+                dynamic_linker = input.next().map(|a| Box::from(Path::new(a.as_str())));
             } else if arg.starts_with("--hash-style=") {
             } else if arg == "--time" {
                 time_phases = true;
@@ -143,26 +480,172 @@ impl Args {
                 num_threads = Some(NonZeroUsize::try_from(rest.parse::<usize>()?)?);
             } else if arg == "--strip-all" {
                 strip_all = true;
-            } else if arg == "-z" || arg == "-m" {
+            } else if let Some(rest) = arg.strip_prefix("-z") {
+                let option = if rest.is_empty() {
+                    input
+                        .next()
+                        .map(|a| a.as_str().to_owned())
+                        .unwrap_or_default()
+                } else {
+                    rest.to_owned()
+                };
+                match option.as_str() {
+                    "relro" => relro = true,
+                    "norelro" => relro = false,
+                    "muldefs" => allow_multiple_definitions = true,
+                    // We don't support lazy binding, so we always bind now, regardless of whether
+                    // we're given `-z now` or `-z lazy`. TODO: Assess which other -z options we
+                    // should support.
+                    _ => {}
+                }
+            } else if arg == "-m" {
                 // Ignore this argument and the next thing that comes after it.
                 input.next();
-            } else if let Some(_rest) = arg.strip_prefix("-O") {
-                // We don't use opt-level for now.
+            } else if let Some(rest) = arg.strip_prefix("-O") {
+                let level = if rest.is_empty() {
+                    input.next().map(|a| a.as_str()).unwrap_or_default()
+                } else {
+                    rest
+                };
+                // Like GNU ld, we treat levels that aren't numbers as 0 rather than failing.
+                let level = level.parse::<u32>().unwrap_or_else(|_| {
+                    diagnostics.warning(format_args!(
+                        "ignoring invalid optimisation level `{level}`"
+                    ));
+                    0
+                });
+                tail_merge_strings = level >= 2;
             } else if arg == "--prepopulate-maps" {
                 prepopulate_maps = true;
             } else if arg == "--sym-info" {
-                sym_info = input.next().map(|a| a.as_ref().to_owned());
+                sym_info = input.next().map(|a| a.as_str().to_owned());
             } else if arg == "--no-string-merge" {
                 merge_strings = false;
             } else if arg == "-pie" {
                 pie = true;
+            } else if arg == "-shared" || arg == "-Bshareable" {
+                shared = true;
+            } else if arg == "-Bsymbolic" {
+                b_symbolic = true;
+            } else if arg == "-soname" || arg == "-h" {
+                soname = input.next().map(|a| a.as_str().to_owned());
+            } else if let Some(rest) = arg
+                .strip_prefix("-soname=")
+                .or_else(|| arg.strip_prefix("--soname="))
+            {
+                soname = Some(rest.to_owned());
+            } else if arg == "-e" || arg == "--entry" {
+                entry = input.next().map(|a| a.as_str().to_owned());
+            } else if let Some(rest) = arg.strip_prefix("--entry=") {
+                entry = Some(rest.to_owned());
+            } else if arg == "--defsym" {
+                if let Some(definition) = input.next() {
+                    defsym.push(Defsym::parse(definition.as_str())?);
+                }
+            } else if let Some(rest) = arg.strip_prefix("--defsym=") {
+                defsym.push(Defsym::parse(rest)?);
+            } else if arg == "-u" || arg == "--undefined" {
+                undefined.extend(input.next().map(|a| a.as_str().to_owned()));
+            } else if let Some(rest) = arg.strip_prefix("--undefined=") {
+                undefined.push(rest.to_owned());
+            } else if arg == "--require-defined" {
+                require_defined.extend(input.next().map(|a| a.as_str().to_owned()));
+            } else if let Some(rest) = arg.strip_prefix("--require-defined=") {
+                require_defined.push(rest.to_owned());
+            } else if arg == "--push-state" {
+                modifier_stack.push(modifiers);
+            } else if arg == "--pop-state" {
+                modifiers = modifier_stack
+                    .pop()
+                    .context("--pop-state without matching --push-state")?;
+            } else if arg == "--as-needed" {
+                modifiers.as_needed = true;
+            } else if arg == "--no-as-needed" {
+                modifiers.as_needed = false;
+            } else if arg == "--whole-archive" {
+                modifiers.whole_archive = true;
+            } else if arg == "--no-whole-archive" {
+                modifiers.whole_archive = false;
+            } else if arg == "--start-lib" {
+                if modifiers.archive_semantics {
+                    bail!("Nested --start-lib");
+                }
+                modifiers.archive_semantics = true;
+            } else if arg == "--end-lib" {
+                if !modifiers.archive_semantics {
+                    bail!("Stray --end-lib");
+                }
+                modifiers.archive_semantics = false;
+            } else if arg == "--wrap" {
+                wrap.extend(input.next().map(|a| a.as_str().to_owned()));
+            } else if let Some(rest) = arg.strip_prefix("--wrap=") {
+                wrap.push(rest.to_owned());
+            } else if arg == "--allow-multiple-definition" {
+                allow_multiple_definitions = true;
+            } else if arg == "--version-script" {
+                version_script = input.next().map(|a| PathBuf::from(a.as_str()));
+            } else if let Some(rest) = arg.strip_prefix("--version-script=") {
+                version_script = Some(PathBuf::from(rest));
             } else if let Some(rest) = arg.strip_prefix("--debug-fuel=") {
                 debug_fuel = Some(AtomicI64::new(rest.parse()?));
                 // Using debug fuel with more than one thread would likely give non-deterministic
                 // results.
                 num_threads = Some(NonZeroUsize::new(1).unwrap());
+            } else if arg == "-Map" || arg == "--Map" {
+                map_file = input.next().map(|a| PathBuf::from(a.as_str()));
+            } else if let Some(rest) = arg
+                .strip_prefix("-Map=")
+                .or_else(|| arg.strip_prefix("--Map="))
+            {
+                map_file = Some(PathBuf::from(rest));
+            } else if arg == "-M" || arg == "--print-map" {
+                print_map = true;
+            } else if arg == "-t" || arg == "--trace" {
+                trace = true;
+            } else if arg == "--why-live" {
+                why_live.extend(input.next().map(|a| a.as_str().to_owned()));
+            } else if let Some(rest) = arg.strip_prefix("--why-live=") {
+                why_live.push(rest.to_owned());
+            } else if arg == "-y" || arg == "--trace-symbol" {
+                trace_symbols.extend(input.next().map(|a| a.as_str().to_owned()));
+            } else if let Some(rest) = arg.strip_prefix("--trace-symbol=") {
+                trace_symbols.push(rest.to_owned());
+            } else if arg == "--gc-sections" {
+                gc_sections = true;
+            } else if arg == "--no-gc-sections" {
+                gc_sections = false;
+            } else if arg == "--print-gc-sections" {
+                print_gc_sections = true;
+            } else if arg == "--no-print-gc-sections" {
+                print_gc_sections = false;
+            } else if arg == "--fork" {
+                should_fork = true;
+            } else if arg == "--no-fork" {
+                should_fork = false;
             } else if arg == "--help" {
-                bail!("Sorry, help isn't implemented yet");
+                return Ok(Action::PrintHelp);
+            } else if arg == "--version" {
+                return Ok(Action::PrintVersion);
+            } else if arg == "-v" {
+                print_version = true;
+            } else if arg == "--fatal-warnings" {
+                diagnostics.fatal_warnings = true;
+            } else if arg == "--no-fatal-warnings" {
+                diagnostics.fatal_warnings = false;
+            } else if arg == "--no-warnings" || arg == "-w" {
+                diagnostics.no_warnings = true;
+            } else if let Some(rest) = arg.strip_prefix("--error-limit=") {
+                diagnostics.error_limit = rest
+                    .parse()
+                    .with_context(|| format!("Invalid --error-limit `{rest}`"))?;
+            } else if arg == "--color-diagnostics" {
+                diagnostics.color = ColorChoice::Always;
+            } else if let Some(rest) = arg.strip_prefix("--color-diagnostics=") {
+                diagnostics.color = ColorChoice::parse(rest)?;
+            } else if arg == "--no-color-diagnostics" {
+                diagnostics.color = ColorChoice::Never;
+            } else if UNSUPPORTED_FLAGS.contains(&arg) || arg.starts_with("--build-id=") {
+                diagnostics.warning(format_args!("ignoring unsupported flag `{arg}`"));
             } else if IGNORED_FLAGS.contains(&arg) {
             } else if arg.starts_with('-') {
                 bail!("Unrecognised argument `{arg}`");
@@ -171,15 +654,31 @@ impl Args {
                 inputs.push(Input {
                     spec: InputSpec::File(Box::from(Path::new(arg))),
                     search_first: None,
+                    modifiers,
                 });
             }
         }
-        let num_threads = num_threads.unwrap_or_else(|| {
-            std::thread::available_parallelism().unwrap_or(NonZeroUsize::new(1).unwrap())
-        });
-        save_dir.finish()?;
-        Ok(Args {
+        // Like ld, `-v` on its own just prints our version, but with inputs, we continue on to link.
+        if print_version && inputs.is_empty() {
+            return Ok(Action::PrintVersion);
+        }
+        if !nostdlib && sysroot.is_some() {
+            lib_search_path.extend_from_slice(DEFAULT_SEARCH_PATH);
+        }
+        let lib_search_path = lib_search_path
+            .into_iter()
+            .map(|dir| {
+                Box::from(
+                    apply_sysroot(dir, sysroot.as_deref())
+                        .unwrap_or_else(|| PathBuf::from(dir))
+                        .as_path(),
+                )
+            })
+            .collect();
+        save_dir.finish(all_args.get(1..).unwrap_or_default())?;
+        Ok(Action::Link(Box::new(Args {
             lib_search_path,
+            sysroot,
             inputs,
             output: output.ok_or_else(|| anyhow!("Missing required argument -o"))?,
             dynamic_linker,
@@ -190,20 +689,57 @@ impl Args {
             prepopulate_maps,
             sym_info,
             merge_strings,
+            tail_merge_strings,
             debug_fuel,
             pie,
-        })
+            shared,
+            soname,
+            version_script,
+            b_symbolic,
+            relro,
+            allow_multiple_definitions,
+            entry,
+            defsym,
+            undefined,
+            require_defined,
+            wrap,
+            diagnostics,
+            should_fork,
+            map_file,
+            print_map,
+            trace,
+            why_live,
+            trace_symbols,
+            gc_sections,
+            print_gc_sections,
+            print_version,
+        })))
     }
+}
 
-    pub(crate) fn setup_thread_pool(&self) -> Result {
+impl Args {
+    /// Sets up our thread pool. If we acquired tokens from a jobserver in order to do so, they're
+    /// returned and should be kept until we're done.
+    pub(crate) fn setup_thread_pool(&self) -> Result<Option<jobserver::Tokens>> {
+        let (num_threads, tokens) = match self.num_threads {
+            Some(num_threads) => (num_threads.get(), None),
+            None => {
+                let available = std::thread::available_parallelism().map_or(1, |n| n.get());
+                match jobserver::acquire_from_env(available - 1) {
+                    // We always have one implicit token in addition to those that we acquired.
+                    Some(tokens) => (tokens.count() + 1, Some(tokens)),
+                    None => (available, None),
+                }
+            }
+        };
         rayon::ThreadPoolBuilder::new()
-            .num_threads(self.num_threads.get())
+            .num_threads(num_threads)
             .build_global()?;
-        Ok(())
+        Ok(tokens)
     }
 
     pub(crate) fn base_address(&self) -> u64 {
-        if self.pie {
+        if self.is_relocatable() {
             0
         } else {
             crate::elf::NON_PIE_START_MEM_ADDRESS
@@ -245,15 +781,232 @@ impl Args {
     }
 
     pub(crate) fn is_relocatable(&self) -> bool {
-        self.pie
+        self.pie || self.shared
+    }
+
+    /// Returns the `--defsym` definition of the symbol called `name`, if any. If a symbol is
+    /// defined more than once, the first definition is the one that takes effect.
+    pub(crate) fn defsym_named(&self, name: &[u8]) -> Option<&Defsym> {
+        self.defsym
+            .iter()
+            .find(|defsym| defsym.name.as_bytes() == name)
+    }
+}
+
+/// Returns what we print for `--version`. Tools such as meson look for "compatible with GNU linkers"
+/// in order to decide what flags we accept.
+pub(crate) fn version_text() -> String {
+    format!(
+        "Wild {} (compatible with GNU linkers)",
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+pub(crate) fn help_text() -> String {
+    const FLAGS_WIDTH: usize = 30;
+    let mut out = String::from("Usage: wild [options] file...\nOptions:\n");
+    for (flags, description) in HELP {
+        if flags.len() < FLAGS_WIDTH {
+            out += &format!("  {flags:FLAGS_WIDTH$}{description}\n");
+        } else {
+            out += &format!("  {flags}\n  {:FLAGS_WIDTH$}{description}\n", "");
+        }
+    }
+    out += &format!(
+        "\nAccepted for compatibility, but ignored:\n  {}\n",
+        IGNORED_FLAGS.join(", ")
+    );
+    out += &format!(
+        "\nAccepted for compatibility, but unsupported (a warning is issued):\n  {}\n",
+        UNSUPPORTED_FLAGS.join(", ")
+    );
+    out
+}
+
+impl InputSpec {
+    /// Returns the spec for `-l<name>`. If `name` starts with a colon, then the remainder is an
+    /// exact filename to search for.
+    pub(crate) fn lib(name: &str) -> Self {
+        match name.strip_prefix(':') {
+            Some(filename) => InputSpec::Search(Box::from(filename)),
+            None => InputSpec::Lib(Box::from(name)),
+        }
+    }
+}
+
+/// If `path` starts with `=` or `$SYSROOT`, returns it with that prefix replaced by `sysroot`. If no
+/// sysroot was specified, the prefix is just removed. Returns None if `path` has no such prefix.
+pub(crate) fn apply_sysroot(path: &str, sysroot: Option<&Path>) -> Option<PathBuf> {
+    let rest = path
+        .strip_prefix('=')
+        .or_else(|| path.strip_prefix("$SYSROOT"))?;
+    Some(prefix_with_sysroot(Path::new(rest), sysroot))
+}
+
+/// Returns `path` with `sysroot` prepended. Unlike `Path::join`, this works when `path` is absolute.
+pub(crate) fn prefix_with_sysroot(path: &Path, sysroot: Option<&Path>) -> PathBuf {
+    let Some(sysroot) = sysroot else {
+        return path.to_owned();
+    };
+    let mut out = sysroot.as_os_str().to_owned();
+    out.push(path.as_os_str());
+    PathBuf::from(out)
+}
+
+/// The maximum depth to which response files may include other response files. This stops us from
+/// recursing forever if a response file includes itself.
+const MAX_RESPONSE_FILE_DEPTH: u32 = 32;
+
+/// Replaces any arguments of the form `@path` with the arguments contained in the file at `path`,
+/// recursively. As with GNU ld, if the file can't be read, the argument is left as-is.
+fn expand_response_files<S: AsRef<str>, I: Iterator<Item = S>>(input: I) -> Result<Vec<String>> {
+    let mut out = Vec::new();
+    for arg in input {
+        expand_response_file_arg(arg.as_ref(), &mut out, 0)?;
+    }
+    Ok(out)
+}
+
+fn expand_response_file_arg(arg: &str, out: &mut Vec<String>, depth: u32) -> Result {
+    let Some(path) = arg.strip_prefix('@') else {
+        out.push(arg.to_owned());
+        return Ok(());
+    };
+    let Ok(text) = std::fs::read_to_string(path) else {
+        out.push(arg.to_owned());
+        return Ok(());
+    };
+    if depth >= MAX_RESPONSE_FILE_DEPTH {
+        bail!("Response file `{path}` is nested too deeply");
+    }
+    for nested in split_response_file(&text) {
+        expand_response_file_arg(&nested, out, depth + 1)
+            .with_context(|| format!("Failed to expand response file `{path}`"))?;
+    }
+    Ok(())
+}
+
+/// Splits the contents of a response file into arguments. Arguments are separated by whitespace.
+/// Single and double quotes can be used to include whitespace in an argument and a backslash
+/// causes the following character to be included literally, even within quotes.
+fn split_response_file(text: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote = None;
+    let mut chars = text.chars();
+    while let Some(ch) = chars.next() {
+        match (ch, quote) {
+            ('\\', _) => {
+                current.extend(chars.next());
+                in_arg = true;
+            }
+            (ch, Some(q)) if ch == q => quote = None,
+            (ch, Some(_)) => current.push(ch),
+            ('\'' | '"', None) => {
+                quote = Some(ch);
+                in_arg = true;
+            }
+            (ch, None) if ch.is_whitespace() => {
+                if in_arg {
+                    args.push(core::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            (ch, None) => {
+                current.push(ch);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+    args
+}
+
+impl Defsym {
+    /// Parses `name=expression`, where expression is a possibly negative number, a symbol name or a
+    /// symbol name plus or minus a number. Anything else is an error.
+    fn parse(definition: &str) -> Result<Self> {
+        let (name, expression) = definition.split_once('=').with_context(|| {
+            format!("--defsym: Expected `symbol=expression`, got `{definition}`")
+        })?;
+        let name = name.trim();
+        let expression = expression.trim();
+        if name.is_empty() || expression.is_empty() {
+            bail!("--defsym: Expected `symbol=expression`, got `{definition}`");
+        }
+        let value = if let Some(value) = parse_number(expression) {
+            DefsymValue::Value(value)
+        } else if let Some(negated) = expression.strip_prefix('-') {
+            let value = parse_number(negated.trim())
+                .with_context(|| format!("--defsym: Invalid number `{expression}`"))?;
+            DefsymValue::Value(value.wrapping_neg())
+        } else {
+            let (target, offset) = match expression.find(['+', '-']) {
+                Some(split) => {
+                    let offset_text = expression[split + 1..].trim();
+                    let offset = parse_number(offset_text)
+                        .and_then(|offset| i64::try_from(offset).ok())
+                        .with_context(|| {
+                            format!("--defsym: Expected a number, got `{offset_text}`")
+                        })?;
+                    let offset = if expression.as_bytes()[split] == b'-' {
+                        -offset
+                    } else {
+                        offset
+                    };
+                    (expression[..split].trim(), offset)
+                }
+                None => (expression, 0),
+            };
+            if !is_symbol_name(target) {
+                bail!("--defsym: Expected a symbol name, got `{target}`");
+            }
+            DefsymValue::Symbol(target.to_owned(), offset)
+        };
+        Ok(Defsym {
+            name: name.to_owned(),
+            value,
+        })
+    }
+}
+
+/// Returns whether `text` is a symbol name that can appear unquoted in a `--defsym` expression.
+fn is_symbol_name(text: &str) -> bool {
+    text.bytes().next().is_some_and(|b| !b.is_ascii_digit())
+        && text
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'.' | b'$'))
+}
+
+/// Parses a number in the format accepted by ld for addresses: hexadecimal with a leading `0x`,
+/// octal with a leading `0`, otherwise decimal.
+pub(crate) fn parse_number(text: &str) -> Option<u64> {
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).ok()
+    } else if text.len() > 1 && text.starts_with('0') {
+        u64::from_str_radix(&text[1..], 8).ok()
+    } else {
+        text.parse().ok()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::args::InputSpec;
+    use std::collections::HashSet;
     use std::path::Path;
 
+    /// Parses `input`, which should be arguments for a link, rather than say `--help`.
+    fn parse<S: AsRef<str>, I: Iterator<Item = S>>(input: I) -> crate::error::Result<super::Args> {
+        match super::Action::parse(input)? {
+            super::Action::Link(args) => Ok(*args),
+            _ => anyhow::bail!("Expected arguments for a link"),
+        }
+    }
+
     const INPUT1: &[&str] = &[
         "wild",
         "-pie",
@@ -339,12 +1092,12 @@ mod tests {
 
     #[test]
     fn test_parse() {
-        let args = super::Args::parse(INPUT1.iter()).unwrap();
+        let args = parse(INPUT1.iter()).unwrap();
         assert_eq!(
             args.inputs
                 .iter()
                 .filter_map(|i| match &i.spec {
-                    InputSpec::File(_) => None,
+                    InputSpec::File(_) | InputSpec::Search(_) => None,
                     InputSpec::Lib(lib_name) => Some(lib_name.as_ref()),
                 })
                 .collect::<Vec<&str>>(),
@@ -356,5 +1109,629 @@ mod tests {
             InputSpec::File(f) => f.as_ref() == Path::new("/usr/bin/ld"),
             _ => false,
         }));
+        assert!(!args.shared);
+        assert_eq!(args.soname, None);
+        assert!(args.relro);
+        assert!(!args.allow_multiple_definitions);
+        assert_eq!(args.entry, None);
+    }
+
+    #[test]
+    fn test_parse_shared() {
+        let args = parse(
+            [
+                "wild",
+                "-shared",
+                "-soname",
+                "libfoo.so.1",
+                "-o",
+                "libfoo.so.1.0",
+            ]
+            .iter(),
+        )
+        .unwrap();
+        assert!(args.shared);
+        assert!(args.is_relocatable());
+        assert_eq!(args.soname.as_deref(), Some("libfoo.so.1"));
+        assert!(!args.b_symbolic);
+
+        let args = parse(
+            [
+                "wild",
+                "-shared",
+                "--soname=libbar.so",
+                "-Bsymbolic",
+                "-o",
+                "x",
+            ]
+            .iter(),
+        )
+        .unwrap();
+        assert_eq!(args.soname.as_deref(), Some("libbar.so"));
+        assert!(args.b_symbolic);
+    }
+
+    #[test]
+    fn test_parse_allow_multiple_definitions() {
+        for flags in [&["--allow-multiple-definition"][..], &["-z", "muldefs"][..]] {
+            let args = parse(["wild", "-o", "out"].iter().chain(flags).copied()).unwrap();
+            assert!(args.allow_multiple_definitions);
+        }
+    }
+
+    #[test]
+    fn test_parse_entry() {
+        for flags in [
+            &["-e", "my_start"][..],
+            &["--entry", "my_start"][..],
+            &["--entry=my_start"][..],
+        ] {
+            let args = parse(["wild", "-o", "out"].iter().chain(flags).copied()).unwrap();
+            assert_eq!(args.entry.as_deref(), Some("my_start"));
+        }
+    }
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(super::parse_number("0x401000"), Some(0x401000));
+        assert_eq!(super::parse_number("0X10"), Some(16));
+        assert_eq!(super::parse_number("010"), Some(8));
+        assert_eq!(super::parse_number("0"), Some(0));
+        assert_eq!(super::parse_number("4096"), Some(4096));
+        assert_eq!(super::parse_number("_start"), None);
+    }
+
+    #[test]
+    fn test_parse_defsym() {
+        use super::Defsym;
+        use super::DefsymValue;
+
+        let args = parse(
+            [
+                "wild",
+                "-o",
+                "out",
+                "--defsym=build_stamp=0x1234",
+                "--defsym",
+                "my_alias=real_fn",
+                "--defsym=after=table+8",
+                "--defsym=before=table-0x10",
+                "--defsym=negative=-5",
+            ]
+            .iter(),
+        )
+        .unwrap();
+        let defsym = |name: &str, value| Defsym {
+            name: name.to_owned(),
+            value,
+        };
+        assert_eq!(
+            args.defsym,
+            [
+                defsym("build_stamp", DefsymValue::Value(0x1234)),
+                defsym("my_alias", DefsymValue::Symbol("real_fn".to_owned(), 0)),
+                defsym("after", DefsymValue::Symbol("table".to_owned(), 8)),
+                defsym("before", DefsymValue::Symbol("table".to_owned(), -16)),
+                defsym("negative", DefsymValue::Value(-5_i64 as u64)),
+            ]
+        );
+        assert!(parse(["wild", "-o", "out", "--defsym=foo"].iter()).is_err());
+        // Only a number can be added to or subtracted from a symbol.
+        assert!(parse(["wild", "-o", "out", "--defsym=a=b-c"].iter()).is_err());
+        assert!(parse(["wild", "-o", "out", "--defsym=a=b+1-2"].iter()).is_err());
+        assert!(parse(["wild", "-o", "out", "--defsym=a=-b"].iter()).is_err());
+        assert!(parse(["wild", "-o", "out", "--defsym=a=1b"].iter()).is_err());
+    }
+
+    #[test]
+    fn test_parse_undefined() {
+        let args = parse(
+            [
+                "wild",
+                "-o",
+                "out",
+                "-u",
+                "a",
+                "--undefined=b",
+                "--undefined",
+                "c",
+                "--require-defined=d",
+                "--require-defined",
+                "e",
+            ]
+            .iter(),
+        )
+        .unwrap();
+        assert_eq!(args.undefined, ["a", "b", "c"]);
+        assert_eq!(args.require_defined, ["d", "e"]);
+    }
+
+    #[test]
+    fn test_parse_trace_and_why_live() {
+        let args = parse(
+            [
+                "wild",
+                "-o",
+                "out",
+                "-t",
+                "--why-live=foo*",
+                "--why-live",
+                "bar",
+            ]
+            .iter(),
+        )
+        .unwrap();
+        assert!(args.trace);
+        assert_eq!(args.why_live, ["foo*", "bar"]);
+    }
+
+    #[test]
+    fn test_parse_trace_symbol() {
+        let args = parse(
+            [
+                "wild",
+                "-o",
+                "out",
+                "-y",
+                "a",
+                "--trace-symbol=b",
+                "--trace-symbol",
+                "c",
+            ]
+            .iter(),
+        )
+        .unwrap();
+        assert_eq!(args.trace_symbols, ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_parse_gc_sections() {
+        let args = parse(["wild", "-o", "out"].iter()).unwrap();
+        assert!(args.gc_sections);
+        assert!(!args.print_gc_sections);
+        let args = parse(
+            [
+                "wild",
+                "-o",
+                "out",
+                "--gc-sections",
+                "--no-gc-sections",
+                "--print-gc-sections",
+            ]
+            .iter(),
+        )
+        .unwrap();
+        assert!(!args.gc_sections);
+        assert!(args.print_gc_sections);
+    }
+
+    #[test]
+    fn test_parse_optimisation_level() {
+        let args = parse(["wild", "-o", "out", "-O1"].iter()).unwrap();
+        assert!(!args.tail_merge_strings);
+        let args = parse(["wild", "-o", "out", "-O2"].iter()).unwrap();
+        assert!(args.tail_merge_strings);
+        let args = parse(["wild", "-O", "3", "-o", "out"].iter()).unwrap();
+        assert!(args.tail_merge_strings);
+        let args = parse(["wild", "-O2", "-Ofast", "-o", "out"].iter()).unwrap();
+        assert!(!args.tail_merge_strings);
+        assert_eq!(args.diagnostics.print_pending(), 1);
+    }
+
+    #[test]
+    fn test_parse_whole_archive() {
+        let args = parse(
+            [
+                "wild",
+                "-o",
+                "out",
+                "a.a",
+                "--whole-archive",
+                "b.a",
+                "-lc",
+                "--no-whole-archive",
+                "d.a",
+            ]
+            .iter(),
+        )
+        .unwrap();
+        assert_eq!(
+            args.inputs
+                .iter()
+                .map(|i| i.modifiers.whole_archive)
+                .collect::<Vec<_>>(),
+            [false, true, true, false]
+        );
+    }
+
+    #[test]
+    fn test_merge_modifiers() {
+        let plain = super::Modifiers::default();
+        let whole = super::Modifiers {
+            whole_archive: true,
+            ..Default::default()
+        };
+        let lazy = super::Modifiers {
+            archive_semantics: true,
+            ..Default::default()
+        };
+        assert!(plain.merge(whole).whole_archive);
+        assert!(whole.merge(plain).whole_archive);
+        assert!(!lazy.merge(plain).archive_semantics);
+        assert!(lazy.merge(lazy).archive_semantics);
+    }
+
+    #[test]
+    fn test_parse_start_lib() {
+        let args = parse(
+            [
+                "wild",
+                "-o",
+                "out",
+                "a.o",
+                "--start-lib",
+                "b.o",
+                "c.o",
+                "--end-lib",
+                "d.o",
+            ]
+            .iter(),
+        )
+        .unwrap();
+        assert_eq!(
+            args.inputs
+                .iter()
+                .map(|i| i.modifiers.archive_semantics)
+                .collect::<Vec<_>>(),
+            [false, true, true, false]
+        );
+        assert!(parse(["wild", "-o", "out", "--end-lib"].iter()).is_err());
+        assert!(parse(["wild", "-o", "out", "--start-lib", "--start-lib"].iter()).is_err());
+    }
+
+    #[test]
+    fn test_library_search_args() {
+        let args = parse(
+            [
+                "wild",
+                "-o",
+                "out",
+                "--sysroot=/sysroot",
+                "-L=/usr/lib",
+                "-L$SYSROOT/lib",
+                "-L",
+                "/opt/lib",
+                "-nostdlib",
+                "-lfoo",
+                "-Bstatic",
+                "-lbar",
+                "-l:libbaz.so.1",
+                "-Bdynamic",
+                "-lqux",
+            ]
+            .iter(),
+        )
+        .unwrap();
+        assert_eq!(
+            args.lib_search_path,
+            [
+                Path::new("/sysroot/usr/lib"),
+                Path::new("/sysroot/lib"),
+                Path::new("/opt/lib")
+            ]
+            .map(Box::<Path>::from)
+        );
+        assert_eq!(
+            args.inputs
+                .iter()
+                .map(|i| (&i.spec, i.modifiers.static_libs_only))
+                .collect::<Vec<_>>(),
+            [
+                (&InputSpec::Lib(Box::from("foo")), false),
+                (&InputSpec::Lib(Box::from("bar")), true),
+                (&InputSpec::Search(Box::from("libbaz.so.1")), true),
+                (&InputSpec::Lib(Box::from("qux")), false),
+            ]
+        );
+
+        let args = parse(["wild", "-o", "out", "-L", "/opt/lib"].iter()).unwrap();
+        assert_eq!(args.lib_search_path[0].as_ref(), Path::new("/opt/lib"));
+        assert!(args
+            .lib_search_path
+            .iter()
+            .any(|p| p.as_ref() == Path::new("/usr/lib")));
+    }
+
+    #[test]
+    fn test_default_library_search_path() {
+        // Without a sysroot, we only search directories that we were given.
+        let args = parse(["wild", "-o", "out", "-L/opt/lib"].iter()).unwrap();
+        assert_eq!(
+            args.lib_search_path,
+            [Path::new("/opt/lib")].map(Box::<Path>::from)
+        );
+
+        let args = parse(["wild", "-o", "out", "--sysroot=/sysroot", "-L/opt/lib"].iter()).unwrap();
+        assert_eq!(
+            args.lib_search_path.len(),
+            super::DEFAULT_SEARCH_PATH.len() + 1
+        );
+        assert_eq!(*args.lib_search_path[0], *Path::new("/opt/lib"));
+        assert_eq!(
+            *args.lib_search_path[1],
+            *Path::new("/sysroot/usr/local/lib/x86_64-linux-gnu")
+        );
+        assert!(args.lib_search_path[1..]
+            .iter()
+            .all(|dir| dir.starts_with("/sysroot")));
+    }
+
+    #[test]
+    fn test_push_pop_state() {
+        let args = parse(
+            [
+                "wild",
+                "-o",
+                "out",
+                "--as-needed",
+                "-la",
+                "--push-state",
+                "--no-as-needed",
+                "-Bstatic",
+                "--whole-archive",
+                "-lb",
+                "--pop-state",
+                "-lc",
+            ]
+            .iter(),
+        )
+        .unwrap();
+        let modifiers = args.inputs.iter().map(|i| i.modifiers).collect::<Vec<_>>();
+        let outer = super::Modifiers {
+            as_needed: true,
+            ..Default::default()
+        };
+        let inner = super::Modifiers {
+            whole_archive: true,
+            static_libs_only: true,
+            ..Default::default()
+        };
+        assert_eq!(modifiers, [outer, inner, outer]);
+
+        assert!(parse(["wild", "-o", "out", "--pop-state"].iter()).is_err());
+    }
+
+    #[test]
+    fn test_parse_diagnostics() {
+        let args = parse(
+            [
+                "wild",
+                "-o",
+                "out",
+                "--build-id",
+                "--fatal-warnings",
+                "--error-limit=5",
+                "--color-diagnostics=never",
+            ]
+            .iter(),
+        )
+        .unwrap();
+        assert!(args.diagnostics.fatal_warnings);
+        assert_eq!(args.diagnostics.error_limit, 5);
+        assert_eq!(
+            args.diagnostics.color,
+            crate::diagnostics::ColorChoice::Never
+        );
+        assert!(args.diagnostics.flush().is_err());
+
+        let args =
+            parse(["wild", "-o", "out", "--eh-frame-hdr", "--fatal-warnings"].iter()).unwrap();
+        assert!(args.diagnostics.flush().is_err());
+    }
+
+    #[test]
+    fn test_help_flags_are_accepted() {
+        let sample_value = |placeholder: &str| match placeholder {
+            "num" | "level" | "expression" => "1",
+            "when" => "auto",
+            "style" => "gnu",
+            "emulation" => "elf_x86_64",
+            "dir" => "/tmp",
+            _ => "foo",
+        };
+        for (flags, _) in super::HELP {
+            for alternative in flags.split(", ") {
+                if ["--help", "--version", "-v"].contains(&alternative) {
+                    continue;
+                }
+                let mut args = vec!["wild".to_owned(), "-o".to_owned(), "out".to_owned()];
+                // Some flags are only valid after another flag.
+                match alternative {
+                    "--end-lib" => args.push("--start-lib".to_owned()),
+                    "--pop-state" => args.push("--push-state".to_owned()),
+                    _ => {}
+                }
+                for part in alternative.split(' ') {
+                    let mut arg = part.to_owned();
+                    while let Some(start) = arg.find('<') {
+                        let end = arg[start..].find('>').unwrap() + start;
+                        arg.replace_range(start..=end, sample_value(&arg[start + 1..end]));
+                    }
+                    args.push(arg);
+                }
+                if let Err(error) = parse(args.iter()) {
+                    panic!("Failed to parse {args:?}: {error}");
+                }
+            }
+        }
+        assert!(super::help_text().contains("--whole-archive"));
+    }
+
+    /// The reverse of `test_help_flags_are_accepted`. Checks that every flag that our parser accepts
+    /// is mentioned in our help. When adding a flag to `Args::parse`, add it here too.
+    #[test]
+    fn test_parsed_flags_are_documented() {
+        const PARSED_FLAGS: &[&str] = &[
+            "-L",
+            "-l",
+            "-static",
+            "-Bstatic",
+            "-dn",
+            "-non_shared",
+            "-Bdynamic",
+            "-dy",
+            "-call_shared",
+            "-nostdlib",
+            "--sysroot",
+            "-o",
+            "-dynamic-linker",
+            "--hash-style",
+            "--time",
+            "--threads",
+            "--strip-all",
+            "-z",
+            "-m",
+            "-O",
+            "--prepopulate-maps",
+            "--sym-info",
+            "--no-string-merge",
+            "-pie",
+            "-shared",
+            "-Bshareable",
+            "-Bsymbolic",
+            "-soname",
+            "-h",
+            "--soname",
+            "-e",
+            "--entry",
+            "--defsym",
+            "-u",
+            "--undefined",
+            "--require-defined",
+            "--push-state",
+            "--pop-state",
+            "--as-needed",
+            "--no-as-needed",
+            "--whole-archive",
+            "--no-whole-archive",
+            "--start-lib",
+            "--end-lib",
+            "--wrap",
+            "--allow-multiple-definition",
+            "--version-script",
+            "--debug-fuel",
+            "-Map",
+            "--Map",
+            "-M",
+            "--print-map",
+            "-t",
+            "--trace",
+            "--why-live",
+            "-y",
+            "--trace-symbol",
+            "--gc-sections",
+            "--no-gc-sections",
+            "--print-gc-sections",
+            "--no-print-gc-sections",
+            "--fork",
+            "--no-fork",
+            "--help",
+            "--version",
+            "-v",
+            "--fatal-warnings",
+            "--no-fatal-warnings",
+            "--no-warnings",
+            "-w",
+            "--error-limit",
+            "--color-diagnostics",
+            "--no-color-diagnostics",
+            "--build-id",
+        ];
+        let documented: HashSet<&str> = super::HELP
+            .iter()
+            .flat_map(|(flags, _)| flags.split(", "))
+            .chain(super::IGNORED_FLAGS.iter().copied())
+            .chain(super::UNSUPPORTED_FLAGS.iter().copied())
+            .filter_map(|flag| flag.split([' ', '=', '<']).next())
+            .collect();
+        for flag in PARSED_FLAGS {
+            assert!(documented.contains(flag), "`{flag}` is missing from HELP");
+        }
+    }
+
+    #[test]
+    fn test_help_and_version() {
+        assert!(matches!(
+            super::Action::parse(["wild", "--help", "--bogus"].iter()).unwrap(),
+            super::Action::PrintHelp
+        ));
+        assert!(matches!(
+            super::Action::parse(["wild", "--version"].iter()).unwrap(),
+            super::Action::PrintVersion
+        ));
+        assert!(matches!(
+            super::Action::parse(["wild", "-v"].iter()).unwrap(),
+            super::Action::PrintVersion
+        ));
+        let args = parse(["wild", "-v", "-o", "out", "a.o"].iter()).unwrap();
+        assert!(args.print_version);
+    }
+
+    #[test]
+    fn test_split_response_file() {
+        assert_eq!(
+            super::split_response_file("-o out\n  'a b.o' \"c\\\"d\" e\\ f.o '' -lfoo\n"),
+            ["-o", "out", "a b.o", "c\"d", "e f.o", "", "-lfoo"]
+        );
+    }
+
+    #[test]
+    fn test_response_file() {
+        let dir = crate::fs::TestDir::new("response-file");
+        let inner = dir.path().join("inner");
+        let outer = dir.path().join("outer");
+        std::fs::write(&inner, "-shared -o out").unwrap();
+        std::fs::write(&outer, format!("@{} 'foo bar.o'", inner.display())).unwrap();
+        let args = parse(["wild".to_owned(), format!("@{}", outer.display())].iter()).unwrap();
+        assert!(args.shared);
+        assert_eq!(args.output.as_ref(), Path::new("out"));
+        assert_eq!(
+            args.inputs[0].spec,
+            InputSpec::File(Box::from(Path::new("foo bar.o")))
+        );
+    }
+
+    #[test]
+    fn test_parse_wrap() {
+        let args = parse(["wild", "-o", "out", "--wrap=malloc", "--wrap", "free"].iter()).unwrap();
+        assert_eq!(args.wrap, ["malloc", "free"]);
+    }
+
+    #[test]
+    fn test_parse_proc_macro() {
+        let args = parse(
+            [
+                "wild",
+                "-shared",
+                "-z",
+                "noexecstack",
+                "--version-script=/tmp/rustcXXXX/list",
+                "-o",
+                "/build/target/debug/deps/libfoo_macros-0123456789abcdef.so",
+                "-lstd-0123456789abcdef",
+                "-zrelro",
+                "-z",
+                "now",
+            ]
+            .iter(),
+        )
+        .unwrap();
+        assert!(args.shared);
+        assert!(args.relro);
+        assert!(!args.allow_multiple_definitions);
+        assert_eq!(
+            args.version_script.as_deref(),
+            Some(Path::new("/tmp/rustcXXXX/list"))
+        );
     }
 }
@@ -0,0 +1,203 @@
+//! Collection and reporting of warnings and other messages. Warnings can be issued from any phase
+//! of the link, including from multiple threads at once. They're held until we reach a point where
+//! we print them, which means that `--no-warnings` and `--fatal-warnings` apply regardless of where
+//! they appear on the command line relative to whatever caused the warning.
+
+use crate::error::Result;
+use anyhow::bail;
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::sync::Mutex;
+
+pub(crate) struct Diagnostics {
+    /// Messages that have been issued, but not yet printed.
+    pending: Mutex<Vec<Message>>,
+    /// Whether warnings should be treated as errors. Set by `--fatal-warnings`.
+    pub(crate) fatal_warnings: bool,
+    /// Whether warnings should be discarded. Set by `--no-warnings`. Takes precedence over
+    /// `fatal_warnings`.
+    pub(crate) no_warnings: bool,
+    /// The maximum number of errors to report. 0 means no limit.
+    pub(crate) error_limit: usize,
+    pub(crate) color: ColorChoice,
+}
+
+enum Message {
+    Warning(String),
+    /// Something that the user asked us to report, e.g. via `--print-gc-sections`. These aren't
+    /// affected by `--no-warnings` or `--fatal-warnings`.
+    Info(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ColorChoice {
+    /// Use colour if stderr is a terminal.
+    Auto,
+    Always,
+    Never,
+}
+
+/// The number of errors that we report if `--error-limit` isn't given. Same as lld.
+const DEFAULT_ERROR_LIMIT: usize = 20;
+
+impl Default for Diagnostics {
+    fn default() -> Self {
+        Self {
+            pending: Default::default(),
+            fatal_warnings: false,
+            no_warnings: false,
+            error_limit: DEFAULT_ERROR_LIMIT,
+            color: ColorChoice::Auto,
+        }
+    }
+}
+
+impl ColorChoice {
+    pub(crate) fn parse(value: &str) -> Result<Self> {
+        Ok(match value {
+            "auto" => ColorChoice::Auto,
+            "always" => ColorChoice::Always,
+            "never" => ColorChoice::Never,
+            other => bail!("Invalid --color-diagnostics value `{other}`"),
+        })
+    }
+}
+
+impl Diagnostics {
+    /// Records a warning to be printed later.
+    pub(crate) fn warning(&self, message: impl Display) {
+        self.pending
+            .lock()
+            .unwrap()
+            .push(Message::Warning(message.to_string()));
+    }
+
+    /// Records an informational message to be printed later, in order with any warnings.
+    pub(crate) fn info(&self, message: impl Display) {
+        self.pending
+            .lock()
+            .unwrap()
+            .push(Message::Info(message.to_string()));
+    }
+
+    /// Prints any warnings that have been issued since the last call. Returns an error if there
+    /// were any and warnings are fatal.
+    pub(crate) fn flush(&self) -> Result {
+        let num_printed = self.print_pending();
+        if num_printed > 0 && self.fatal_warnings {
+            bail!("{num_printed} warning(s) treated as errors due to --fatal-warnings");
+        }
+        Ok(())
+    }
+
+    /// Prints any messages that have been issued since the last call, returning how many warnings
+    /// were printed.
+    pub(crate) fn print_pending(&self) -> usize {
+        let pending = core::mem::take(&mut *self.pending.lock().unwrap());
+        let prefix = if self.fatal_warnings {
+            self.label("error", "1;31")
+        } else {
+            self.label("warning", "1;35")
+        };
+        let mut num_warnings = 0;
+        for message in &pending {
+            match message {
+                Message::Warning(_) if self.no_warnings => {}
+                Message::Warning(message) => {
+                    eprintln!("wild: {prefix} {message}");
+                    num_warnings += 1;
+                }
+                Message::Info(message) => eprintln!("wild: {message}"),
+            }
+        }
+        num_warnings
+    }
+
+    /// Joins `messages` into a single error message, keeping at most `error_limit` of them.
+    pub(crate) fn limit_errors(&self, mut messages: Vec<String>) -> String {
+        let limit = self.error_limit;
+        if limit != 0 && messages.len() > limit {
+            messages.truncate(limit);
+            messages.push(
+                "too many errors emitted, stopping now (use --error-limit=0 to see all errors)"
+                    .to_owned(),
+            );
+        }
+        messages.join("\n")
+    }
+
+    fn label(&self, label: &str, color_code: &str) -> String {
+        let use_color = match self.color {
+            ColorChoice::Auto => std::io::stderr().is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        };
+        if use_color {
+            format!("\x1b[{color_code}m{label}:\x1b[0m")
+        } else {
+            format!("{label}:")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Diagnostics;
+
+    #[test]
+    fn test_warnings() {
+        let diagnostics = Diagnostics::default();
+        diagnostics.warning("something odd");
+        diagnostics.warning(format_args!("and {}", "another"));
+        assert_eq!(diagnostics.print_pending(), 2);
+        assert_eq!(diagnostics.print_pending(), 0);
+
+        let diagnostics = Diagnostics {
+            fatal_warnings: true,
+            ..Default::default()
+        };
+        assert!(diagnostics.flush().is_ok());
+        diagnostics.warning("something odd");
+        assert!(diagnostics.flush().is_err());
+
+        let diagnostics = Diagnostics {
+            fatal_warnings: true,
+            no_warnings: true,
+            ..Default::default()
+        };
+        diagnostics.warning("something odd");
+        assert!(diagnostics.flush().is_ok());
+    }
+
+    #[test]
+    fn test_info() {
+        let diagnostics = Diagnostics {
+            fatal_warnings: true,
+            ..Default::default()
+        };
+        diagnostics.info("just so you know");
+        assert!(diagnostics.flush().is_ok());
+        diagnostics.info("just so you know");
+        diagnostics.warning("something odd");
+        assert_eq!(diagnostics.print_pending(), 1);
+    }
+
+    #[test]
+    fn test_limit_errors() {
+        let messages = || (0..5).map(|i| format!("error {i}")).collect::<Vec<_>>();
+        let diagnostics = Diagnostics {
+            error_limit: 2,
+            ..Default::default()
+        };
+        assert_eq!(
+            diagnostics.limit_errors(messages()),
+            "error 0\nerror 1\ntoo many errors emitted, stopping now (use --error-limit=0 to see \
+             all errors)"
+        );
+        let diagnostics = Diagnostics {
+            error_limit: 0,
+            ..Default::default()
+        };
+        assert_eq!(diagnostics.limit_errors(messages()).lines().count(), 5);
+    }
+}
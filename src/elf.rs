@@ -16,6 +16,12 @@ pub(crate) type Section<'data, 'file> =
 pub(crate) type Symbol<'data, 'file> =
     object::read::elf::ElfSymbol64<'data, 'file, LittleEndian, &'data [u8]>;
 
+/// Returns the size of each entry in `section`. For string-merge sections, this is the size of each
+/// character.
+pub(crate) fn section_entsize(section: &Section) -> u64 {
+    object::read::elf::SectionHeader::sh_entsize(section.elf_section_header(), LittleEndian)
+}
+
 /// The module number for TLS variables in the current executable.
 pub(crate) const CURRENT_EXE_TLS_MOD: u64 = 1;
 
@@ -173,6 +179,7 @@ pub(crate) enum SegmentType {
     Phdr = 6,
     Tls = 7,
     EhFrame = 0x6474e550,
+    GnuRelro = 0x6474e552,
 }
 
 #[allow(unused)]
@@ -1,4 +1,5 @@
 use crate::args::Args;
+use crate::args::DefsymValue;
 use crate::elf;
 use crate::elf::DynamicEntry;
 use crate::elf::DynamicTag;
@@ -14,6 +15,7 @@ use crate::elf::SymtabEntry;
 use crate::elf::PLT_ENTRY_TEMPLATE;
 use crate::error::Result;
 use crate::input_data::INTERNAL_FILE_ID;
+use crate::layout::DynamicLayout;
 use crate::layout::FileLayout;
 use crate::layout::HeaderInfo;
 use crate::layout::InternalLayout;
@@ -31,9 +33,12 @@ use crate::output_section_map::OutputSectionMap;
 use crate::output_section_part_map::OutputSectionPartMap;
 use crate::relaxation::Relaxation;
 use crate::resolution::LocalSymbolResolution;
+use crate::resolution::MergeSectionRef;
+use crate::resolution::MergedStringResolution;
 use crate::resolution::SectionSlot;
 use crate::slice::slice_take_prefix_mut;
 use crate::symbol_db::GlobalSymbolId;
+use crate::symbol_db::InternalSymDefInfo;
 use crate::symbol_db::SymbolDb;
 use ahash::AHashMap;
 use anyhow::anyhow;
@@ -45,6 +50,8 @@ use object::ObjectSection;
 use object::ObjectSymbol;
 use rayon::prelude::*;
 use std::fmt::Display;
+use std::num::NonZeroU32;
+use std::num::NonZeroU64;
 use std::ops::Range;
 use std::path::Path;
 use std::sync::mpsc::Receiver;
@@ -81,7 +88,7 @@ struct SectionAllocation {
 
 impl Output {
     pub(crate) fn new(args: &Args) -> Output {
-        if args.num_threads.get() > 1 {
+        if rayon::current_num_threads() > 1 {
             let (sized_output_sender, sized_output_recv) = std::sync::mpsc::channel();
             Output {
                 path: args.output.clone(),
@@ -166,6 +173,9 @@ impl SizedOutput {
         // We consumed the .eh_frame_hdr section in `split_buffers_by_alignment` above, get a fresh copy.
         let mut section_buffers = split_output_into_sections(layout, &mut self.mmap);
         sort_eh_frame_hdr_entries(section_buffers.get_mut(output_section_id::EH_FRAME_HDR));
+        if layout.symbol_db.needs_dynamic() {
+            write_hash_table(&mut section_buffers)?;
+        }
         crate::fs::make_executable(&self.file)
             .with_context(|| format!("Failed to make `{}` executable", self.path.display()))?;
         Ok(())
@@ -242,6 +252,52 @@ fn sort_eh_frame_hdr_entries(eh_frame_hdr: &mut [u8]) {
     entries.sort_by_key(|e| e.frame_ptr);
 }
 
+/// Writes .hash. This is done once .dynsym and .dynstr have been fully written, since every symbol
+/// needs to be placed in a bucket according to the hash of its name.
+fn write_hash_table(section_buffers: &mut OutputSectionMap<&mut [u8]>) -> Result {
+    let dynsym: &[SymtabEntry] = bytemuck::cast_slice(core::mem::take(
+        section_buffers.get_mut(output_section_id::DYNSYM),
+    ));
+    let dynstr: &[u8] = core::mem::take(section_buffers.get_mut(output_section_id::DYNSTR));
+    let hash: &mut [u32] = bytemuck::cast_slice_mut(core::mem::take(
+        section_buffers.get_mut(output_section_id::HASH),
+    ));
+    let num_symbols = dynsym.len();
+    let Some(num_buckets) = hash.len().checked_sub(2 + num_symbols).filter(|n| *n > 0) else {
+        bail!("Incorrect allocation for .hash");
+    };
+    let (header, rest) = hash.split_at_mut(2);
+    let (buckets, chains) = rest.split_at_mut(num_buckets);
+    header[0] = num_buckets as u32;
+    header[1] = num_symbols as u32;
+    buckets.fill(0);
+    chains.fill(0);
+    for (index, sym) in dynsym.iter().enumerate().skip(1) {
+        let name = dynstr
+            .get(sym.name as usize..)
+            .and_then(|s| s.split(|b| *b == 0).next())
+            .context("Dynamic symbol name outside of .dynstr")?;
+        let bucket = &mut buckets[elf_hash(name) as usize % num_buckets];
+        chains[index] = *bucket;
+        *bucket = index as u32;
+    }
+    Ok(())
+}
+
+/// The hash function used by the SysV .hash section.
+fn elf_hash(name: &[u8]) -> u32 {
+    let mut h: u32 = 0;
+    for &b in name {
+        h = (h << 4).wrapping_add(u32::from(b));
+        let g = h & 0xf000_0000;
+        if g != 0 {
+            h ^= g >> 24;
+        }
+        h &= !g;
+    }
+    h
+}
+
 /// Splits the writable buffers for each segment further into separate buffers for each alignment.
 fn split_buffers_by_alignment<'out>(
     section_buffers: &'out mut OutputSectionMap<&mut [u8]>,
@@ -280,7 +336,7 @@ fn write_program_headers(program_headers_out: &mut ProgramHeaderWriter, layout:
 impl FileHeader {
     fn build(layout: &Layout, header_info: &HeaderInfo) -> Result<Self> {
         let args = layout.args();
-        let ty = if args.pie {
+        let ty = if args.is_relocatable() {
             elf::FileType::SharedObject
         } else {
             elf::FileType::Executable
@@ -320,7 +376,7 @@ impl<'data> FileLayout<'data> {
         match self {
             Self::Object(s) => s.write(buffers, layout)?,
             Self::Internal(s) => s.write(buffers, layout)?,
-            Self::Dynamic(_) => {}
+            Self::Dynamic(s) => s.write(buffers, layout)?,
         }
         Ok(())
     }
@@ -357,7 +413,6 @@ impl<'data, 'out> PltGotWriter<'data, 'out> {
             Some(SymbolResolution::Resolved(res)) => {
                 self.process_resolution(res, relocation_writer)?;
             }
-            Some(SymbolResolution::Dynamic) => {}
             None => {}
         }
         Ok(())
@@ -372,29 +427,61 @@ impl<'data, 'out> PltGotWriter<'data, 'out> {
             if self.got.is_empty() {
                 bail!("Didn't allocate enough space in GOT");
             }
+            if let Some(dynamic_symbol_index) = res.dynamic_symbol_index {
+                return self.process_dynamic_resolution(
+                    res,
+                    got_address,
+                    dynamic_symbol_index,
+                    relocation_writer,
+                );
+            }
 
-            let mut needs_relocation = relocation_writer.is_active;
+            let mut needs_relocation = relocation_writer.is_active && !res.is_absolute;
             let address = match res.kind {
                 TargetResolutionKind::GotTlsDouble => {
                     let mod_got_entry = slice_take_prefix_mut(&mut self.got, 1);
-                    mod_got_entry.copy_from_slice(&[elf::CURRENT_EXE_TLS_MOD]);
+                    if self.layout.args().shared {
+                        // Our module ID isn't known until runtime, so the dynamic loader needs to
+                        // fill it in.
+                        mod_got_entry[0] = 0;
+                        relocation_writer.write_symbolless_relocation(
+                            got_address.get(),
+                            elf::rel::R_X86_64_DTPMOD64,
+                            0,
+                        )?;
+                    } else {
+                        mod_got_entry.copy_from_slice(&[elf::CURRENT_EXE_TLS_MOD]);
+                    }
                     let offset_entry = slice_take_prefix_mut(&mut self.got, 1);
-                    // Convert the address to an offset relative to the TCB which is the end of the TLS
-                    // segment.
-                    offset_entry[0] = res.address.wrapping_sub(self.tls.end);
+                    // Convert the address to an offset within our TLS block, which is what
+                    // __tls_get_addr expects. Unlike the TP-relative offsets that we compute for
+                    // GotTlsOffset, this is relative to the start of the block, since
+                    // __tls_get_addr adds it to the address of the start of the module's block.
+                    offset_entry[0] = res.address.wrapping_sub(self.tls.start);
                     return Ok(());
                 }
                 TargetResolutionKind::GotTlsOffset => {
                     needs_relocation = false;
-                    // Convert the address to an offset relative to the TCB which is the end of the TLS
-                    // segment.
                     if !self.tls.contains(&res.address) {
                         bail!(
                             "GotTlsOffset resolves to address not in TLS segment 0x{:x}",
                             res.address
                         );
                     }
-                    res.address.wrapping_sub(self.tls.end)
+                    if self.layout.args().shared {
+                        // Where our TLS block ends up relative to the TCB is only known at
+                        // runtime, so have the dynamic loader compute the offset.
+                        relocation_writer.write_symbolless_relocation(
+                            got_address.get(),
+                            elf::rel::R_X86_64_TPOFF64,
+                            res.address.wrapping_sub(self.tls.start),
+                        )?;
+                        0
+                    } else {
+                        // Convert the address to an offset relative to the TCB which is the end of
+                        // the TLS segment.
+                        res.address.wrapping_sub(self.tls.end)
+                    }
                 }
                 TargetResolutionKind::IFunc => {
                     needs_relocation = false;
@@ -413,14 +500,64 @@ impl<'data, 'out> PltGotWriter<'data, 'out> {
                     bail!("Didn't allocate enough space in PLT");
                 }
                 let plt_entry = slice_take_prefix_mut(&mut self.plt, elf::PLT_ENTRY_SIZE as usize);
-                plt_entry.copy_from_slice(PLT_ENTRY_TEMPLATE);
-                let offset: i32 = ((got_address.get().wrapping_sub(plt_address.get() + 0xb))
-                    as i64)
-                    .try_into()
-                    .map_err(|_| anyhow!("PLT is more than 2GB away from GOT"))?;
-                plt_entry[7..11].copy_from_slice(&offset.to_le_bytes());
+                write_plt_entry(plt_entry, got_address.get(), plt_address.get())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes GOT and PLT entries for a symbol that the dynamic loader binds at runtime. The GOT
+    /// entries are left as zero for the dynamic loader to fill in.
+    fn process_dynamic_resolution(
+        &mut self,
+        res: &Resolution,
+        got_address: NonZeroU64,
+        dynamic_symbol_index: NonZeroU32,
+        relocation_writer: &mut RelocationWriter,
+    ) -> Result {
+        let place = got_address.get();
+        match res.kind {
+            TargetResolutionKind::GotTlsDouble => {
+                slice_take_prefix_mut(&mut self.got, 2).fill(0);
+                relocation_writer.write_symbol_relocation(
+                    place,
+                    dynamic_symbol_index,
+                    elf::rel::R_X86_64_DTPMOD64,
+                    0,
+                )?;
+                relocation_writer.write_symbol_relocation(
+                    place + elf::GOT_ENTRY_SIZE,
+                    dynamic_symbol_index,
+                    elf::rel::R_X86_64_DTPOFF64,
+                    0,
+                )?;
+            }
+            TargetResolutionKind::GotTlsOffset => {
+                slice_take_prefix_mut(&mut self.got, 1)[0] = 0;
+                relocation_writer.write_symbol_relocation(
+                    place,
+                    dynamic_symbol_index,
+                    elf::rel::R_X86_64_TPOFF64,
+                    0,
+                )?;
+            }
+            _ => {
+                slice_take_prefix_mut(&mut self.got, 1)[0] = 0;
+                relocation_writer.write_symbol_relocation(
+                    place,
+                    dynamic_symbol_index,
+                    elf::rel::R_X86_64_GLOB_DAT,
+                    0,
+                )?;
             }
         }
+        if let Some(plt_address) = res.plt_address {
+            if self.plt.is_empty() {
+                bail!("Didn't allocate enough space in PLT");
+            }
+            let plt_entry = slice_take_prefix_mut(&mut self.plt, elf::PLT_ENTRY_SIZE as usize);
+            write_plt_entry(plt_entry, place, plt_address.get())?;
+        }
         Ok(())
     }
 
@@ -596,6 +733,7 @@ impl<'data> ObjectLayout<'data> {
                     )
                 })?;
         }
+        self.write_exported_symbols(&mut buffers, layout)?;
         if !layout.args().strip_all {
             self.write_symbols(start_str_offset, buffers, &layout.output_sections, layout)?;
         }
@@ -647,6 +785,81 @@ impl<'data> ObjectLayout<'data> {
         Ok(())
     }
 
+    /// Writes .dynsym entries for the symbols that we export when producing a shared object.
+    fn write_exported_symbols(
+        &self,
+        buffers: &mut OutputSectionPartMap<&mut [u8]>,
+        layout: &Layout,
+    ) -> Result {
+        let mut dynsym: &mut [SymtabEntry] =
+            bytemuck::cast_slice_mut(core::mem::take(&mut buffers.dynsym));
+        let mut dynstr: &mut [u8] = core::mem::take(&mut buffers.dynstr);
+        let mut next_string_offset = self.dynstr_start_offset;
+        for &symbol_id in &self.exported_symbols {
+            let local_index = layout
+                .symbol_db
+                .symbol(symbol_id)
+                .local_index_for_file(self.file_id)?;
+            let sym = self.object.symbol_by_index(local_index)?;
+            let Some(SymbolResolution::Resolved(res)) = layout.global_symbol_resolution(symbol_id)
+            else {
+                bail!(
+                    "Exported symbol `{}` wasn't resolved",
+                    layout.symbol_db.symbol_name(symbol_id)
+                );
+            };
+            let output_section_id = match sym.section() {
+                object::SymbolSection::Section(section_index) => {
+                    match (
+                        &self.sections[section_index.0],
+                        self.local_symbol_resolutions[local_index.0],
+                    ) {
+                        (SectionSlot::Loaded(section), _) => section.output_section_id,
+                        (_, LocalSymbolResolution::MergedString(res)) => {
+                            Some(res.output_section_id)
+                        }
+                        _ => None,
+                    }
+                }
+                object::SymbolSection::Common => Some(output_section_id::BSS),
+                _ => None,
+            };
+            let shndx = match output_section_id {
+                Some(id) => layout
+                    .output_sections
+                    .output_index_of_section(id)
+                    .context("Exported symbol is in a section that's not being output")?,
+                None if sym.section() == object::SymbolSection::Absolute => object::elf::SHN_ABS,
+                None => bail!(
+                    "Exported symbol `{}` isn't in a loaded section",
+                    layout.symbol_db.symbol_name(symbol_id)
+                ),
+            };
+            let object::SymbolFlags::Elf { st_info, st_other } = sym.flags() else {
+                unreachable!()
+            };
+            let name = sym.name_bytes()?;
+            *crate::slice::take_first_mut(&mut dynsym)
+                .context("Insufficient .dynsym allocation")? = SymtabEntry {
+                name: u32::try_from(next_string_offset).context(".dynstr overflowed 32 bits")?,
+                info: st_info,
+                other: st_other,
+                shndx,
+                value: res.address,
+                size: sym.size(),
+            };
+            write_string(&mut dynstr, name)?;
+            next_string_offset += name.len() as u64 + 1;
+        }
+        if !dynsym.is_empty() || !dynstr.is_empty() {
+            bail!(
+                "Allocated more space than was used for exports from {}",
+                self.input
+            );
+        }
+        Ok(())
+    }
+
     fn write_symbols(
         &self,
         start_str_offset: u32,
@@ -907,7 +1120,6 @@ impl<'data> ObjectLayout<'data> {
                     LocalSymbolResolution::Global(symbol_id) => {
                         match layout.global_symbol_resolution(symbol_id) {
                             Some(SymbolResolution::Resolved(resolution)) => *resolution,
-                            Some(SymbolResolution::Dynamic) => todo!(),
                             None => {
                                 bail!(
                                     "Missing resolution for non-weak symbol {}",
@@ -919,38 +1131,55 @@ impl<'data> ObjectLayout<'data> {
                     LocalSymbolResolution::WeakRefToGlobal(symbol_id) => {
                         match layout.global_symbol_resolution(symbol_id) {
                             Some(SymbolResolution::Resolved(resolution)) => *resolution,
-                            Some(SymbolResolution::Dynamic) => todo!(),
                             None => layout.internal().undefined_symbol_resolution,
                         }
                     }
                     LocalSymbolResolution::LocalSection(local_index) => {
-                        let mut r = self.section_resolutions[local_index.0].with_context(|| {
-                            format!(
-                                "Reference to section that hasn't been resolved {}",
-                                self.display_section_name(local_index)
-                            )
-                        })?;
                         let local_sym = self.object.symbol_by_index(local_symbol_id)?;
-                        r.address += local_sym.address();
-                        r
+                        if let Some(mut r) = self.section_resolutions[local_index.0] {
+                            r.address += local_sym.address();
+                            r
+                        } else {
+                            let input_offset =
+                                MergeSectionRef::referenced_offset(local_sym.address(), rel)?;
+                            let merged = self
+                                .merge_section_ref_resolution(local_index, input_offset)
+                                .with_context(|| {
+                                    format!(
+                                        "Reference to section that hasn't been resolved {}",
+                                        self.display_section_name(local_index)
+                                    )
+                                })?;
+                            // The string that the reference points into may have moved relative to
+                            // the other strings in the section, so we resolve the referenced byte,
+                            // then take off its offset from the symbol. That offset is the addend,
+                            // plus any PC bias, and the addend gets added back when the relocation
+                            // is applied.
+                            Resolution {
+                                address: layout
+                                    .merged_string_start_addresses
+                                    .resolve(merged)
+                                    .wrapping_sub(input_offset.wrapping_sub(local_sym.address())),
+                                got_address: None,
+                                plt_address: None,
+                                kind: TargetResolutionKind::Address,
+                                is_absolute: false,
+                                dynamic_symbol_index: None,
+                            }
+                        }
                     }
                     LocalSymbolResolution::UnresolvedWeak => {
                         layout.internal().undefined_symbol_resolution
                     }
                     LocalSymbolResolution::TlsGetAddr => return Ok(None),
                     LocalSymbolResolution::UndefinedSymbol => {
-                        let name = self.object.symbol_by_index(local_symbol_id)?.name_bytes()?;
-                        bail!(
-                            "Reference to undefined symbol `{}`",
-                            String::from_utf8_lossy(name),
-                        );
+                        unreachable!("References to undefined symbols are reported during layout")
                     }
                     LocalSymbolResolution::Null => bail!("Reference to null symbol"),
                     LocalSymbolResolution::MergedString(res) => {
                         if let Some(symbol_id) = res.symbol_id {
                             match layout.global_symbol_resolution(symbol_id) {
                                 Some(SymbolResolution::Resolved(resolution)) => *resolution,
-                                Some(SymbolResolution::Dynamic) => todo!(),
                                 None => {
                                     bail!(
                                         "Missing resolution for global string-merge symbol {}",
@@ -964,6 +1193,8 @@ impl<'data> ObjectLayout<'data> {
                                 got_address: None,
                                 plt_address: None,
                                 kind: TargetResolutionKind::Address,
+                                is_absolute: false,
+                                dynamic_symbol_index: None,
                             }
                         }
                     }
@@ -977,6 +1208,22 @@ impl<'data> ObjectLayout<'data> {
         Ok(Some(resolution))
     }
 
+    /// Returns where the byte at `input_offset` in the specified string-merge section ended up, if
+    /// it was referenced via the section's symbol.
+    fn merge_section_ref_resolution(
+        &self,
+        section_index: object::SectionIndex,
+        input_offset: u64,
+    ) -> Option<MergedStringResolution> {
+        let refs = &self.merge_section_refs;
+        let index = refs
+            .binary_search_by_key(&(section_index.0, input_offset), |r| {
+                (r.section_index.0, r.input_offset)
+            })
+            .ok()?;
+        refs[index].resolution
+    }
+
     fn display_section_name(&self, section_index: object::SectionIndex) -> String {
         if let Ok(section) = self.object.section_by_index(section_index) {
             if let Ok(name) = section.name() {
@@ -1071,14 +1318,53 @@ struct RelocationWriter<'out> {
     /// output file.
     is_active: bool,
     rela_dyn: &'out mut [crate::elf::Rela],
+    /// Relocations other than R_X86_64_RELATIVE. These go after all the relative relocations.
+    rela_dyn_general: &'out mut [crate::elf::Rela],
 }
 
 impl<'out> RelocationWriter<'out> {
     fn new(is_active: bool, buffers: &mut OutputSectionPartMap<&'out mut [u8]>) -> Self {
         Self {
             is_active,
-            rela_dyn: bytemuck::cast_slice_mut(core::mem::take(&mut buffers.rela_dyn)),
+            rela_dyn: bytemuck::cast_slice_mut(core::mem::take(&mut buffers.rela_dyn_relative)),
+            rela_dyn_general: bytemuck::cast_slice_mut(core::mem::take(
+                &mut buffers.rela_dyn_general,
+            )),
+        }
+    }
+
+    /// Writes a dynamic relocation of type `r_type` that doesn't reference any symbol. Used for
+    /// TLS relocations where the value depends on where the dynamic loader puts our TLS block.
+    fn write_symbolless_relocation(&mut self, place: u64, r_type: u32, addend: u64) -> Result {
+        if !self.is_active {
+            return Ok(());
+        }
+        let rela = crate::slice::take_first_mut(&mut self.rela_dyn_general)
+            .context("insufficient allocation to .rela.dyn")?;
+        rela.address = place;
+        rela.addend = addend;
+        rela.info = r_type.into();
+        Ok(())
+    }
+
+    /// Writes a dynamic relocation of type `r_type` against the symbol at `dynamic_symbol_index` in
+    /// .dynsym. Used for references to symbols that the dynamic loader binds at runtime.
+    fn write_symbol_relocation(
+        &mut self,
+        place: u64,
+        dynamic_symbol_index: NonZeroU32,
+        r_type: u32,
+        addend: u64,
+    ) -> Result {
+        if !self.is_active {
+            return Ok(());
         }
+        let rela = crate::slice::take_first_mut(&mut self.rela_dyn_general)
+            .context("insufficient allocation to .rela.dyn")?;
+        rela.address = place;
+        rela.addend = addend;
+        rela.info = (u64::from(dynamic_symbol_index.get()) << 32) | u64::from(r_type);
+        Ok(())
     }
 
     fn write_relocation(&mut self, place: u64, address: u64) -> Result {
@@ -1097,16 +1383,17 @@ impl<'out> RelocationWriter<'out> {
         Self {
             is_active: false,
             rela_dyn: Default::default(),
+            rela_dyn_general: Default::default(),
         }
     }
 
     fn validate_empty(&self) -> Result {
-        if self.rela_dyn.is_empty() {
+        if self.rela_dyn.is_empty() && self.rela_dyn_general.is_empty() {
             return Ok(());
         }
         bail!(
             "Allocated too much space in .rela.dyn. {} unused entries remain.",
-            self.rela_dyn.len()
+            self.rela_dyn.len() + self.rela_dyn_general.len()
         );
     }
 }
@@ -1131,8 +1418,12 @@ fn apply_relocation(
     let object::RelocationFlags::Elf { mut r_type } = rel.flags() else {
         unreachable!();
     };
-    if let Some(relaxation) = Relaxation::new(r_type, out, offset_in_section as usize) {
-        let value_is_relocatable = address != 0 && layout.args().is_relocatable();
+    // Relaxations bypass the GOT, which we can't do if the address won't be known until runtime.
+    let relaxation = Relaxation::new(r_type, out, offset_in_section as usize)
+        .filter(|_| resolution.dynamic_symbol_index.is_none());
+    if let Some(relaxation) = relaxation {
+        let value_is_relocatable =
+            address != 0 && !resolution.is_absolute && layout.args().is_relocatable();
         r_type = relaxation.new_relocation_kind(value_is_relocatable);
         relaxation.apply(out, offset_in_section as usize, value_is_relocatable);
         if !value_is_relocatable {
@@ -1143,14 +1434,37 @@ fn apply_relocation(
     debug_assert!(rel.size() == 0 || rel.size() as usize / 8 == rel_info.byte_size);
     let value = match rel_info.kind {
         RelocationKind::Absolute => {
-            if relocation_writer.is_active && address != 0 {
-                relocation_writer.write_relocation(place, address)?;
+            if let Some(dynamic_symbol_index) = resolution.dynamic_symbol_index {
+                if rel_info.byte_size != 8 {
+                    bail!(
+                        "Can't apply a {}-byte absolute relocation to a symbol that's bound at \
+                         runtime. Recompile with -fPIC",
+                        rel_info.byte_size
+                    );
+                }
+                relocation_writer.write_symbol_relocation(
+                    place,
+                    dynamic_symbol_index,
+                    elf::rel::R_X86_64_64,
+                    addend,
+                )?;
+                0
+            } else if relocation_writer.is_active && address != 0 && !resolution.is_absolute {
+                relocation_writer.write_relocation(place, address.wrapping_add(addend))?;
                 0
             } else {
                 address.wrapping_add(addend)
             }
         }
-        RelocationKind::Relative => address.wrapping_add(addend).wrapping_sub(place),
+        RelocationKind::Relative => {
+            if resolution.dynamic_symbol_index.is_some() {
+                bail!(
+                    "Can't apply a PC-relative relocation to a symbol that's bound at runtime. \
+                     Recompile with -fPIC"
+                );
+            }
+            address.wrapping_add(addend).wrapping_sub(place)
+        }
         RelocationKind::GotRelative => resolution
             .got_address()?
             .wrapping_add(addend)
@@ -1216,7 +1530,9 @@ fn apply_relocation(
                     .wrapping_sub(layout.tls_end_address())
                     .wrapping_add(addend)
             } else {
-                todo!()
+                address
+                    .wrapping_sub(layout.tls_start_address())
+                    .wrapping_add(addend)
             }
         }
         RelocationKind::GotTpOff => resolution
@@ -1279,10 +1595,18 @@ impl<'data> InternalLayout<'data> {
 
         self.write_merged_strings(&mut buffers);
 
-        if layout.args().pie {
+        if layout.symbol_db.needs_dynamic() {
+            self.write_dynamic_symbols(&mut buffers, layout)?;
             self.write_dynamic_entries(buffers.dynamic, layout)?;
         }
 
+        if let Some(dynamic_linker) = self.dynamic_linker.as_ref() {
+            let interp = buffers.regular_mut(output_section_id::INTERP, crate::alignment::MIN);
+            if !interp.is_empty() {
+                interp.copy_from_slice(dynamic_linker.as_bytes_with_nul());
+            }
+        }
+
         relocation_writer.validate_empty()?;
 
         Ok(())
@@ -1291,10 +1615,13 @@ impl<'data> InternalLayout<'data> {
     fn write_merged_strings(&self, buffers: &mut OutputSectionPartMap<&mut [u8]>) {
         self.merged_strings.for_each(|section_id, merged| {
             if merged.len > 0 {
-                let buffer = buffers.regular_mut(section_id, crate::alignment::MIN);
-                for string in &merged.strings {
-                    let dest = crate::slice::slice_take_prefix_mut(buffer, string.len());
-                    dest.copy_from_slice(string)
+                let buffer = crate::slice::slice_take_prefix_mut(
+                    buffers.regular_mut(section_id, merged.alignment),
+                    merged.len as usize,
+                );
+                for (offset, string) in &merged.strings {
+                    let offset = *offset as usize;
+                    buffer[offset..offset + string.len()].copy_from_slice(string);
                 }
             }
         });
@@ -1326,23 +1653,18 @@ impl<'data> InternalLayout<'data> {
             )
             .context("undefined symbol resolution")?;
         if let Some(got_address) = self.tlsld_got_entry {
+            // The TLSLD entry is a module ID / offset pair with an offset of zero, which is the
+            // same as a GotTlsDouble entry for the start of our TLS segment.
             plt_got_writer.process_resolution(
                 &Resolution {
-                    address: 1,
+                    address: layout.tls_start_address(),
                     got_address: Some(got_address),
                     plt_address: None,
-                    kind: TargetResolutionKind::Got,
+                    kind: TargetResolutionKind::GotTlsDouble,
+                    is_absolute: false,
+                    dynamic_symbol_index: None,
                 },
-                &mut RelocationWriter::disabled(),
-            )?;
-            plt_got_writer.process_resolution(
-                &Resolution {
-                    address: 0,
-                    got_address: Some(got_address.saturating_add(elf::GOT_ENTRY_SIZE)),
-                    plt_address: None,
-                    kind: TargetResolutionKind::Got,
-                },
-                &mut RelocationWriter::disabled(),
+                relocation_writer,
             )?;
         }
 
@@ -1382,56 +1704,113 @@ impl<'data> InternalLayout<'data> {
             let symbol = layout.symbol_db.symbol(symbol_id);
             let local_index = symbol.local_index_for_file(INTERNAL_FILE_ID)?;
             let def_info = &self.symbol_definitions[local_index.0];
-            let section_id = def_info.section_id();
-
-            // We don't emit a section header for our headers section, so don't emit symbols that
-            // are in that section, otherwise they'll show up as undefined.
-            if section_id == output_section_id::HEADERS {
-                continue;
-            }
-
-            let shndx = layout
-                .output_sections
-                .output_index_of_section(section_id)
-                .with_context(|| {
-                    format!(
-                        "symbol `{}` in section `{}` that we're not going to output",
-                        layout.symbol_db.symbol_name(symbol_id),
-                        String::from_utf8_lossy(layout.output_sections.details(section_id).name)
-                    )
-                })?;
-            let address = match resolution {
-                SymbolResolution::Resolved(res) => res.address,
-                SymbolResolution::Dynamic => unreachable!(),
+            let SymbolResolution::Resolved(res) = resolution;
+            let address = res.address;
+
+            let shndx = match def_info.section_id() {
+                // We don't emit a section header for our headers section, so don't emit symbols
+                // that are in that section, otherwise they'll show up as undefined.
+                Some(output_section_id::HEADERS) => continue,
+                Some(section_id) => layout
+                    .output_sections
+                    .output_index_of_section(section_id)
+                    .with_context(|| {
+                        format!(
+                            "symbol `{}` in section `{}` that we're not going to output",
+                            layout.symbol_db.symbol_name(symbol_id),
+                            String::from_utf8_lossy(
+                                layout.output_sections.details(section_id).name
+                            )
+                        )
+                    })?,
+                None if matches!(
+                    def_info,
+                    InternalSymDefInfo::Undefined | InternalSymDefInfo::UndefinedWeak
+                ) =>
+                {
+                    object::elf::SHN_UNDEF
+                }
+                None => defsym_section_index(layout, def_info, address),
             };
             let symbol_name = layout.symbol_db.symbol_name(symbol_id);
             let entry =
                 symbol_writer.define_symbol(false, shndx, address, 0, symbol_name.bytes())?;
-            entry.info = (elf::Binding::Global as u8) << 4;
+            entry.info = (internal_symbol_binding(def_info) as u8) << 4;
         }
         symbol_writer.check_exhausted()?;
         Ok(())
     }
 
+    /// Writes our parts of .dynsym and .dynstr. That's the null symbol, the empty string, our
+    /// soname, then any undefined symbols that we're leaving for the dynamic loader to resolve.
+    fn write_dynamic_symbols(
+        &self,
+        buffers: &mut OutputSectionPartMap<&mut [u8]>,
+        layout: &Layout,
+    ) -> Result {
+        let mut dynsym: &mut [SymtabEntry] =
+            bytemuck::cast_slice_mut(core::mem::take(&mut buffers.dynsym));
+        let mut dynstr: &mut [u8] = core::mem::take(&mut buffers.dynstr);
+        let dynstr_len = dynstr.len();
+
+        // The null symbol is all zeros, which our buffer already is.
+        crate::slice::take_first_mut(&mut dynsym).context("Insufficient .dynsym allocation")?;
+        write_string(&mut dynstr, &[])?;
+        if let Some(soname) = output_soname(layout.args()) {
+            write_string(&mut dynstr, soname.as_bytes())?;
+        }
+
+        for &symbol_id in &self.undefined_dynamic_symbols {
+            let local_index = layout
+                .symbol_db
+                .symbol(symbol_id)
+                .local_index_for_file(INTERNAL_FILE_ID)?;
+            let def_info = &self.symbol_definitions[local_index.0];
+            let name_offset = self.dynstr_start_offset + (dynstr_len - dynstr.len()) as u64;
+            *crate::slice::take_first_mut(&mut dynsym)
+                .context("Insufficient .dynsym allocation")? = SymtabEntry {
+                name: u32::try_from(name_offset).context(".dynstr overflowed 32 bits")?,
+                info: (internal_symbol_binding(def_info) as u8) << 4,
+                other: 0,
+                shndx: object::elf::SHN_UNDEF,
+                value: 0,
+                size: 0,
+            };
+            write_string(&mut dynstr, layout.symbol_db.symbol_name(symbol_id).bytes())?;
+        }
+        if !dynsym.is_empty() || !dynstr.is_empty() {
+            bail!("Allocated more space than was used for internal dynamic symbols");
+        }
+        Ok(())
+    }
+
     fn write_dynamic_entries(&self, out: &mut [u8], layout: &Layout) -> Result {
         let mut entries: &mut [DynamicEntry] = bytemuck::cast_slice_mut(out);
-        assert_eq!(entries.len(), NUM_DYNAMIC_ENTRIES);
-        // When adding/removing entries, don't forget to update NUM_DYNAMIC_ENTRIES
+        assert_eq!(entries.len(), num_dynamic_entries(layout.symbol_db));
+        // When adding/removing entries, don't forget to update `num_dynamic_entries`.
+        for file_layout in &layout.file_layouts {
+            if let FileLayout::Dynamic(d) = file_layout {
+                write_dynamic_entry(&mut entries, DynamicTag::Needed, d.dynstr_start_offset)?;
+            }
+        }
+        if output_soname(layout.args()).is_some() {
+            write_dynamic_entry(&mut entries, DynamicTag::SoName, 1)?;
+        }
         write_dynamic_entry(
             &mut entries,
             DynamicTag::Init,
-            layout.offset_of_section(output_section_id::INIT),
+            layout.mem_address_of_section(output_section_id::INIT),
         )?;
         write_dynamic_entry(
             &mut entries,
             DynamicTag::Fini,
-            layout.offset_of_section(output_section_id::FINI),
+            layout.mem_address_of_section(output_section_id::FINI),
         )?;
 
         write_dynamic_entry(
             &mut entries,
             DynamicTag::InitArray,
-            layout.offset_of_section(output_section_id::INIT_ARRAY),
+            layout.mem_address_of_section(output_section_id::INIT_ARRAY),
         )?;
         write_dynamic_entry(
             &mut entries,
@@ -1442,7 +1821,7 @@ impl<'data> InternalLayout<'data> {
         write_dynamic_entry(
             &mut entries,
             DynamicTag::FiniArray,
-            layout.offset_of_section(output_section_id::FINI_ARRAY),
+            layout.mem_address_of_section(output_section_id::FINI_ARRAY),
         )?;
         write_dynamic_entry(
             &mut entries,
@@ -1453,7 +1832,7 @@ impl<'data> InternalLayout<'data> {
         write_dynamic_entry(
             &mut entries,
             DynamicTag::StrTab,
-            layout.offset_of_section(output_section_id::DYNSTR),
+            layout.mem_address_of_section(output_section_id::DYNSTR),
         )?;
         write_dynamic_entry(
             &mut entries,
@@ -1464,7 +1843,7 @@ impl<'data> InternalLayout<'data> {
         write_dynamic_entry(
             &mut entries,
             DynamicTag::SymTab,
-            layout.offset_of_section(output_section_id::DYNSYM),
+            layout.mem_address_of_section(output_section_id::DYNSYM),
         )?;
         write_dynamic_entry(
             &mut entries,
@@ -1472,12 +1851,18 @@ impl<'data> InternalLayout<'data> {
             core::mem::size_of::<elf::SymtabEntry>() as u64,
         )?;
 
+        write_dynamic_entry(
+            &mut entries,
+            DynamicTag::Hash,
+            layout.mem_address_of_section(output_section_id::HASH),
+        )?;
+
         write_dynamic_entry(&mut entries, DynamicTag::Debug, 0)?;
 
         write_dynamic_entry(
             &mut entries,
             DynamicTag::Rela,
-            layout.offset_of_section(output_section_id::RELA_DYN),
+            layout.mem_address_of_section(output_section_id::RELA_DYN),
         )?;
         write_dynamic_entry(
             &mut entries,
@@ -1485,33 +1870,211 @@ impl<'data> InternalLayout<'data> {
             layout.size_of_section(output_section_id::RELA_DYN),
         )?;
         write_dynamic_entry(&mut entries, DynamicTag::RelaEnt, elf::RELA_ENTRY_SIZE)?;
+        // Our relative relocations all come first in .rela.dyn, so this is the number of them.
         write_dynamic_entry(
             &mut entries,
             DynamicTag::RelaCount,
-            layout.size_of_section(output_section_id::RELA_DYN)
+            layout.section_part_layouts.rela_dyn_relative.mem_size
                 / core::mem::size_of::<elf::Rela>() as u64,
         )?;
 
+        if layout.symbol_db.num_dynamic_inputs > 0 {
+            write_dynamic_entry(
+                &mut entries,
+                DynamicTag::JmpRel,
+                layout.mem_address_of_built_in(output_section_id::RELA_PLT),
+            )?;
+            write_dynamic_entry(
+                &mut entries,
+                DynamicTag::PltRelSize,
+                layout.size_of_section(output_section_id::RELA_PLT),
+            )?;
+            write_dynamic_entry(&mut entries, DynamicTag::PltRel, DynamicTag::Rela as u64)?;
+        }
+
         write_dynamic_entry(&mut entries, DynamicTag::Flags, elf::flags::BIND_NOW)?;
-        write_dynamic_entry(
-            &mut entries,
-            DynamicTag::Flags1,
-            elf::flags_1::PIE | elf::flags_1::NOW,
-        )?;
+        let mut flags_1 = elf::flags_1::NOW;
+        if layout.args().pie && !layout.args().shared {
+            flags_1 |= elf::flags_1::PIE;
+        }
+        write_dynamic_entry(&mut entries, DynamicTag::Flags1, flags_1)?;
 
-        //write_dynamic_entry(&mut entries, DynamicTag::Hash, todo)?;
-        //write_dynamic_entry(&mut entries, DynamicTag::StrTab, todo)?;
-        // write_dynamic_entry(&mut entries, DynamicTag::Rela, todo)?;
-        // write_dynamic_entry(&mut entries, DynamicTag::RelaSize, todo)?;
-        // write_dynamic_entry(&mut entries, DynamicTag::RelEnt, todo)?;
-        // write_dynamic_entry(&mut entries, DynamicTag::StrSize, todo)?;
-        // write_dynamic_entry(&mut entries, DynamicTag::Rel, todo)?;
-        // write_dynamic_entry(&mut entries, DynamicTag::RelSize, todo)?;
         write_dynamic_entry(&mut entries, DynamicTag::Null, 0)?;
         Ok(())
     }
 }
 
+impl<'data> DynamicLayout<'data> {
+    fn write(&self, buffers: OutputSectionPartMap<&mut [u8]>, layout: &Layout) -> Result {
+        let mut dynsym: &mut [SymtabEntry] = bytemuck::cast_slice_mut(buffers.dynsym);
+        let mut dynstr: &mut [u8] = buffers.dynstr;
+        let mut got: &mut [u64] = bytemuck::cast_slice_mut(buffers.got);
+        let mut plt: &mut [u8] = buffers.plt;
+        let mut rela_plt: &mut [elf::Rela] = bytemuck::cast_slice_mut(buffers.rela_plt);
+        let mut rela_dyn: &mut [elf::Rela] = bytemuck::cast_slice_mut(buffers.rela_dyn_general);
+        let bss_index = layout
+            .output_sections
+            .output_index_of_section(output_section_id::BSS);
+
+        write_string(&mut dynstr, self.soname)?;
+        let mut next_string_offset = self.dynstr_start_offset + self.soname.len() as u64 + 1;
+
+        for (i, sym) in self.symbols.iter().enumerate() {
+            let dynsym_index = self.dynsym_start_index + i as u64;
+            let res = &sym.resolution;
+            let binding = if sym.is_weak {
+                elf::Binding::Weak
+            } else {
+                elf::Binding::Global
+            };
+
+            let entry = crate::slice::take_first_mut(&mut dynsym)
+                .context("Insufficient .dynsym allocation")?;
+            *entry = SymtabEntry {
+                name: u32::try_from(next_string_offset).context(".dynstr overflowed 32 bits")?,
+                info: ((binding as u8) << 4) | sym.symbol_type,
+                other: 0,
+                shndx: 0,
+                value: 0,
+                size: 0,
+            };
+            if let Some(size) = sym.copy_size {
+                entry.shndx = bss_index.context("Copy relocation, but no .bss")?;
+                entry.value = res.address;
+                entry.size = size;
+                write_dynamic_relocation(
+                    &mut rela_dyn,
+                    res.address,
+                    dynsym_index,
+                    elf::rel::R_X86_64_COPY,
+                )?;
+            }
+            write_string(&mut dynstr, sym.name)?;
+            next_string_offset += sym.name.len() as u64 + 1;
+
+            let Some(got_address) = res.got_address else {
+                continue;
+            };
+            // The dynamic loader will fill in our GOT entries, so we just need to emit relocations
+            // for them.
+            *crate::slice::take_first_mut(&mut got).context("Insufficient GOT allocation")? = 0;
+            let got_address = got_address.get();
+            match res.kind {
+                TargetResolutionKind::Plt => {
+                    let plt_address = res.plt_address.context("Missing PLT address")?;
+                    if plt.len() < elf::PLT_ENTRY_SIZE as usize {
+                        bail!("Insufficient PLT allocation");
+                    }
+                    let plt_entry = slice_take_prefix_mut(&mut plt, elf::PLT_ENTRY_SIZE as usize);
+                    write_plt_entry(plt_entry, got_address, plt_address.get())?;
+                    if sym.is_canonical_plt {
+                        // The symbol stays undefined, but its value tells the dynamic loader to
+                        // bind other references to our PLT entry, so that the function's address
+                        // is the same everywhere.
+                        entry.value = plt_address.get();
+                    }
+                    write_dynamic_relocation(
+                        &mut rela_plt,
+                        got_address,
+                        dynsym_index,
+                        elf::rel::R_X86_64_JUMP_SLOT,
+                    )?;
+                }
+                TargetResolutionKind::Got => {
+                    write_dynamic_relocation(
+                        &mut rela_dyn,
+                        got_address,
+                        dynsym_index,
+                        elf::rel::R_X86_64_GLOB_DAT,
+                    )?;
+                }
+                TargetResolutionKind::GotTlsOffset => {
+                    write_dynamic_relocation(
+                        &mut rela_dyn,
+                        got_address,
+                        dynsym_index,
+                        elf::rel::R_X86_64_TPOFF64,
+                    )?;
+                }
+                TargetResolutionKind::GotTlsDouble => {
+                    *crate::slice::take_first_mut(&mut got)
+                        .context("Insufficient GOT allocation")? = 0;
+                    write_dynamic_relocation(
+                        &mut rela_dyn,
+                        got_address,
+                        dynsym_index,
+                        elf::rel::R_X86_64_DTPMOD64,
+                    )?;
+                    write_dynamic_relocation(
+                        &mut rela_dyn,
+                        got_address + elf::GOT_ENTRY_SIZE,
+                        dynsym_index,
+                        elf::rel::R_X86_64_DTPOFF64,
+                    )?;
+                }
+                other => bail!("Unexpected resolution kind {other:?} for dynamic symbol"),
+            }
+        }
+
+        if !dynsym.is_empty()
+            || !dynstr.is_empty()
+            || !got.is_empty()
+            || !plt.is_empty()
+            || !rela_plt.is_empty()
+            || !rela_dyn.is_empty()
+        {
+            bail!(
+                "Allocated more space than was used for imports from {}",
+                self.input
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Writes `string` followed by a null terminator to the start of `out`.
+/// Returns the binding for a symbol defined by our internal file.
+fn internal_symbol_binding(def_info: &InternalSymDefInfo) -> elf::Binding {
+    if matches!(def_info, InternalSymDefInfo::UndefinedWeak) {
+        elf::Binding::Weak
+    } else {
+        elf::Binding::Global
+    }
+}
+
+fn write_string(out: &mut &mut [u8], string: &[u8]) -> Result {
+    if out.len() <= string.len() {
+        bail!("Insufficient space allocated for string");
+    }
+    let dest = slice_take_prefix_mut(out, string.len() + 1);
+    dest[..string.len()].copy_from_slice(string);
+    dest[string.len()] = 0;
+    Ok(())
+}
+
+fn write_dynamic_relocation(
+    out: &mut &mut [elf::Rela],
+    place: u64,
+    dynsym_index: u64,
+    r_type: u32,
+) -> Result {
+    let rela =
+        crate::slice::take_first_mut(out).context("Insufficient dynamic relocation allocation")?;
+    rela.address = place;
+    rela.info = (dynsym_index << 32) | u64::from(r_type);
+    rela.addend = 0;
+    Ok(())
+}
+
+fn write_plt_entry(plt_entry: &mut [u8], got_address: u64, plt_address: u64) -> Result {
+    plt_entry.copy_from_slice(PLT_ENTRY_TEMPLATE);
+    let offset: i32 = ((got_address.wrapping_sub(plt_address + 0xb)) as i64)
+        .try_into()
+        .map_err(|_| anyhow!("PLT is more than 2GB away from GOT"))?;
+    plt_entry[7..11].copy_from_slice(&offset.to_le_bytes());
+    Ok(())
+}
+
 fn write_eh_frame_hdr(
     buffers: &mut OutputSectionPartMap<&mut [u8]>,
     layout: &Layout<'_>,
@@ -1555,9 +2118,42 @@ fn eh_frame_ptr(layout: &Layout<'_>) -> Result<i32> {
     .context(".eh_frame more than 2GB away from .eh_frame_hdr")
 }
 
-// TODO: Compute this at runtime by making the that writes the dynamic entries generic over its
-// output, then instantiating it with an output that just counts.
-pub(crate) const NUM_DYNAMIC_ENTRIES: usize = 18;
+/// Returns the number of entries that `write_dynamic_entries` will write.
+// TODO: Compute this by making the code that writes the dynamic entries generic over its output,
+// then instantiating it with an output that just counts.
+pub(crate) fn num_dynamic_entries(symbol_db: &SymbolDb) -> usize {
+    let num_needed = symbol_db.num_dynamic_inputs;
+    let num_plt_entries = if num_needed > 0 { 3 } else { 0 };
+    let num_soname = usize::from(output_soname(symbol_db.args).is_some());
+    19 + num_needed + num_plt_entries + num_soname
+}
+
+/// Returns the section index for a symbol defined by `--defsym`. Symbols defined as a number are
+/// absolute. Symbols defined relative to another symbol go in whichever section contains them.
+fn defsym_section_index(layout: &Layout, def_info: &InternalSymDefInfo, address: u64) -> u16 {
+    let InternalSymDefInfo::Defsym(index) = *def_info else {
+        return object::elf::SHN_ABS;
+    };
+    if let DefsymValue::Symbol(..) = layout.args().defsym[index].value {
+        let mut containing = None;
+        layout.section_layouts.for_each(|section_id, section| {
+            let contains =
+                section.mem_offset <= address && address < section.mem_offset + section.mem_size;
+            if contains && containing.is_none() {
+                containing = layout.output_sections.output_index_of_section(section_id);
+            }
+        });
+        if let Some(shndx) = containing {
+            return shndx;
+        }
+    }
+    object::elf::SHN_ABS
+}
+
+/// Returns the soname that we should write to DT_SONAME, if any.
+fn output_soname(args: &Args) -> Option<&str> {
+    args.soname.as_deref().filter(|_| args.shared)
+}
 
 fn write_dynamic_entry(out: &mut &mut [DynamicEntry], tag: DynamicTag, value: u64) -> Result {
     let entry = crate::slice::take_first_mut(out)
@@ -12,3 +12,31 @@ pub(crate) fn make_executable(file: &File) -> Result {
     file.set_permissions(permissions)?;
     Ok(())
 }
+
+/// A directory for tests to create files in. The directory and everything in it is removed when
+/// this is dropped, so it's cleaned up even if the test panics.
+#[cfg(test)]
+pub(crate) struct TestDir(std::path::PathBuf);
+
+#[cfg(test)]
+impl TestDir {
+    /// Creates an empty directory. `name` needs to be unique among our tests, since tests within a
+    /// process run in parallel.
+    pub(crate) fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("wild-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TestDir(path)
+    }
+
+    pub(crate) fn path(&self) -> &std::path::Path {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
@@ -4,20 +4,27 @@ use crate::archive;
 use crate::args::Args;
 use crate::args::Input;
 use crate::args::InputSpec;
+use crate::args::Modifiers;
 use crate::error::Result;
 use crate::file_kind::FileKind;
-use crate::linker_script::linker_script_to_inputs;
-use anyhow::bail;
+use crate::linker_script::parse_linker_script;
+use crate::version_script::VersionScript;
 use anyhow::Context;
 use memmap2::Mmap;
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::path::Path;
 use std::path::PathBuf;
 
 pub(crate) struct InputData<'config> {
     pub(crate) config: &'config Args,
-    filenames: HashSet<PathBuf>,
+    /// Maps from the path of each input that we've registered to its index in `files`. Linker
+    /// scripts map to None, since they don't get an entry.
+    filenames: HashMap<PathBuf, Option<usize>>,
     pub(crate) files: Vec<InputFile>,
+    pub(crate) version_script: VersionScript,
+    /// The symbol (or address) at which execution should start. `-e` takes precedence over
+    /// `ENTRY(...)` in a linker script. None if neither was given.
+    pub(crate) entry: Option<String>,
 }
 
 /// Identifies an input file. IDs start from 0 which is reserved for our "internal" state file.
@@ -29,6 +36,7 @@ pub(crate) const INTERNAL_FILE_ID: FileId = FileId::new(0);
 pub(crate) struct InputFile {
     pub(crate) filename: PathBuf,
     pub(crate) kind: FileKind,
+    pub(crate) modifiers: Modifiers,
     bytes: Option<Mmap>,
 }
 
@@ -55,29 +63,48 @@ impl<'config> InputData<'config> {
             InputFile {
                 filename: PathBuf::new(),
                 kind: FileKind::Internal,
+                modifiers: Default::default(),
                 bytes: None,
             },
         ];
+        let version_script = config
+            .version_script
+            .as_deref()
+            .map(VersionScript::from_file)
+            .transpose()?
+            .unwrap_or_default();
         let mut input_data = Self {
             config,
             filenames: Default::default(),
             files,
+            version_script,
+            entry: None,
         };
         for input in &config.inputs {
             input_data.register_input(input)?;
         }
+        if config.entry.is_some() {
+            input_data.entry = config.entry.clone();
+        }
         Ok(input_data)
     }
 
     fn register_input(&mut self, input: &Input) -> Result {
-        self.register_file(input.path(self.config)?)
+        self.register_file(input.path(self.config)?, input.modifiers)
     }
 
-    fn register_file(&mut self, path: PathBuf) -> Result {
-        if !self.filenames.insert(path.clone()) {
-            // File has already been added.
+    fn register_file(&mut self, path: PathBuf, modifiers: Modifiers) -> Result {
+        if let Some(&index) = self.filenames.get(&path) {
+            // File has already been added. If it was given with different modifiers, we combine
+            // them, so that for example `--whole-archive` still applies to an archive that was
+            // given earlier without it.
+            if let Some(index) = index {
+                let existing = &mut self.files[index].modifiers;
+                *existing = existing.merge(modifiers);
+            }
             return Ok(());
         }
+        self.filenames.insert(path.clone(), None);
         let file = std::fs::File::open(&path)
             .with_context(|| format!("Failed to open input file `{}`", path.display()))?;
 
@@ -107,7 +134,13 @@ impl<'config> InputData<'config> {
 
         let kind = FileKind::identify_bytes(&bytes)?;
         if matches!(kind, FileKind::Text) {
-            for input in linker_script_to_inputs(&bytes, &path)? {
+            let script = parse_linker_script(&bytes, &path, self.config.sysroot.as_deref())?;
+            if script.entry.is_some() {
+                self.entry = script.entry;
+            }
+            // Inputs from the script are treated as if they'd been given in place of the script.
+            for mut input in script.inputs {
+                input.modifiers = modifiers;
                 self.register_input(&input)?;
             }
             return Ok(());
@@ -116,8 +149,10 @@ impl<'config> InputData<'config> {
         let file_info = InputFile {
             filename: path.to_owned(),
             kind,
+            modifiers,
             bytes: Some(bytes),
         };
+        self.filenames.insert(path, Some(self.files.len()));
         self.files.push(file_info);
         Ok(())
     }
@@ -138,29 +173,48 @@ impl Input {
                 }
                 Ok(p.as_ref().to_owned())
             }
-            InputSpec::Lib(lib_name) => {
-                if !args.link_static {
-                    if let Some(path) = search_for_file(
-                        &args.lib_search_path,
-                        self.search_first.as_ref(),
-                        format!("lib{lib_name}.so"),
-                    ) {
-                        return Ok(path);
-                    }
-                }
-                if let Some(path) = search_for_file(
-                    &args.lib_search_path,
-                    self.search_first.as_ref(),
-                    format!("lib{lib_name}.a"),
-                ) {
-                    return Ok(path);
-                }
-                bail!("Couldn't find library `{lib_name}` on library search path");
-            }
+            InputSpec::Lib(lib_name) => find_library(
+                &args.lib_search_path,
+                self.search_first.as_deref(),
+                lib_name,
+                self.modifiers.static_libs_only,
+            )
+            .with_context(|| format!("Couldn't find library `{lib_name}` on library search path")),
+            InputSpec::Search(filename) => search_for_file(
+                &args.lib_search_path,
+                self.search_first.as_ref(),
+                &**filename,
+            )
+            .with_context(|| format!("Couldn't find `{filename}` on library search path")),
         }
     }
 }
 
+/// Looks for the library `lib_name` in the same way as ld. Each directory is checked in turn and
+/// within a directory, a shared object is preferred over an archive unless `static_only` is set.
+fn find_library(
+    lib_search_path: &[Box<Path>],
+    search_first: Option<&Path>,
+    lib_name: &str,
+    static_only: bool,
+) -> Option<PathBuf> {
+    let shared = format!("lib{lib_name}.so");
+    let archive = format!("lib{lib_name}.a");
+    search_first
+        .into_iter()
+        .chain(lib_search_path.iter().map(|dir| &**dir))
+        .find_map(|dir| {
+            if !static_only {
+                let path = dir.join(&shared);
+                if path.exists() {
+                    return Some(path);
+                }
+            }
+            let path = dir.join(&archive);
+            path.exists().then_some(path)
+        })
+}
+
 fn search_for_file(
     lib_search_path: &[Box<Path>],
     search_first: Option<&PathBuf>,
@@ -220,3 +274,27 @@ impl std::fmt::Display for FileId {
         std::fmt::Display::fmt(&self.0, f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_find_library() {
+        let dir = crate::fs::TestDir::new("find-library");
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        std::fs::create_dir_all(&a).unwrap();
+        std::fs::create_dir_all(&b).unwrap();
+        for path in [a.join("libfoo.a"), b.join("libfoo.so"), b.join("libbar.so")] {
+            std::fs::write(path, []).unwrap();
+        }
+        let search_path = [Box::from(a.as_path()), Box::from(b.as_path())];
+        let find = |name, static_only| super::find_library(&search_path, None, name, static_only);
+        // An archive in an earlier directory takes precedence over a shared object in a later one.
+        assert_eq!(find("foo", false), Some(a.join("libfoo.a")));
+        assert_eq!(find("bar", false), Some(b.join("libbar.so")));
+        assert_eq!(find("bar", true), None);
+        assert_eq!(
+            super::find_library(&search_path, Some(b.as_path()), "foo", false),
+            Some(b.join("libfoo.so"))
+        );
+    }
+}
@@ -0,0 +1,164 @@
+//! Support for the GNU make jobserver protocol, which is also used by cargo. When we're run by a
+//! build system that's running lots of jobs in parallel, this lets us limit how many threads we
+//! use, rather than each of several concurrent links trying to use every core.
+//!
+//! The jobserver is a pipe or named fifo containing one byte per available job slot. Every process
+//! implicitly holds one slot. To get more, we read bytes from the pipe and when we're done, we
+//! write them back. We never wait for tokens - we take whatever is available when we start.
+
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+
+/// Tokens that we've acquired from a jobserver. They're returned when this is dropped.
+pub(crate) struct Tokens {
+    write: File,
+    tokens: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Auth {
+    /// A named fifo, from `--jobserver-auth=fifo:PATH`.
+    Fifo(PathBuf),
+    /// The read and write ends of a pipe that we inherited, from `--jobserver-auth=R,W`.
+    Pipe(i32, i32),
+}
+
+/// Attempts to acquire up to `max` tokens from the jobserver specified in our environment, if any.
+/// Returns None if there's no jobserver, or we can't connect to it.
+pub(crate) fn acquire_from_env(max: usize) -> Option<Tokens> {
+    let auth = ["CARGO_MAKEFLAGS", "MAKEFLAGS", "MFLAGS"]
+        .into_iter()
+        .find_map(|var| parse_auth(&std::env::var(var).ok()?))?;
+    acquire(&auth, max)
+}
+
+fn acquire(auth: &Auth, max: usize) -> Option<Tokens> {
+    let (mut read, write) = open(auth)?;
+    let mut tokens = Vec::new();
+    let mut buffer = [0_u8; 1];
+    while tokens.len() < max {
+        match read.read(&mut buffer) {
+            Ok(1) => tokens.push(buffer[0]),
+            Err(error) if error.kind() == ErrorKind::Interrupted => {}
+            // Either there are no more tokens available right now, or something went wrong, in
+            // which case we just make do with what we've got.
+            _ => break,
+        }
+    }
+    Some(Tokens { write, tokens })
+}
+
+/// Extracts the jobserver details from the value of MAKEFLAGS. If the option appears more than
+/// once, the last one wins.
+fn parse_auth(makeflags: &str) -> Option<Auth> {
+    let value = makeflags
+        .split_ascii_whitespace()
+        .filter_map(|word| {
+            word.strip_prefix("--jobserver-auth=")
+                .or_else(|| word.strip_prefix("--jobserver-fds="))
+        })
+        .last()?;
+    if let Some(path) = value.strip_prefix("fifo:") {
+        return Some(Auth::Fifo(PathBuf::from(path)));
+    }
+    let (read, write) = value.split_once(',')?;
+    Some(Auth::Pipe(read.parse().ok()?, write.parse().ok()?))
+}
+
+/// Opens the read and write ends of the jobserver. The read end is non-blocking, so that we can
+/// stop once no tokens are available. We open pipes afresh via /proc rather than using the file
+/// descriptors that we inherited, since the non-blocking flag would otherwise be shared with other
+/// processes using the jobserver.
+fn open(auth: &Auth) -> Option<(File, File)> {
+    let (read_path, write_path) = match auth {
+        Auth::Fifo(path) => (path.clone(), path.clone()),
+        Auth::Pipe(read, write) => {
+            // Make doesn't pass the jobserver file descriptors to commands that it doesn't think
+            // are recursive invocations of make, in which case they might be closed or even
+            // reused for something else.
+            for fd in [*read, *write] {
+                // Safety: F_GETFD just queries the file descriptor flags.
+                if unsafe { libc::fcntl(fd, libc::F_GETFD) } < 0 {
+                    return None;
+                }
+            }
+            (
+                PathBuf::from(format!("/proc/self/fd/{read}")),
+                PathBuf::from(format!("/proc/self/fd/{write}")),
+            )
+        }
+    };
+    let read = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(read_path)
+        .ok()?;
+    let write = OpenOptions::new().write(true).open(write_path).ok()?;
+    Some((read, write))
+}
+
+impl Tokens {
+    /// Returns the number of tokens that we acquired. This doesn't include the token that we
+    /// implicitly hold.
+    pub(crate) fn count(&self) -> usize {
+        self.tokens.len()
+    }
+}
+
+impl Drop for Tokens {
+    fn drop(&mut self) {
+        // If this fails, there's not much we can do about it.
+        let _ = self.write.write_all(&self.tokens);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_auth() {
+        assert_eq!(
+            parse_auth("-j --jobserver-auth=3,4"),
+            Some(Auth::Pipe(3, 4))
+        );
+        assert_eq!(
+            parse_auth("-j8 --jobserver-fds=5,6 --jobserver-auth=fifo:/tmp/GMfifo1"),
+            Some(Auth::Fifo(PathBuf::from("/tmp/GMfifo1")))
+        );
+        assert_eq!(parse_auth("-j8"), None);
+        assert_eq!(parse_auth("--jobserver-auth=bad"), None);
+    }
+
+    #[test]
+    fn test_acquire_from_fifo() {
+        let dir = crate::fs::TestDir::new("jobserver");
+        let path = dir.path().join("fifo");
+        let c_path = std::ffi::CString::new(path.as_os_str().as_encoded_bytes()).unwrap();
+        // Safety: `c_path` is a valid nul-terminated string.
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+        let auth = Auth::Fifo(path.clone());
+        // Stand in for the jobserver by holding the fifo open and putting some tokens in it.
+        let (mut read, mut write) = open(&auth).unwrap();
+        write.write_all(b"+++").unwrap();
+
+        let tokens = acquire(&auth, 2).unwrap();
+        assert_eq!(tokens.count(), 2);
+        drop(tokens);
+        // We shouldn't wait for more tokens than are available.
+        let tokens = acquire(&auth, 5).unwrap();
+        assert_eq!(tokens.count(), 3);
+        drop(tokens);
+
+        // All the tokens that we took should have been returned. Reading stops with an error once
+        // the fifo is empty, since the read end is non-blocking.
+        let mut remaining = Vec::new();
+        let _ = read.read_to_end(&mut remaining);
+        assert_eq!(remaining, b"+++");
+    }
+}
@@ -5,6 +5,8 @@
 use crate::alignment;
 use crate::alignment::Alignment;
 use crate::args::Args;
+use crate::args::Defsym;
+use crate::args::DefsymValue;
 use crate::elf;
 use crate::elf::EhFrameHdrEntry;
 use crate::elf::File;
@@ -31,10 +33,10 @@ use crate::resolution;
 use crate::resolution::LocalSymbolResolution;
 use crate::resolution::SectionSlot;
 use crate::symbol::SymbolName;
-use crate::symbol_db;
 use crate::symbol_db::GlobalSymbolId;
 use crate::symbol_db::InternalSymDefInfo;
 use crate::symbol_db::SymbolDb;
+use crate::version_script::glob_matches;
 use ahash::AHashMap;
 use anyhow::bail;
 use anyhow::Context;
@@ -42,12 +44,15 @@ use crossbeam_queue::ArrayQueue;
 use object::Object;
 use object::ObjectSection;
 use object::ObjectSymbol;
+use object::ObjectSymbolTable;
 use rayon::prelude::IndexedParallelIterator;
 use rayon::prelude::IntoParallelIterator;
 use rayon::prelude::IntoParallelRefMutIterator;
 use rayon::prelude::ParallelIterator;
 use smallvec::SmallVec;
+use std::ffi::CString;
 use std::mem::size_of;
+use std::num::NonZeroU32;
 use std::num::NonZeroU64;
 use std::sync::atomic;
 use std::sync::atomic::AtomicBool;
@@ -64,6 +69,13 @@ pub(crate) fn compute<'data>(
         print_symbol_info(symbol_db, &file_states, sym_info);
     }
     let mut layout_states = find_required_sections(file_states, symbol_db, &output_sections)?;
+    if !symbol_db.args.why_live.is_empty() {
+        print_why_live(symbol_db, &layout_states)?;
+    }
+    merge_strings(&mut layout_states, &output_sections, symbol_db.args)?;
+    if symbol_db.args.print_gc_sections {
+        report_gc_sections(&layout_states, symbol_db.args)?;
+    }
     finalise_all_sizes(symbol_db, &output_sections, &mut layout_states)?;
     let section_part_sizes =
         compute_total_section_part_sizes(&mut layout_states, &mut output_sections);
@@ -80,8 +92,11 @@ pub(crate) fn compute<'data>(
     let mem_offsets: OutputSectionPartMap<u64> =
         starting_memory_offsets(&section_part_layouts, &output_sections);
     let starting_mem_offsets_by_file = compute_start_offsets_by_file(&layout_states, mem_offsets);
-    let merged_string_start_addresses =
-        MergedStringStartAddresses::compute(&output_sections, &starting_mem_offsets_by_file);
+    let merged_string_start_addresses = MergedStringStartAddresses::compute(
+        &output_sections,
+        &starting_mem_offsets_by_file,
+        &internal.merged_strings,
+    );
     let symbols_and_layouts = compute_symbols_and_layouts(
         layout_states,
         starting_mem_offsets_by_file,
@@ -90,7 +105,9 @@ pub(crate) fn compute<'data>(
         &merged_string_start_addresses,
     )?;
 
-    let (symbol_addresses, file_layouts) = merge_symbol_addresses(symbol_db, symbols_and_layouts);
+    let (mut symbol_addresses, file_layouts) =
+        merge_symbol_addresses(symbol_db, symbols_and_layouts);
+    resolve_defsym_aliases(symbol_db, &mut symbol_addresses)?;
     Ok(Layout {
         symbol_db,
         symbol_addresses,
@@ -134,6 +151,72 @@ fn merge_symbol_addresses<'data>(
     (symbol_addresses, file_layouts)
 }
 
+/// Fills in the addresses of symbols that `--defsym` defined relative to some other symbol. These
+/// can only be computed once all other symbols have addresses.
+fn resolve_defsym_aliases(
+    symbol_db: &SymbolDb,
+    symbol_addresses: &mut SymbolResolutions,
+) -> Result {
+    for defsym in &symbol_db.args.defsym {
+        if !matches!(defsym.value, DefsymValue::Symbol(..)) {
+            continue;
+        }
+        let symbol_id = *symbol_db
+            .symbol_ids
+            .get(&SymbolName::new(defsym.name.as_bytes()))
+            .context("--defsym symbol missing from symbol table")?;
+        // We check every alias, even unreferenced ones, so that cycles and undefined targets are
+        // always reported.
+        let address = defsym_alias_address(symbol_db, symbol_addresses, defsym)?;
+        // If nothing referenced our symbol, then it won't have a resolution to update.
+        if let Some(SymbolResolution::Resolved(resolution)) =
+            &mut symbol_addresses.resolutions[symbol_id.as_usize()]
+        {
+            resolution.address = address;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the address of a symbol that `--defsym` defined relative to another symbol. If the target
+/// was itself defined with `--defsym`, then we follow it, so that the order of the definitions on
+/// the command line doesn't matter.
+fn defsym_alias_address(
+    symbol_db: &SymbolDb,
+    symbol_addresses: &SymbolResolutions,
+    defsym: &Defsym,
+) -> Result<u64> {
+    let mut current = defsym;
+    let mut total_offset = 0_i64;
+    // Each step follows one alias, so more steps than there are definitions means that we're going
+    // around in a cycle.
+    for _ in 0..=symbol_db.args.defsym.len() {
+        let (target_name, offset) = match &current.value {
+            DefsymValue::Value(value) => return Ok(value.wrapping_add_signed(total_offset)),
+            DefsymValue::Symbol(target_name, offset) => (target_name, *offset),
+        };
+        total_offset = total_offset.wrapping_add(offset);
+        if let Some(target) = symbol_db.args.defsym_named(target_name.as_bytes()) {
+            current = target;
+            continue;
+        }
+        let target_id = *symbol_db
+            .symbol_ids
+            .get(&SymbolName::new(target_name.as_bytes()))
+            .with_context(|| format!("--defsym: Undefined symbol `{target_name}`"))?;
+        let Some(SymbolResolution::Resolved(target)) =
+            &symbol_addresses.resolutions[target_id.as_usize()]
+        else {
+            bail!("--defsym: Symbol `{target_name}` didn't get an address");
+        };
+        return Ok(target.address.wrapping_add_signed(total_offset));
+    }
+    bail!(
+        "--defsym: Definition of `{}` refers back to itself",
+        defsym.name
+    );
+}
+
 fn compute_total_file_size(section_layouts: &OutputSectionMap<OutputRecordLayout>) -> u64 {
     let mut file_size = 0;
     section_layouts.for_each(|_, s| file_size = file_size.max(s.file_offset + s.file_size));
@@ -178,7 +261,6 @@ pub(crate) enum FileLayout<'data> {
 #[derive(Debug, Clone)]
 pub(crate) enum SymbolResolution {
     Resolved(Resolution),
-    Dynamic,
 }
 
 /// The addresses of the start of the merged strings for each output section.
@@ -194,6 +276,13 @@ pub(crate) struct Resolution {
     pub(crate) got_address: Option<NonZeroU64>,
     pub(crate) plt_address: Option<NonZeroU64>,
     pub(crate) kind: TargetResolutionKind,
+    /// Whether `address` is an absolute value (e.g. from `--defsym`) rather than an address within
+    /// our output. Absolute values don't change if our output gets loaded at a different address,
+    /// so they don't need dynamic relocations.
+    pub(crate) is_absolute: bool,
+    /// The index of the symbol in .dynsym if the dynamic loader binds it at runtime. References to
+    /// such symbols need symbolic dynamic relocations and mustn't be relaxed.
+    pub(crate) dynamic_symbol_index: Option<NonZeroU32>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -208,7 +297,6 @@ pub(crate) enum TlsMode {
 enum FileLayoutState<'data> {
     Internal(Box<InternalLayoutState<'data>>),
     Object(Box<ObjectLayoutState<'data>>),
-    #[allow(dead_code)]
     Dynamic(Box<DynamicLayoutState<'data>>),
     NotLoaded,
 }
@@ -218,11 +306,15 @@ struct InternalLayoutState<'data> {
     common: CommonLayoutState,
     defined: Vec<GlobalSymbolId>,
     symbol_definitions: Vec<InternalSymDefInfo>,
-    entry_symbol_id: Option<GlobalSymbolId>,
+    entry: EntryPoint,
     needs_tlsld_got_entry: bool,
     merged_strings: OutputSectionMap<resolution::MergedStringsSection<'data>>,
     identity: String,
     header_info: Option<HeaderInfo>,
+    dynamic_linker: Option<CString>,
+    /// Referenced symbols that nothing defined, which we leave for the dynamic loader to resolve.
+    /// Only present when producing a shared object.
+    undefined_dynamic_symbols: Vec<GlobalSymbolId>,
 }
 
 pub(crate) struct ObjectLayout<'data> {
@@ -236,8 +328,16 @@ pub(crate) struct ObjectLayout<'data> {
     pub(crate) plt_relocations: Vec<PltRelocation>,
     pub(crate) loaded_symbols: Vec<GlobalSymbolId>,
     pub(crate) local_symbol_resolutions: Vec<LocalSymbolResolution>,
+    /// References to our string-merge sections via section symbols. Sorted by section index then
+    /// offset.
+    pub(crate) merge_section_refs: Vec<resolution::MergeSectionRef>,
     /// The memory address of the start of this object's allocation within .eh_frame.
     pub(crate) eh_frame_start_address: u64,
+    pub(crate) exported_symbols: Vec<GlobalSymbolId>,
+    /// The index in .dynsym of our first exported symbol.
+    pub(crate) dynsym_start_index: u64,
+    /// The offset in .dynstr of the name of our first exported symbol.
+    pub(crate) dynstr_start_offset: u64,
 }
 
 pub(crate) struct InternalLayout<'data> {
@@ -246,16 +346,57 @@ pub(crate) struct InternalLayout<'data> {
     pub(crate) defined: Vec<GlobalSymbolId>,
     pub(crate) strings_offset_start: u32,
     pub(crate) symbol_definitions: Vec<InternalSymDefInfo>,
-    pub(crate) entry_symbol_id: GlobalSymbolId,
+    pub(crate) entry: EntryPoint,
     pub(crate) tlsld_got_entry: Option<NonZeroU64>,
     pub(crate) merged_strings: OutputSectionMap<resolution::MergedStringsSection<'data>>,
     pub(crate) identity: String,
     pub(crate) header_info: HeaderInfo,
+    pub(crate) dynamic_linker: Option<CString>,
+    pub(crate) undefined_dynamic_symbols: Vec<GlobalSymbolId>,
+    /// The index in .dynsym of our first symbol, which is the null symbol.
+    pub(crate) dynsym_start_index: u64,
+    /// The offset in .dynstr of our first string, which is the empty string.
+    pub(crate) dynstr_start_offset: u64,
+}
+
+/// Where execution of our output should start.
+#[derive(Clone, Copy)]
+pub(crate) enum EntryPoint {
+    /// No entry point. This is normal for shared objects, but can also happen for executables if
+    /// the entry symbol wasn't found.
+    None,
+    Symbol(GlobalSymbolId),
+    /// A numeric address given in place of a symbol name, e.g. `-e 0x401000`.
+    Address(u64),
 }
 
 pub(crate) struct DynamicLayout<'data> {
-    #[allow(dead_code)]
-    input: InputRef<'data>,
+    pub(crate) input: InputRef<'data>,
+    pub(crate) mem_sizes: OutputSectionPartMap<u64>,
+    pub(crate) soname: &'data [u8],
+    /// The index in .dynsym of our first symbol.
+    pub(crate) dynsym_start_index: u64,
+    /// The offset in .dynstr of our first string, which is the soname.
+    pub(crate) dynstr_start_offset: u64,
+    pub(crate) symbols: Vec<DynamicSymbol<'data>>,
+}
+
+/// A symbol that we import from a shared object.
+pub(crate) struct DynamicSymbol<'data> {
+    pub(crate) name: &'data [u8],
+    /// The symbol's type, e.g. STT_FUNC or STT_OBJECT.
+    pub(crate) symbol_type: u8,
+    pub(crate) resolution: Resolution,
+    /// If the symbol is data, the size of the copy that we made of it in our .bss. The address of
+    /// the copy is the address of the resolution.
+    pub(crate) copy_size: Option<u64>,
+    /// Whether the symbol is a function whose address we take directly rather than via the GOT.
+    /// Our PLT entry is then the function's canonical address, which the dynamic loader also needs
+    /// to use when binding references to the function from shared objects.
+    pub(crate) is_canonical_plt: bool,
+    /// Whether all our references to the symbol are weak, in which case we import it as a weak
+    /// symbol.
+    pub(crate) is_weak: bool,
 }
 
 #[derive(Debug)]
@@ -292,7 +433,10 @@ trait SymbolRequestHandler<'data>: std::fmt::Display {
             );
         }
         if common.symbol_states[local_index] == TargetResolutionKind::None {
-            if self.load_symbol(symbol_id, local_index, resources, queue)? == SymbolKind::IFunc {
+            let requester = symbol_request.requester;
+            if self.load_symbol(symbol_id, local_index, requester, resources, queue)?
+                == SymbolKind::IFunc
+            {
                 common = self.common_mut();
                 common.mem_sizes.got += elf::GOT_ENTRY_SIZE;
                 common.mem_sizes.plt += elf::PLT_ENTRY_SIZE;
@@ -308,8 +452,13 @@ trait SymbolRequestHandler<'data>: std::fmt::Display {
                 if common.symbol_states[local_index] < TargetResolutionKind::Got {
                     common.symbol_states[local_index] = TargetResolutionKind::Got;
                     common.mem_sizes.got += elf::GOT_ENTRY_SIZE;
-                    if resources.symbol_db.args.is_relocatable() {
-                        common.mem_sizes.rela_dyn += elf::RELA_ENTRY_SIZE;
+                    if resources.symbol_db.is_dynamic(symbol_id) {
+                        // The dynamic loader fills in the GOT entry once it has bound the symbol.
+                        common.mem_sizes.rela_dyn_general += elf::RELA_ENTRY_SIZE;
+                    } else if resources.symbol_db.args.is_relocatable()
+                        && !resources.symbol_db.is_absolute_defsym(symbol_id)
+                    {
+                        common.mem_sizes.rela_dyn_relative += elf::RELA_ENTRY_SIZE;
                     }
                 }
                 if matches!(
@@ -325,12 +474,22 @@ trait SymbolRequestHandler<'data>: std::fmt::Display {
                 if common.symbol_states[local_index] < TargetResolutionKind::Got {
                     common.symbol_states[local_index] = TargetResolutionKind::GotTlsOffset;
                     common.mem_sizes.got += elf::GOT_ENTRY_SIZE;
+                    if resources.symbol_db.args.shared {
+                        common.mem_sizes.rela_dyn_general += elf::RELA_ENTRY_SIZE;
+                    }
                 }
             }
             TargetResolutionKind::GotTlsDouble => match &common.symbol_states[local_index] {
                 TargetResolutionKind::Address => {
                     common.symbol_states[local_index] = TargetResolutionKind::GotTlsDouble;
                     common.mem_sizes.got += elf::GOT_ENTRY_SIZE * 2;
+                    if resources.symbol_db.args.shared {
+                        common.mem_sizes.rela_dyn_general += elf::RELA_ENTRY_SIZE;
+                    }
+                    if resources.symbol_db.is_dynamic(symbol_id) {
+                        // The symbol's offset within its module also isn't known until runtime.
+                        common.mem_sizes.rela_dyn_general += elf::RELA_ENTRY_SIZE;
+                    }
                 }
                 TargetResolutionKind::GotTlsDouble => {}
                 other => {
@@ -350,6 +509,7 @@ trait SymbolRequestHandler<'data>: std::fmt::Display {
         &mut self,
         symbol_id: GlobalSymbolId,
         local_index: usize,
+        requester: Requester,
         resources: &GraphResources<'data, 'scope>,
         queue: &mut LocalWorkQueue,
     ) -> Result<SymbolKind>;
@@ -367,6 +527,7 @@ impl<'data> SymbolRequestHandler<'data> for ObjectLayoutState<'data> {
         &mut self,
         symbol_id: GlobalSymbolId,
         local_index: usize,
+        requester: Requester,
         resources: &GraphResources<'data, 'scope>,
         queue: &mut LocalWorkQueue,
     ) -> Result<SymbolKind> {
@@ -384,9 +545,13 @@ impl<'data> SymbolRequestHandler<'data> for ObjectLayoutState<'data> {
             _ => SymbolKind::Regular,
         };
         if let object::SymbolSection::Section(section_id) = local_symbol.section() {
-            self.state
-                .sections_required
-                .push(SectionRequest::new(section_id));
+            self.state.sections_required.push(SectionRequest::new(
+                section_id,
+                LiveReason {
+                    requester,
+                    symbol_id: Some(symbol_id),
+                },
+            ));
             self.load_sections(resources, queue)?;
         } else if let Some(common) = CommonSymbol::new(&local_symbol)? {
             *self
@@ -424,6 +589,7 @@ impl<'data> SymbolRequestHandler<'data> for InternalLayoutState<'data> {
         &mut self,
         _symbol_id: GlobalSymbolId,
         _local_index: usize,
+        _requester: Requester,
         _resources: &GraphResources<'data, 'scope>,
         _queue: &mut LocalWorkQueue,
     ) -> Result<SymbolKind> {
@@ -474,6 +640,27 @@ impl CommonLayoutState {
         strings_offset_start
     }
 
+    /// Returns the index in .dynsym of our first dynamic symbol and the offset in .dynstr of our
+    /// first string, then advances `memory_offsets` past our dynamic symbols and strings.
+    fn finalise_dynamic_symbols_layout(
+        &self,
+        memory_offsets: &mut OutputSectionPartMap<u64>,
+        section_layouts: &OutputSectionMap<OutputRecordLayout>,
+    ) -> (u64, u64) {
+        let dynsym_start_index = (memory_offsets.dynsym
+            - section_layouts
+                .built_in(output_section_id::DYNSYM)
+                .mem_offset)
+            / size_of::<elf::SymtabEntry>() as u64;
+        let dynstr_start_offset = memory_offsets.dynstr
+            - section_layouts
+                .built_in(output_section_id::DYNSTR)
+                .mem_offset;
+        memory_offsets.dynsym += self.mem_sizes.dynsym;
+        memory_offsets.dynstr += self.mem_sizes.dynstr;
+        (dynsym_start_index, dynstr_start_offset)
+    }
+
     fn create_global_address_emitter<'state>(
         &'state self,
         memory_offsets: &OutputSectionPartMap<u64>,
@@ -495,6 +682,11 @@ struct ObjectLayoutState<'data> {
     object: Box<File<'data>>,
     state: ObjectLayoutMutableState<'data>,
     section_frame_data: Vec<SectionFrameData>,
+    /// Our sections that contain strings to be merged with those from other objects.
+    merge_strings_sections: Vec<resolution::MergeStringsFileSection<'data>>,
+    /// The global symbols that we define and that we're going to put in .dynsym. Only populated
+    /// when producing a shared object.
+    exported_symbols: Vec<GlobalSymbolId>,
 }
 
 /// The parts of `ObjectLayoutState` that we mutate during layout. Separate so that we can pass
@@ -516,9 +708,16 @@ struct ObjectLayoutMutableState<'data> {
     /// A queue of sections that we need to load.
     sections_required: Vec<SectionRequest>,
 
+    /// Why each of our sections was loaded. Only populated if `--why-live` was given, otherwise
+    /// empty. Indexed the same as `sections`.
+    live_reasons: Vec<Option<LiveReason>>,
+
     loaded_symbols: Vec<GlobalSymbolId>,
     local_symbol_resolutions: Vec<resolution::LocalSymbolResolution>,
 
+    /// References from our live sections to our string-merge sections via section symbols.
+    merge_section_refs: Vec<resolution::MergeSectionRef>,
+
     cies: SmallVec<[CieAtOffset<'data>; 2]>,
 }
 
@@ -582,11 +781,21 @@ pub(crate) enum TargetResolutionKind {
 }
 
 struct DynamicLayoutState<'data> {
-    // TODO: Use this or remove it.
-    #[allow(dead_code)]
     object: Box<File<'data>>,
     input: InputRef<'data>,
+    /// Symbol states are indexed by the symbol's index in the shared object's dynamic symbol
+    /// table.
+    common: CommonLayoutState,
+    /// The name that we'll put in DT_NEEDED. Set once we finalise sizes.
+    soname: &'data [u8],
+    /// The symbols that we import from this shared object in the order that we'll emit them.
     referenced_symbol_ids: Vec<GlobalSymbolId>,
+    /// Whether we take the address of each function directly. Indexed like symbol states.
+    address_taken: Vec<bool>,
+    /// Whether each symbol has at least one non-weak reference. Symbols that are only weakly
+    /// referenced are imported as weak, so the dynamic loader doesn't require a definition.
+    /// Indexed like symbol states.
+    strongly_referenced: Vec<bool>,
 }
 
 #[derive(Debug)]
@@ -631,6 +840,10 @@ struct GraphResources<'data, 'scope> {
 
     errors: Mutex<Vec<Error>>,
 
+    /// References to symbols that aren't defined anywhere. We collect these rather than failing on
+    /// the first one so that we can report all of them together.
+    undefined_references: Mutex<Vec<UndefinedReference>>,
+
     waiting_workers: ArrayQueue<FileWorker<'data>>,
 
     /// A queue in which we store threads when they're idle so that other threads can wake them up
@@ -655,6 +868,27 @@ enum WorkItem {
 struct SymbolRequest {
     symbol_id: GlobalSymbolId,
     target_resolution_kind: TargetResolutionKind,
+    requester: Requester,
+    /// Whether the request is for a weak reference to the symbol.
+    weak_reference: bool,
+}
+
+/// What requested that a symbol or section be loaded. Used to explain why a section was kept when
+/// `--why-live` is given.
+#[derive(Copy, Clone, Debug)]
+enum Requester {
+    /// A GC root. e.g. the entry point, a symbol passed to -u or a section that's always retained.
+    Root,
+    /// A relocation in the specified section of an object.
+    Section(FileId, object::SectionIndex),
+}
+
+/// Why a section was loaded. Only the first request for each section is recorded.
+#[derive(Copy, Clone, Debug)]
+struct LiveReason {
+    requester: Requester,
+    /// The symbol via which the section was referenced, if it wasn't referenced directly.
+    symbol_id: Option<GlobalSymbolId>,
 }
 
 struct GlobalSymbolAddress {
@@ -682,13 +916,13 @@ impl<'data> Layout<'data> {
     }
 
     pub(crate) fn entry_symbol_address(&self) -> Result<u64> {
-        let symbol_id = self.internal().entry_symbol_id;
+        let symbol_id = match self.internal().entry {
+            EntryPoint::None => return Ok(0),
+            EntryPoint::Address(address) => return Ok(address),
+            EntryPoint::Symbol(symbol_id) => symbol_id,
+        };
         match self.global_symbol_resolution(symbol_id) {
             Some(SymbolResolution::Resolved(resolution)) => Ok(resolution.address),
-            Some(SymbolResolution::Dynamic) => {
-                let symbol_name = self.symbol_db.symbol_name(symbol_id);
-                bail!("{symbol_name} can't be from a dynamic library",)
-            }
             None => {
                 let symbol_name = self.symbol_db.symbol_name(symbol_id);
                 bail!("{symbol_name} symbol was present, but didn't get loaded")
@@ -711,8 +945,8 @@ impl<'data> Layout<'data> {
         alignment::USIZE.align_up(tls_end)
     }
 
-    pub(crate) fn offset_of_section(&self, section_id: OutputSectionId) -> u64 {
-        self.section_layouts.get(section_id).file_offset as u64
+    pub(crate) fn mem_address_of_section(&self, section_id: OutputSectionId) -> u64 {
+        self.section_layouts.get(section_id).mem_offset
     }
 
     pub(crate) fn size_of_section(&self, section_id: OutputSectionId) -> u64 {
@@ -846,6 +1080,22 @@ fn compute_segment_layout(
         .active_segment_ids
         .iter()
         .map(|&id| {
+            if id == crate::program_segments::PHDR {
+                // The PHDR segment covers just the program headers, which are within our headers
+                // section.
+                let headers = section_layouts.built_in(output_section_id::HEADERS);
+                let size = header_info.program_headers_size();
+                return SegmentLayout {
+                    id,
+                    sizes: OutputRecordLayout {
+                        file_size: size as usize,
+                        mem_size: size,
+                        alignment: alignment::USIZE,
+                        file_offset: headers.file_offset + elf::PHEADER_OFFSET as usize,
+                        mem_offset: headers.mem_offset + elf::PHEADER_OFFSET,
+                    },
+                };
+            }
             let r = &complete[id.as_usize()];
             SegmentLayout {
                 id,
@@ -907,13 +1157,14 @@ fn find_required_sections<'data>(
     let num_workers = file_states.len();
     let (worker_slots, workers) = create_worker_slots(file_states, output_sections);
 
-    let num_threads = symbol_db.args.num_threads.get();
+    let num_threads = rayon::current_num_threads();
 
     let idle_threads = (num_threads > 1).then(|| ArrayQueue::new(num_threads - 1));
     let resources = &GraphResources {
         symbol_db,
         worker_slots,
         errors: Mutex::new(Vec::new()),
+        undefined_references: Mutex::new(Vec::new()),
         waiting_workers: ArrayQueue::new(num_workers),
         // NB, the -1 is because we never want all our threads to be idle. Once the last thread is
         // about to go idle, we're done and need to wake up and terminate all the the threads.
@@ -975,14 +1226,130 @@ fn find_required_sections<'data>(
         }
     });
     let mut errors: Vec<Error> = core::mem::take(resources.errors.lock().unwrap().as_mut());
-    // TODO: Figure out good way to report more than one error.
-    if let Some(error) = errors.pop() {
-        return Err(error);
+    let undefined_references: Vec<UndefinedReference> =
+        core::mem::take(&mut *resources.undefined_references.lock().unwrap());
+    if !undefined_references.is_empty() {
+        errors.push(undefined_symbols_error(undefined_references));
+    }
+    if errors.len() == 1 {
+        return Err(errors.pop().unwrap());
+    }
+    if !errors.is_empty() {
+        // Errors arrive from multiple threads, so sort them to keep our output deterministic.
+        let mut messages: Vec<String> = errors.iter().map(|e| format!("{e:#}")).collect();
+        messages.sort();
+        bail!(
+            "{}",
+            resources.symbol_db.args.diagnostics.limit_errors(messages)
+        );
     }
     let worker_slots = &resources.worker_slots;
     unwrap_worker_states(worker_slots)
 }
 
+/// Looks up the entry point named `entry_name`. Like other linkers, if there's no symbol with that
+/// name, we try interpreting it as an address and failing that, warn and leave the entry point
+/// unset.
+fn entry_point(entry_name: &str, resources: &GraphResources) -> EntryPoint {
+    if let Some(symbol_id) = resources
+        .symbol_db
+        .symbol_ids
+        .get(&SymbolName::new(entry_name.as_bytes()))
+    {
+        return EntryPoint::Symbol(*symbol_id);
+    }
+    if let Some(address) = crate::args::parse_number(entry_name) {
+        return EntryPoint::Address(address);
+    }
+    resources.symbol_db.args.diagnostics.warning(format_args!(
+        "cannot find entry symbol {entry_name}; not setting start address"
+    ));
+    EntryPoint::None
+}
+
+/// A reference from a particular place in an input file to a symbol that isn't defined.
+struct UndefinedReference {
+    symbol_name: String,
+
+    /// Where the reference is from, e.g. "foo.o:(.text.bar+0x12) in function `bar`".
+    location: String,
+}
+
+impl UndefinedReference {
+    fn new(
+        worker: &ObjectLayoutState,
+        symbol_db: &SymbolDb,
+        local_index: object::SymbolIndex,
+        section: &elf::Section,
+        offset: u64,
+    ) -> Result<Self> {
+        let name = worker.object.symbol_by_index(local_index)?.name_bytes()?;
+        // If the reference was redirected by `--wrap`, report the symbol that we actually looked
+        // for.
+        let name = symbol_db
+            .wrapped_reference_name(name)
+            .unwrap_or_else(|| name.to_owned());
+        let mut location = format!(
+            "{}:({}+0x{offset:x})",
+            worker.input,
+            String::from_utf8_lossy(section.name_bytes()?)
+        );
+        // Symbol addresses in relocatable objects are relative to the start of their section, so
+        // the containing function is the last one that starts at or before the reference.
+        let function = worker
+            .object
+            .symbols()
+            .filter(|sym| {
+                sym.kind() == object::SymbolKind::Text
+                    && sym.section_index() == Some(section.index())
+                    && sym.address() <= offset
+            })
+            .max_by_key(|sym| sym.address());
+        if let Some(function) = function {
+            location += &format!(
+                " in function `{}`",
+                String::from_utf8_lossy(function.name_bytes()?)
+            );
+        }
+        Ok(UndefinedReference {
+            symbol_name: String::from_utf8_lossy(&name).into_owned(),
+            location,
+        })
+    }
+}
+
+/// Builds a single error listing all undefined symbols, each followed by the places that reference
+/// it.
+fn undefined_symbols_error(references: Vec<UndefinedReference>) -> Error {
+    const MAX_LOCATIONS_PER_SYMBOL: usize = 10;
+    let mut by_symbol: std::collections::BTreeMap<String, Vec<String>> = Default::default();
+    for reference in references {
+        by_symbol
+            .entry(reference.symbol_name)
+            .or_default()
+            .push(reference.location);
+    }
+    let mut message = String::new();
+    for (symbol_name, mut locations) in by_symbol {
+        locations.sort();
+        locations.dedup();
+        if !message.is_empty() {
+            message.push('\n');
+        }
+        message += &format!("undefined symbol: {symbol_name}");
+        for location in locations.iter().take(MAX_LOCATIONS_PER_SYMBOL) {
+            message += &format!("\n>>> referenced by {location}");
+        }
+        if locations.len() > MAX_LOCATIONS_PER_SYMBOL {
+            message += &format!(
+                "\n>>> referenced {} more times",
+                locations.len() - MAX_LOCATIONS_PER_SYMBOL
+            );
+        }
+    }
+    anyhow::anyhow!(message)
+}
+
 fn create_worker_slots<'data>(
     file_states: Vec<resolution::ResolvedFile<'data>>,
     output_sections: &OutputSections<'data>,
@@ -1040,7 +1407,7 @@ impl<'data> FileWorker<'data> {
         match &mut self.state {
             FileLayoutState::Object(s) => s.activate(resources, &mut self.queue),
             FileLayoutState::Internal(s) => s.activate(resources),
-            FileLayoutState::Dynamic(_) => todo!(),
+            FileLayoutState::Dynamic(_) => Ok(()),
             FileLayoutState::NotLoaded => Ok(()),
         }
     }
@@ -1066,12 +1433,16 @@ impl LocalWorkQueue {
         &mut self,
         symbol_id: GlobalSymbolId,
         plt_got_flags: TargetResolutionKind,
+        requester: Requester,
+        weak_reference: bool,
         resources: &GraphResources,
     ) {
         let symbol = resources.symbol_db.symbol(symbol_id);
         let symbol_request = SymbolRequest {
             symbol_id,
             target_resolution_kind: plt_got_flags,
+            requester,
+            weak_reference,
         };
         self.send_work(
             resources,
@@ -1086,6 +1457,25 @@ impl<'data, 'scope> GraphResources<'data, 'scope> {
         self.errors.lock().unwrap().push(error);
     }
 
+    fn report_undefined_reference(&self, reference: UndefinedReference) {
+        self.undefined_references.lock().unwrap().push(reference);
+    }
+
+    /// Requests that the file that defines `symbol_id` load it, along with whatever section
+    /// defines it. The symbol is treated as a GC root.
+    fn request_global_symbol(&self, symbol_id: GlobalSymbolId) {
+        let file_id = self.symbol_db.symbol(symbol_id).file_id;
+        self.send_work(
+            file_id,
+            WorkItem::LoadGlobalSymbol(SymbolRequest {
+                symbol_id,
+                target_resolution_kind: Default::default(),
+                requester: Requester::Root,
+                weak_reference: false,
+            }),
+        );
+    }
+
     /// Sends all work in `work` to the worker for `file_id`. Leaves `work` empty so that it can be
     /// reused.
     fn send_work(&self, file_id: FileId, work: WorkItem) {
@@ -1132,7 +1522,10 @@ impl<'data> FileLayoutState<'data> {
                 .finalise_sizes(symbol_db, output_sections)
                 .with_context(|| format!("finalise_sizes failed for {s}"))?,
             FileLayoutState::Internal(s) => s.finalise_sizes(symbol_db)?,
-            _ => (),
+            FileLayoutState::Dynamic(s) => s
+                .finalise_sizes(symbol_db)
+                .with_context(|| format!("finalise_sizes failed for {}", s.input))?,
+            FileLayoutState::NotLoaded => (),
         }
         Ok(())
     }
@@ -1168,7 +1561,9 @@ impl<'data> FileLayoutState<'data> {
             FileLayoutState::Internal(state) => {
                 state.handle_symbol_request(symbol_request, resources, queue)?;
             }
-            FileLayoutState::Dynamic(state) => state.load_symbol(symbol_request, resources)?,
+            FileLayoutState::Dynamic(state) => {
+                state.handle_symbol_request(symbol_request, resources)?;
+            }
             FileLayoutState::NotLoaded => {}
         }
         Ok(())
@@ -1178,7 +1573,7 @@ impl<'data> FileLayoutState<'data> {
         match self {
             Self::Object(s) => Some(&s.state.common.mem_sizes),
             Self::Internal(s) => Some(&s.common.mem_sizes),
-            Self::Dynamic(_) => None,
+            Self::Dynamic(s) => Some(&s.common.mem_sizes),
             Self::NotLoaded => None,
         }
     }
@@ -1205,7 +1600,12 @@ impl<'data> FileLayoutState<'data> {
                 &mut addresses_out,
                 symbol_db,
             )?),
-            Self::Dynamic(s) => FileLayout::Dynamic(s.finalise_layout(&mut addresses_out)),
+            Self::Dynamic(s) => FileLayout::Dynamic(s.finalise_layout(
+                memory_offsets.unwrap(),
+                section_layouts,
+                &mut addresses_out,
+                symbol_db,
+            )?),
             Self::NotLoaded => {
                 return Ok(None);
             }
@@ -1219,7 +1619,7 @@ impl<'data> FileLayout<'data> {
         match self {
             Self::Object(s) => Some(&s.mem_sizes),
             Self::Internal(s) => Some(&s.mem_sizes),
-            Self::Dynamic(_) => None,
+            Self::Dynamic(s) => Some(&s.mem_sizes),
         }
     }
 
@@ -1250,7 +1650,7 @@ impl<'data> std::fmt::Display for FileLayoutState<'data> {
         match self {
             Self::Object(s) => std::fmt::Display::fmt(s, f),
             Self::Internal(_) => std::fmt::Display::fmt("<internal>", f),
-            Self::Dynamic(_) => todo!(),
+            Self::Dynamic(s) => std::fmt::Display::fmt(&s.input, f),
             Self::NotLoaded => std::fmt::Display::fmt("<not-loaded>", f),
         }
     }
@@ -1261,7 +1661,7 @@ impl<'data> std::fmt::Display for FileLayout<'data> {
         match self {
             Self::Object(s) => std::fmt::Display::fmt(s, f),
             Self::Internal(_) => std::fmt::Display::fmt("<internal>", f),
-            Self::Dynamic(_) => todo!(),
+            Self::Dynamic(s) => std::fmt::Display::fmt(&s.input, f),
         }
     }
 }
@@ -1293,13 +1693,15 @@ impl<'data> std::fmt::Display for ObjectLayout<'data> {
 struct SectionRequest {
     id: object::SectionIndex,
     resolution_kind: TargetResolutionKind,
+    reason: LiveReason,
 }
 
 impl SectionRequest {
-    fn new(id: object::SectionIndex) -> Self {
+    fn new(id: object::SectionIndex, reason: LiveReason) -> Self {
         Self {
             id,
             resolution_kind: Default::default(),
+            reason,
         }
     }
 }
@@ -1317,11 +1719,39 @@ impl<'data> Section<'data> {
         let size = object_section.size();
         let section_data = object_section.data()?;
         let is_relocatable = resources.symbol_db.args.is_relocatable();
+        let requester = Requester::Section(worker.file_id(), section_id);
         for (rel_offset, rel) in object_section.relocations() {
-            let rel_info = RelInfo::new(&rel, rel_offset, &object_section, resources.args())?;
-            process_relocation(&rel_info, resources, &mut worker.state, queue);
+            let rel_info = RelInfo::new(
+                &rel,
+                rel_offset,
+                &object_section,
+                &worker.state.local_symbol_resolutions,
+                resources.symbol_db,
+            )?;
+            process_relocation(&rel_info, resources, &mut worker.state, requester, queue);
+            if let Some(merge_ref) = worker.merge_section_ref(&rel)? {
+                worker.state.merge_section_refs.push(merge_ref);
+            }
+            if let object::RelocationTarget::Symbol(local_index) = rel_info.target {
+                if matches!(
+                    worker.state.local_symbol_resolutions[local_index.0],
+                    LocalSymbolResolution::UndefinedSymbol
+                ) {
+                    resources.report_undefined_reference(UndefinedReference::new(
+                        worker,
+                        resources.symbol_db,
+                        local_index,
+                        &object_section,
+                        rel_offset,
+                    )?);
+                }
+            }
             if is_relocatable && rel_info.is_relocation_position_dependent() {
-                worker.state.common.mem_sizes.rela_dyn += elf::RELA_ENTRY_SIZE;
+                if rel_info.target_is_dynamic {
+                    worker.state.common.mem_sizes.rela_dyn_general += elf::RELA_ENTRY_SIZE;
+                } else if !worker.references_absolute_defsym(&rel_info, resources.symbol_db) {
+                    worker.state.common.mem_sizes.rela_dyn_relative += elf::RELA_ENTRY_SIZE;
+                }
             }
         }
         let section = Section {
@@ -1349,7 +1779,7 @@ impl<'data> Section<'data> {
             (_, TargetResolutionKind::Got) => {
                 mem_sizes.got += elf::GOT_ENTRY_SIZE;
                 if args.is_relocatable() {
-                    mem_sizes.rela_dyn += elf::RELA_ENTRY_SIZE;
+                    mem_sizes.rela_dyn_relative += elf::RELA_ENTRY_SIZE;
                 }
             }
             (
@@ -1357,6 +1787,9 @@ impl<'data> Section<'data> {
                 TargetResolutionKind::GotTlsOffset,
             ) => {
                 mem_sizes.got += elf::GOT_ENTRY_SIZE;
+                if args.shared {
+                    mem_sizes.rela_dyn_general += elf::RELA_ENTRY_SIZE;
+                }
             }
             (TargetResolutionKind::Got, TargetResolutionKind::Plt) => {
                 mem_sizes.plt += elf::PLT_ENTRY_SIZE;
@@ -1364,7 +1797,7 @@ impl<'data> Section<'data> {
             (_, TargetResolutionKind::Plt) => {
                 mem_sizes.got += elf::GOT_ENTRY_SIZE;
                 if args.is_relocatable() {
-                    mem_sizes.rela_dyn += elf::RELA_ENTRY_SIZE;
+                    mem_sizes.rela_dyn_relative += elf::RELA_ENTRY_SIZE;
                 }
                 mem_sizes.plt += elf::PLT_ENTRY_SIZE;
             }
@@ -1373,6 +1806,9 @@ impl<'data> Section<'data> {
                 TargetResolutionKind::GotTlsDouble,
             ) => {
                 mem_sizes.got += elf::GOT_ENTRY_SIZE * 2;
+                if args.shared {
+                    mem_sizes.rela_dyn_general += elf::RELA_ENTRY_SIZE;
+                }
             }
             (_, TargetResolutionKind::Address) => {}
             (a, b) => bail!("Unexpected state transition {a:?} {b:?}"),
@@ -1396,6 +1832,8 @@ struct RelInfo {
     target: object::RelocationTarget,
     resolution_kind: TargetResolutionKind,
     relocation_kind: RelocationKind,
+    /// Whether the target is a symbol that the dynamic loader binds at runtime.
+    target_is_dynamic: bool,
 }
 
 impl RelInfo {
@@ -1403,19 +1841,32 @@ impl RelInfo {
         rel: &object::Relocation,
         rel_offset: u64,
         section: &elf::Section,
-        args: &Args,
+        local_symbol_resolutions: &[LocalSymbolResolution],
+        symbol_db: &SymbolDb,
     ) -> Result<Self> {
         let object::RelocationFlags::Elf { mut r_type } = rel.flags() else {
             unreachable!();
         };
-        if let Some(relaxation) = Relaxation::new(r_type, section.data()?, rel_offset as usize) {
-            r_type = relaxation.new_relocation_kind(true);
+        let target_is_dynamic = match rel.target() {
+            object::RelocationTarget::Symbol(local_index) => local_symbol_resolutions
+                [local_index.0]
+                .referenced_symbol_id()
+                .is_some_and(|symbol_id| symbol_db.is_dynamic(symbol_id)),
+            _ => false,
+        };
+        // We can't bypass the GOT for a symbol whose address isn't known until runtime.
+        if !target_is_dynamic {
+            if let Some(relaxation) = Relaxation::new(r_type, section.data()?, rel_offset as usize)
+            {
+                r_type = relaxation.new_relocation_kind(true);
+            }
         }
         let rel_info = RelocationKindInfo::from_raw(r_type)?;
         Ok(Self {
             target: rel.target(),
-            resolution_kind: TargetResolutionKind::new(rel_info, args)?,
+            resolution_kind: TargetResolutionKind::new(rel_info, symbol_db.args)?,
             relocation_kind: rel_info.kind,
+            target_is_dynamic,
         })
     }
 
@@ -1428,10 +1879,12 @@ fn process_relocation(
     rel: &RelInfo,
     resources: &GraphResources<'_, '_>,
     state: &mut ObjectLayoutMutableState<'_>,
+    requester: Requester,
     queue: &mut LocalWorkQueue,
 ) {
     let mut section_to_load = None;
     let mut symbol_to_load = None;
+    let mut weak_reference = false;
     let plt_got_flags = rel.resolution_kind;
     match rel.target {
         object::RelocationTarget::Symbol(local_sym_index) => {
@@ -1460,6 +1913,7 @@ fn process_relocation(
                 }
                 LocalSymbolResolution::WeakRefToGlobal(symbol_id) => {
                     symbol_to_load = Some(symbol_id);
+                    weak_reference = true;
                 }
                 LocalSymbolResolution::LocalSection(local_section_index) => {
                     section_to_load = Some(local_section_index);
@@ -1480,11 +1934,21 @@ fn process_relocation(
         state.sections_required.push(SectionRequest {
             id: local_section_index,
             resolution_kind: plt_got_flags,
+            reason: LiveReason {
+                requester,
+                symbol_id: None,
+            },
         });
     }
 
     if let Some(symbol_id) = symbol_to_load {
-        queue.send_symbol_request(symbol_id, plt_got_flags, resources);
+        queue.send_symbol_request(
+            symbol_id,
+            plt_got_flags,
+            requester,
+            weak_reference,
+            resources,
+        );
     }
 }
 
@@ -1519,13 +1983,14 @@ impl TargetResolutionKind {
     pub(crate) fn needs_got_entry(&self) -> bool {
         !matches!(self, Self::Address | Self::None)
     }
+
+    fn is_tls(self) -> bool {
+        matches!(self, Self::GotTlsOffset | Self::GotTlsDouble)
+    }
 }
 
 impl<'data> InternalLayoutState<'data> {
-    fn new(
-        input_state: resolution::ResolvedInternal<'data>,
-        output_sections: &OutputSections,
-    ) -> Self {
+    fn new(input_state: resolution::ResolvedInternal, output_sections: &OutputSections) -> Self {
         let mut layout = Self {
             common: CommonLayoutState::new(
                 input_state.file_id,
@@ -1534,22 +1999,15 @@ impl<'data> InternalLayoutState<'data> {
             ),
             defined: input_state.defined,
             symbol_definitions: input_state.symbol_definitions,
-            entry_symbol_id: None,
+            entry: EntryPoint::None,
             needs_tlsld_got_entry: false,
-            merged_strings: input_state.merged_strings,
+            merged_strings: OutputSectionMap::with_size(output_sections.len()),
             identity: crate::identity::linker_identity(),
             header_info: None,
+            dynamic_linker: input_state.dynamic_linker,
+            undefined_dynamic_symbols: Vec::new(),
         };
 
-        layout.merged_strings.for_each(|section_id, merged| {
-            if merged.len > 0 {
-                *layout
-                    .common
-                    .mem_sizes
-                    .regular_mut(section_id, alignment::MIN) += merged.len;
-            }
-        });
-
         // Allocate space to store the identify of the linker in the .comment section.
         *layout
             .common
@@ -1569,29 +2027,82 @@ impl<'data> InternalLayoutState<'data> {
     }
 
     fn activate(&mut self, resources: &GraphResources<'_, '_>) -> Result {
-        let symbol_id = *resources
+        let args = resources.symbol_db.args;
+        // Shared objects only get an entry point if one is explicitly requested.
+        let entry_name = resources
             .symbol_db
-            .symbol_ids
-            .get(&SymbolName::new(b"_start"))
-            .context("Missing _start symbol")?;
-        self.entry_symbol_id = Some(symbol_id);
-        let file_id = resources.symbol_db.symbol(symbol_id).file_id;
-        resources.send_work(
-            file_id,
-            WorkItem::LoadGlobalSymbol(SymbolRequest {
-                symbol_id,
-                target_resolution_kind: Default::default(),
-            }),
-        );
-        if resources.symbol_db.args.tls_mode() == TlsMode::Preserve {
+            .entry
+            .or((!args.shared).then_some("_start"));
+        if let Some(entry_name) = entry_name {
+            self.entry = entry_point(entry_name, resources);
+            if let EntryPoint::Symbol(symbol_id) = self.entry {
+                resources.request_global_symbol(symbol_id);
+            }
+        }
+        // Symbols that `--defsym` defines relative to other symbols need those other symbols to be
+        // loaded.
+        for defsym in &args.defsym {
+            if let DefsymValue::Symbol(target_name, _) = &defsym.value {
+                let symbol_id = *resources
+                    .symbol_db
+                    .symbol_ids
+                    .get(&SymbolName::new(target_name.as_bytes()))
+                    .with_context(|| format!("--defsym: Undefined symbol `{target_name}`"))?;
+                resources.request_global_symbol(symbol_id);
+            }
+        }
+        // Symbols passed to -u or --require-defined are roots, so whatever defines them needs to be
+        // kept, even if nothing references them.
+        for name in &args.undefined {
+            if let Some(&symbol_id) = resources
+                .symbol_db
+                .symbol_ids
+                .get(&SymbolName::new(name.as_bytes()))
+            {
+                resources.request_global_symbol(symbol_id);
+            }
+        }
+        for name in &args.require_defined {
+            let symbol_id = *resources
+                .symbol_db
+                .symbol_ids
+                .get(&SymbolName::new(name.as_bytes()))
+                .with_context(|| format!("Required symbol `{name}` not defined"))?;
+            resources.request_global_symbol(symbol_id);
+        }
+        if args.tls_mode() == TlsMode::Preserve {
             // Allocate space for a TLS module number and offset for use with TLSLD relocations.
             self.common.mem_sizes.got += elf::GOT_ENTRY_SIZE * 2;
+            if args.shared {
+                // Our TLS module number won't be known until runtime.
+                self.common.mem_sizes.rela_dyn_general += elf::RELA_ENTRY_SIZE;
+            }
             self.needs_tlsld_got_entry = true;
         }
 
-        if resources.symbol_db.args.is_relocatable() {
+        if resources.symbol_db.needs_dynamic() {
+            // The first symbol and string are null.
             self.common.mem_sizes.dynstr += 1;
             self.common.mem_sizes.dynsym += size_of::<elf::SymtabEntry>() as u64;
+            // The chain entry in .hash for the null symbol. The header and buckets get allocated
+            // once we know how many dynamic symbols there are.
+            self.common.mem_sizes.hash += size_of::<u32>() as u64;
+        }
+
+        if let Some(soname) = args.soname.as_ref() {
+            if args.shared {
+                self.common.mem_sizes.dynstr += soname.len() as u64 + 1;
+            }
+        }
+
+        if resources.symbol_db.num_dynamic_inputs > 0 && !args.link_static && !args.shared {
+            if let Some(dynamic_linker) = self.dynamic_linker.as_ref() {
+                *self
+                    .common
+                    .mem_sizes
+                    .regular_mut(output_section_id::INTERP, alignment::MIN) +=
+                    dynamic_linker.as_bytes_with_nul().len() as u64;
+            }
         }
         Ok(())
     }
@@ -1601,8 +2112,12 @@ impl<'data> InternalLayoutState<'data> {
             self.allocate_symbol_table_sizes(symbol_db)?;
         }
 
-        if symbol_db.args.pie {
-            self.common.mem_sizes.dynamic += (elf_writer::NUM_DYNAMIC_ENTRIES
+        if symbol_db.args.shared {
+            self.allocate_undefined_dynamic_symbols(symbol_db)?;
+        }
+
+        if symbol_db.needs_dynamic() {
+            self.common.mem_sizes.dynamic += (elf_writer::num_dynamic_entries(symbol_db)
                 * core::mem::size_of::<crate::elf::DynamicEntry>())
                 as u64;
         }
@@ -1639,7 +2154,9 @@ impl<'data> InternalLayoutState<'data> {
             .zip(self.symbol_definitions.iter())
             .for_each(|(symbol_state, definition)| {
                 if *symbol_state != TargetResolutionKind::None {
-                    keep_sections[definition.section_id().as_usize()] = true;
+                    if let Some(section_id) = definition.section_id() {
+                        keep_sections[section_id.as_usize()] = true;
+                    }
                 }
             });
         let num_sections = keep_sections.iter().filter(|p| **p).count();
@@ -1672,6 +2189,11 @@ impl<'data> InternalLayoutState<'data> {
                 }
             }
         });
+        // The PHDR segment is only of use to the dynamic loader, so we only emit it if we're also
+        // emitting an INTERP segment.
+        if !keep_segments[crate::program_segments::INTERP.as_usize()] {
+            keep_segments[crate::program_segments::PHDR.as_usize()] = false;
+        }
         let active_segment_ids = (0..crate::program_segments::MAX_SEGMENTS)
             .filter(|i| keep_segments[*i])
             .map(ProgramSegmentId::new)
@@ -1699,6 +2221,15 @@ impl<'data> InternalLayoutState<'data> {
             .sum::<u64>();
         total_sizes.shstrtab += self.common.mem_sizes.shstrtab;
 
+        // Now that all dynamic symbols have been allocated, we can size the .hash header and
+        // buckets.
+        if total_sizes.hash > 0 {
+            let num_symbols = total_sizes.dynsym / size_of::<elf::SymtabEntry>() as u64;
+            let header_and_buckets = size_of::<u32>() as u64 * (2 + hash_bucket_count(num_symbols));
+            self.common.mem_sizes.hash += header_and_buckets;
+            total_sizes.hash += header_and_buckets;
+        }
+
         self.header_info = Some(header_info);
     }
 
@@ -1711,7 +2242,7 @@ impl<'data> InternalLayoutState<'data> {
             let sym_state = &self.common.symbol_states[local_index.0];
             // Don't allocate space for symbols that are in our headers section, since it doesn't
             // have an entry.
-            if def_info.section_id() == output_section_id::HEADERS {
+            if def_info.section_id() == Some(output_section_id::HEADERS) {
                 continue;
             }
 
@@ -1727,6 +2258,27 @@ impl<'data> InternalLayoutState<'data> {
         Ok(())
     }
 
+    /// Allocates space in .dynsym, .dynstr and .hash for referenced symbols that nothing defined.
+    fn allocate_undefined_dynamic_symbols(&mut self, symbol_db: &SymbolDb<'_>) -> Result {
+        for &symbol_id in &self.defined {
+            let local_index = symbol_db
+                .symbol(symbol_id)
+                .local_index_for_file(self.file_id())?;
+            if !matches!(
+                self.symbol_definitions[local_index.0],
+                InternalSymDefInfo::Undefined | InternalSymDefInfo::UndefinedWeak
+            ) || self.common.symbol_states[local_index.0] == TargetResolutionKind::None
+            {
+                continue;
+            }
+            self.common.mem_sizes.dynsym += size_of::<elf::SymtabEntry>() as u64;
+            self.common.mem_sizes.dynstr += symbol_db.symbol_name(symbol_id).len() as u64 + 1;
+            self.common.mem_sizes.hash += size_of::<u32>() as u64;
+            self.undefined_dynamic_symbols.push(symbol_id);
+        }
+        Ok(())
+    }
+
     fn finalise_layout(
         self,
         memory_offsets: &mut OutputSectionPartMap<u64>,
@@ -1747,6 +2299,8 @@ impl<'data> InternalLayoutState<'data> {
             // undefined behaviour, so we can put whatever pointer we like here.
             plt_address: NonZeroU64::new(0xdead),
             kind: TargetResolutionKind::Plt,
+            is_absolute: false,
+            dynamic_symbol_index: None,
         };
         memory_offsets.got += elf::GOT_ENTRY_SIZE;
 
@@ -1757,6 +2311,14 @@ impl<'data> InternalLayoutState<'data> {
             address
         });
 
+        let (dynsym_start_index, dynstr_start_offset) = self
+            .common
+            .finalise_dynamic_symbols_layout(memory_offsets, section_layouts);
+
+        // Our undefined dynamic symbols come straight after the null symbol, in the same order as
+        // in `defined`.
+        let mut next_dynamic_symbol_index = dynsym_start_index;
+
         // Define symbols that are optionally put at the start/end of some sections.
         let mut emitter = self
             .common
@@ -1780,8 +2342,20 @@ impl<'data> InternalLayoutState<'data> {
                     let sec = &section_layouts.built_in(*section_id);
                     sec.mem_offset + sec.mem_size
                 }
+                InternalSymDefInfo::Defsym(index) => match symbol_db.args.defsym[*index].value {
+                    DefsymValue::Value(value) => value,
+                    // Filled in by `resolve_defsym_aliases` once the target has an address.
+                    DefsymValue::Symbol(..) => 0,
+                },
+                InternalSymDefInfo::Undefined | InternalSymDefInfo::UndefinedWeak => 0,
+            };
+            let dynamic_symbol_index = if symbol_db.is_dynamic(*symbol_id) {
+                next_dynamic_symbol_index += 1;
+                Some(nonzero_dynsym_index(next_dynamic_symbol_index)?)
+            } else {
+                None
             };
-            if let Some(global) = emitter.build_symbol(*symbol_id, address)? {
+            if let Some(global) = emitter.build_symbol(*symbol_id, address, dynamic_symbol_index)? {
                 global_addresses_out.push(global);
             }
         }
@@ -1793,10 +2367,14 @@ impl<'data> InternalLayoutState<'data> {
             symbol_definitions: self.symbol_definitions,
             undefined_symbol_resolution,
             strings_offset_start,
-            entry_symbol_id: self.entry_symbol_id.unwrap(),
+            entry: self.entry,
             tlsld_got_entry,
             merged_strings: self.merged_strings,
             identity: self.identity,
+            dynamic_linker: self.dynamic_linker,
+            undefined_dynamic_symbols: self.undefined_dynamic_symbols,
+            dynsym_start_index,
+            dynstr_start_offset,
             header_info: self
                 .header_info
                 .expect("we should have computed header info by now"),
@@ -1831,6 +2409,8 @@ impl<'data> ObjectLayoutState<'data> {
             input: input_state.input,
             object: input_state.object,
             section_frame_data: Default::default(),
+            merge_strings_sections: input_state.merge_strings_sections,
+            exported_symbols: Default::default(),
             state: ObjectLayoutMutableState {
                 common: CommonLayoutState::new(
                     input_state.file_id,
@@ -1842,7 +2422,9 @@ impl<'data> ObjectLayoutState<'data> {
                 local_symbol_states: vec![LocalSymbolState::Unloaded; num_symbols],
                 plt_got_flags: vec![TargetResolutionKind::None; num_symbols],
                 sections_required: Default::default(),
+                live_reasons: Default::default(),
                 local_symbol_resolutions: input_state.local_symbol_resolutions,
+                merge_section_refs: Default::default(),
                 cies: Default::default(),
             },
         }
@@ -1853,15 +2435,24 @@ impl<'data> ObjectLayoutState<'data> {
         resources: &GraphResources<'data, 'scope>,
         queue: &mut LocalWorkQueue,
     ) -> Result {
+        if !resources.args().why_live.is_empty() {
+            self.state.live_reasons = vec![None; self.state.sections.len()];
+        }
+        // Without GC, every section of a loaded object is a root.
+        let retain_all = !resources.args().gc_sections;
         let mut eh_frame_section = None;
         for (i, section) in self.state.sections.iter().enumerate() {
             match section {
                 SectionSlot::Unloaded(unloaded_section) => {
-                    let retain = unloaded_section.details.retain;
+                    let retain = retain_all || unloaded_section.details.retain;
                     if retain {
-                        self.state
-                            .sections_required
-                            .push(SectionRequest::new(object::SectionIndex(i)));
+                        self.state.sections_required.push(SectionRequest::new(
+                            object::SectionIndex(i),
+                            LiveReason {
+                                requester: Requester::Root,
+                                symbol_id: None,
+                            },
+                        ));
                     }
                 }
                 SectionSlot::EhFrameData(index) => {
@@ -1880,9 +2471,112 @@ impl<'data> ObjectLayoutState<'data> {
                 queue,
             )?;
         }
+        if resources.symbol_db.args.shared {
+            self.request_exported_symbols(resources, queue);
+        }
         self.load_sections(resources, queue)
     }
 
+    /// Returns what `merge_strings` needs in order to merge our strings with those of other
+    /// objects. A string is live if a relocation in one of our live sections references it, or if
+    /// it's referenced via a global symbol that we were asked to load.
+    fn strings_to_merge(&mut self, args: &Args) -> resolution::ObjectStringsToMerge<'data, '_> {
+        let gc_sections = args.gc_sections;
+        let section_refs = &mut self.state.merge_section_refs;
+        section_refs.sort_unstable_by_key(|r| (r.section_index.0, r.input_offset));
+        section_refs.dedup_by_key(|r| (r.section_index.0, r.input_offset));
+        let local_symbol_states = &self.state.local_symbol_states;
+        let symbol_states = &self.state.common.symbol_states;
+        resolution::ObjectStringsToMerge {
+            sections: &mut self.merge_strings_sections,
+            local_symbol_resolutions: &mut self.state.local_symbol_resolutions,
+            section_refs,
+            is_live: Box::new(move |symbol_index| {
+                !gc_sections
+                    || local_symbol_states[symbol_index.0] == LocalSymbolState::Loaded
+                    || symbol_states[symbol_index.0] != TargetResolutionKind::None
+            }),
+        }
+    }
+
+    /// Returns whether `rel` references a symbol that `--defsym` defined as an absolute value.
+    fn references_absolute_defsym(&self, rel: &RelInfo, symbol_db: &SymbolDb) -> bool {
+        let object::RelocationTarget::Symbol(local_index) = rel.target else {
+            return false;
+        };
+        self.state.local_symbol_resolutions[local_index.0]
+            .global_symbol_id()
+            .is_some_and(|symbol_id| symbol_db.is_absolute_defsym(symbol_id))
+    }
+
+    /// Returns the referenced offset if `rel` references one of our string-merge sections via the
+    /// section's symbol.
+    fn merge_section_ref(
+        &self,
+        rel: &object::Relocation,
+    ) -> Result<Option<resolution::MergeSectionRef>> {
+        let object::RelocationTarget::Symbol(local_index) = rel.target() else {
+            return Ok(None);
+        };
+        let LocalSymbolResolution::LocalSection(section_index) =
+            self.state.local_symbol_resolutions[local_index.0]
+        else {
+            return Ok(None);
+        };
+        if !matches!(
+            self.state.sections[section_index.0],
+            SectionSlot::MergeStrings(_)
+        ) {
+            return Ok(None);
+        }
+        let symbol = self.object.symbol_by_index(local_index)?;
+        if symbol.kind() != object::SymbolKind::Section {
+            return Ok(None);
+        }
+        Ok(Some(resolution::MergeSectionRef {
+            section_index,
+            input_offset: resolution::MergeSectionRef::referenced_offset(symbol.address(), rel)?,
+            resolution: None,
+        }))
+    }
+
+    /// Returns a description of one of our sections, suitable for use in diagnostics.
+    fn section_description(&self, section_index: object::SectionIndex) -> Result<String> {
+        let section = self.object.section_by_index(section_index)?;
+        Ok(format!(
+            "{}:({})",
+            self.input,
+            String::from_utf8_lossy(section.name_bytes()?)
+        ))
+    }
+
+    /// Requests all the symbols that we'll export. When producing a shared object, we export all
+    /// of our non-hidden global symbols, so they need to be loaded regardless of whether anything
+    /// within the link references them.
+    fn request_exported_symbols(&self, resources: &GraphResources, queue: &mut LocalWorkQueue) {
+        for symbol in self.object.symbols() {
+            if !resources.symbol_db.is_exported(&symbol) {
+                continue;
+            }
+            let LocalSymbolResolution::Global(symbol_id) =
+                self.state.local_symbol_resolutions[symbol.index().0]
+            else {
+                continue;
+            };
+            // If another file's definition was selected, then it's up to that file to export it.
+            if resources.symbol_db.symbol(symbol_id).file_id != self.file_id() {
+                continue;
+            }
+            queue.send_symbol_request(
+                symbol_id,
+                TargetResolutionKind::Address,
+                Requester::Root,
+                false,
+                resources,
+            );
+        }
+    }
+
     /// Loads sections in `sections_required` (which may be empty).
     fn load_sections<'scope>(
         &mut self,
@@ -1894,6 +2588,9 @@ impl<'data> ObjectLayoutState<'data> {
             match &self.state.sections[section_id.0] {
                 SectionSlot::Unloaded(unloaded) => {
                     let unloaded = *unloaded;
+                    if let Some(reason) = self.state.live_reasons.get_mut(section_id.0) {
+                        *reason = Some(section_request.reason);
+                    }
                     let mut section =
                         Section::create(self, queue, &unloaded, section_id, resources)?;
                     let sec_id = match unloaded.output_section_id {
@@ -1922,8 +2619,9 @@ impl<'data> ObjectLayoutState<'data> {
                                 * u64::from(frame_data.num_fdes);
                         // Request loading of any sections/symbols referenced by the FDEs for our
                         // section.
+                        let requester = Requester::Section(self.file_id(), section_id);
                         for rel in &frame_data.refs {
-                            process_relocation(rel, resources, &mut self.state, queue)
+                            process_relocation(rel, resources, &mut self.state, requester, queue)
                         }
                     }
                     self.state.sections[section_id.0] = SectionSlot::Loaded(section);
@@ -1936,9 +2634,18 @@ impl<'data> ObjectLayoutState<'data> {
                     );
                 }
                 SectionSlot::Loaded(_) | SectionSlot::EhFrameData(..) => {}
+                SectionSlot::DiscardedGroupMember => {
+                    // References to local symbols in discarded group members were redirected when
+                    // we discarded the group and references to global symbols go to the copy that
+                    // we selected, so there's nothing to load here.
+                }
                 SectionSlot::MergeStrings(_) => {
-                    // We currently always load everything in merge-string sections. i.e. we don't
-                    // GC unreferenced data. So there's nothing to do here.
+                    // Strings are merged once we know which ones are referenced, which is after all
+                    // sections have been loaded, so all we need to do here is record why, since the
+                    // section's slot doesn't change when it's first requested.
+                    if let Some(reason @ None) = self.state.live_reasons.get_mut(section_id.0) {
+                        *reason = Some(section_request.reason);
+                    }
                 }
             }
             if let SectionSlot::Loaded(section) = &mut self.state.sections[section_id.0] {
@@ -1957,6 +2664,9 @@ impl<'data> ObjectLayoutState<'data> {
         if !symbol_db.args.strip_all {
             self.allocate_symtab_space(symbol_db)?;
         }
+        if symbol_db.args.shared {
+            self.allocate_dynsym_space(symbol_db)?;
+        }
         // TODO: Deduplicate CIEs from different objects, then only allocate space for those CIEs
         // that we "won".
         for cie in &self.state.cies {
@@ -1965,6 +2675,27 @@ impl<'data> ObjectLayoutState<'data> {
         Ok(())
     }
 
+    fn allocate_dynsym_space(&mut self, symbol_db: &SymbolDb<'_>) -> Result {
+        // Sort so that the order of our exports doesn't depend on the order in which symbols were
+        // requested.
+        self.state.loaded_symbols.sort();
+        for &symbol_id in &self.state.loaded_symbols {
+            let local_index = symbol_db
+                .symbol(symbol_id)
+                .local_index_for_file(self.state.common.file_id)?;
+            let sym = self.object.symbol_by_index(local_index)?;
+            if !symbol_db.is_exported(&sym) {
+                continue;
+            }
+            let mem_sizes = &mut self.state.common.mem_sizes;
+            mem_sizes.dynsym += size_of::<elf::SymtabEntry>() as u64;
+            mem_sizes.dynstr += sym.name_bytes()?.len() as u64 + 1;
+            mem_sizes.hash += size_of::<u32>() as u64;
+            self.exported_symbols.push(symbol_id);
+        }
+        Ok(())
+    }
+
     fn allocate_symtab_space(&mut self, symbol_db: &SymbolDb<'_>) -> Result {
         let mut num_locals = 0;
         let mut num_globals = 0;
@@ -2023,6 +2754,11 @@ impl<'data> ObjectLayoutState<'data> {
         let file_id = self.file_id();
         let mut sections = self.state.sections;
 
+        let (dynsym_start_index, dynstr_start_offset) = self
+            .state
+            .common
+            .finalise_dynamic_symbols_layout(memory_offsets, section_layouts);
+
         let mut emitter = self
             .state
             .common
@@ -2087,7 +2823,24 @@ impl<'data> ObjectLayoutState<'data> {
                 address = *offset;
                 *offset += common.size;
             }
-            if let Some(global) = emitter.build_symbol(*symbol_id, address)? {
+            // Our exported symbols that can be interposed are referenced via their .dynsym entries,
+            // which are in the same order as `exported_symbols`.
+            let dynamic_symbol_index = if symbol_db.is_dynamic(*symbol_id) {
+                let position = self
+                    .exported_symbols
+                    .binary_search(symbol_id)
+                    .ok()
+                    .with_context(|| {
+                        format!(
+                            "Interposable symbol `{}` wasn't exported",
+                            symbol_db.symbol_name(*symbol_id)
+                        )
+                    })?;
+                Some(nonzero_dynsym_index(dynsym_start_index + position as u64)?)
+            } else {
+                None
+            };
+            if let Some(global) = emitter.build_symbol(*symbol_id, address, dynamic_symbol_index)? {
                 global_addresses_out.push(global);
             }
         }
@@ -2104,12 +2857,16 @@ impl<'data> ObjectLayoutState<'data> {
             object: self.object,
             mem_sizes: self.state.common.mem_sizes,
             local_symbol_resolutions: self.state.local_symbol_resolutions,
+            merge_section_refs: self.state.merge_section_refs,
             sections,
             section_resolutions,
             strings_offset_start,
             plt_relocations,
             loaded_symbols: self.state.loaded_symbols,
             eh_frame_start_address: memory_offsets.eh_frame,
+            exported_symbols: self.exported_symbols,
+            dynsym_start_index,
+            dynstr_start_offset,
         })
     }
 }
@@ -2119,6 +2876,7 @@ impl MergedStringStartAddresses {
     fn compute(
         output_sections: &OutputSections<'_>,
         starting_mem_offsets_by_file: &[Option<OutputSectionPartMap<u64>>],
+        merged_strings: &OutputSectionMap<resolution::MergedStringsSection>,
     ) -> Self {
         let mut addresses = OutputSectionMap::with_size(output_sections.len());
         if let Some(internal_start_offsets) =
@@ -2126,8 +2884,8 @@ impl MergedStringStartAddresses {
         {
             for i in 0..output_sections.num_regular_sections() {
                 let section_id = OutputSectionId::regular(i as u16);
-                *addresses.get_mut(section_id) =
-                    *internal_start_offsets.regular(section_id, alignment::MIN);
+                *addresses.get_mut(section_id) = *internal_start_offsets
+                    .regular(section_id, merged_strings.get(section_id).alignment);
             }
         }
         Self { addresses }
@@ -2151,6 +2909,11 @@ impl MergedStringStartAddresses {
     pub(crate) fn resolve(&self, res: resolution::MergedStringResolution) -> u64 {
         self.addresses.get(res.output_section_id) + res.offset
     }
+
+    /// Returns the address of the start of the merged strings in `section_id`.
+    pub(crate) fn section_address(&self, section_id: OutputSectionId) -> u64 {
+        *self.addresses.get(section_id)
+    }
 }
 
 /// Returns whether we should copy a symbol with the specified name into the output symbol table.
@@ -2194,9 +2957,15 @@ fn process_eh_frame_data<'data>(
                     break;
                 }
                 // We currently always load all CIEs, so any relocations found in CIEs always need
-                // to be processed.
-                let rel_info = RelInfo::new(rel, *rel_offset, &eh_frame_section, resources.args())?;
-                process_relocation(&rel_info, resources, state, queue);
+                // to be processed. This makes whatever they reference GC roots.
+                let rel_info = RelInfo::new(
+                    rel,
+                    *rel_offset,
+                    &eh_frame_section,
+                    &state.local_symbol_resolutions,
+                    resources.symbol_db,
+                )?;
+                process_relocation(&rel_info, resources, state, Requester::Root, queue);
                 if let object::RelocationTarget::Symbol(local_sym_index) = rel.target() {
                     let symbol_res = state.local_symbol_resolutions[local_sym_index.0];
                     match symbol_res {
@@ -2242,7 +3011,8 @@ fn process_eh_frame_data<'data>(
                             rel,
                             *rel_offset,
                             &eh_frame_section,
-                            resources.args(),
+                            &state.local_symbol_resolutions,
+                            resources.symbol_db,
                         )?);
                     }
                     relocations.next();
@@ -2314,13 +3084,16 @@ impl<'state> GlobalAddressEmitter<'state> {
         &mut self,
         symbol_id: GlobalSymbolId,
         address: u64,
+        dynamic_symbol_index: Option<NonZeroU32>,
     ) -> Result<Option<GlobalSymbolAddress>> {
         let local_symbol_index = self
             .symbol_db
             .symbol(symbol_id)
             .local_index_for_file(self.file_id)?;
-        let resolution =
+        let mut resolution =
             self.create_resolution(self.symbol_states[local_symbol_index.0], address)?;
+        resolution.is_absolute = self.symbol_db.is_absolute_defsym(symbol_id);
+        resolution.dynamic_symbol_index = dynamic_symbol_index;
         Ok(Some(GlobalSymbolAddress {
             symbol_id,
             resolution: SymbolResolution::Resolved(resolution),
@@ -2337,6 +3110,8 @@ impl<'state> GlobalAddressEmitter<'state> {
             got_address: None,
             plt_address: None,
             kind: res_kind,
+            is_absolute: false,
+            dynamic_symbol_index: None,
         };
         match res_kind {
             TargetResolutionKind::None | TargetResolutionKind::Address => {}
@@ -2392,11 +3167,36 @@ impl<'data> resolution::ResolvedFile<'data> {
             resolution::ResolvedFile::Internal(s) => {
                 FileLayoutState::Internal(Box::new(InternalLayoutState::new(s, output_sections)))
             }
+            resolution::ResolvedFile::Dynamic(s) => {
+                FileLayoutState::Dynamic(Box::new(DynamicLayoutState::new(s, output_sections)))
+            }
             resolution::ResolvedFile::NotLoaded => FileLayoutState::NotLoaded,
         }
     }
 }
 
+/// Converts an index in .dynsym into the form stored in resolutions.
+fn nonzero_dynsym_index(index: u64) -> Result<NonZeroU32> {
+    NonZeroU32::new(u32::try_from(index).context("Too many dynamic symbols")?)
+        .context("Dynamic symbols can't be at index 0")
+}
+
+/// Returns the number of buckets to use in .hash for the supplied number of dynamic symbols. Like
+/// GNU ld, we pick the largest of a fixed set of bucket counts that's not more than the number of
+/// symbols.
+fn hash_bucket_count(num_symbols: u64) -> u64 {
+    const BUCKET_COUNTS: &[u64] = &[
+        1, 3, 17, 37, 67, 97, 131, 197, 263, 521, 1031, 2053, 4099, 8209, 16411, 32771, 65537,
+        131101, 262147,
+    ];
+    BUCKET_COUNTS
+        .iter()
+        .copied()
+        .take_while(|count| *count <= num_symbols)
+        .last()
+        .unwrap_or(1)
+}
+
 impl Resolution {
     pub(crate) fn got_address(&self) -> Result<u64> {
         Ok(self.got_address.context("Missing GOT address")?.get())
@@ -2449,44 +3249,252 @@ fn layout_section_parts(
     )
 }
 
-#[allow(dead_code)]
 impl<'data> DynamicLayoutState<'data> {
-    fn load_symbol(
-        &mut self,
-        _symbol_request: SymbolRequest,
-        _resources: &GraphResources,
-    ) -> Result {
-        todo!();
-        // let local_info = self
-        //     .defined_symbols
-        //     .get(symbol_request.symbol_id, resources.symbol_db)?;
-        // let local_symbol_id = local_info.local_symbol_id;
-        // // TODO: Now that we know what range of symbol IDs we are responsible for, we could do a vec
-        // // lookup here instead of a hashset insertion.
-        // if self.referenced_symbols.insert(local_symbol_id) {
-        //     self.referenced_symbol_ids.push(symbol_request.symbol_id)
-        // }
-    }
-
-    fn new(input_state: symbol_db::ObjectSymbols<'data>) -> DynamicLayoutState<'data> {
+    fn new(
+        input_state: resolution::ResolvedDynamic<'data>,
+        output_sections: &OutputSections,
+    ) -> DynamicLayoutState<'data> {
+        let num_symbols = input_state
+            .object
+            .dynamic_symbols()
+            .map(|sym| sym.index().0 + 1)
+            .max()
+            .unwrap_or(0);
         DynamicLayoutState {
+            common: CommonLayoutState::new(input_state.file_id, num_symbols, output_sections),
             object: input_state.object,
-            referenced_symbol_ids: Default::default(),
             input: input_state.input,
+            soname: &[],
+            referenced_symbol_ids: Default::default(),
+            address_taken: vec![false; num_symbols],
+            strongly_referenced: vec![false; num_symbols],
         }
     }
 
-    fn finalise_layout(self, addresses_out: &mut Vec<GlobalSymbolAddress>) -> DynamicLayout<'data> {
-        addresses_out.extend(self.referenced_symbol_ids.iter().map(|symbol_id| {
-            GlobalSymbolAddress {
-                symbol_id: *symbol_id,
-                resolution: SymbolResolution::Dynamic,
+    fn file_id(&self) -> FileId {
+        self.common.file_id
+    }
+
+    fn handle_symbol_request(
+        &mut self,
+        symbol_request: SymbolRequest,
+        resources: &GraphResources<'data, '_>,
+    ) -> Result {
+        let symbol_id = symbol_request.symbol_id;
+        let local_index = resources
+            .symbol_db
+            .symbol(symbol_id)
+            .local_index_for_file(self.file_id())?;
+        let sym = dynamic_symbol(&self.object, local_index)?;
+        let shared = resources.symbol_db.args.shared;
+        let requested = match (sym.kind(), symbol_request.target_resolution_kind) {
+            (
+                object::SymbolKind::Tls,
+                kind @ (TargetResolutionKind::GotTlsOffset | TargetResolutionKind::GotTlsDouble),
+            ) => kind,
+            (object::SymbolKind::Tls, kind) => {
+                bail!("Unsupported {kind:?} reference to TLS variable in shared object")
+            }
+            (_, TargetResolutionKind::GotTlsOffset | TargetResolutionKind::GotTlsDouble) => {
+                bail!("TLS reference to non-TLS symbol in shared object")
             }
-        }));
-        DynamicLayout { input: self.input }
+            // When we're producing a shared object, direct references to data or to a function's
+            // address get dynamic relocations against the symbol, so we don't need a copy or a PLT
+            // entry.
+            (_, TargetResolutionKind::Address) if shared => TargetResolutionKind::Address,
+            // Otherwise, data symbols get copied into our .bss, so they have an address that's local
+            // to us. Any GOT entry will then point at our copy.
+            (object::SymbolKind::Data, TargetResolutionKind::Got | TargetResolutionKind::Plt) => {
+                TargetResolutionKind::Got
+            }
+            (object::SymbolKind::Data, _) => TargetResolutionKind::Address,
+            (_, TargetResolutionKind::Got) => TargetResolutionKind::Got,
+            // Functions don't have an address that's local to us, so any direct references need to
+            // go via a PLT entry.
+            (_, _) => TargetResolutionKind::Plt,
+        };
+        if requested == TargetResolutionKind::Plt
+            && symbol_request.target_resolution_kind == TargetResolutionKind::Address
+        {
+            self.address_taken[local_index.0] = true;
+        }
+        if !symbol_request.weak_reference {
+            self.strongly_referenced[local_index.0] = true;
+        }
+        let state = &mut self.common.symbol_states[local_index.0];
+        if *state == TargetResolutionKind::None {
+            self.referenced_symbol_ids.push(symbol_id);
+        } else if *state != requested && (state.is_tls() || requested.is_tls()) {
+            bail!("Symbol referenced as both {state:?} and {requested:?}");
+        }
+        *state = (*state).max(requested);
+        Ok(())
+    }
+
+    fn finalise_sizes(&mut self, symbol_db: &SymbolDb) -> Result {
+        self.soname = self.soname()?;
+        // Requests can arrive in any order, so sort in order to make our output deterministic.
+        self.referenced_symbol_ids.sort();
+        let mem_sizes = &mut self.common.mem_sizes;
+        mem_sizes.dynstr += self.soname.len() as u64 + 1;
+        for &symbol_id in &self.referenced_symbol_ids {
+            let local_index = symbol_db
+                .symbol(symbol_id)
+                .local_index_for_file(self.common.file_id)?;
+            let sym = dynamic_symbol(&self.object, local_index)?;
+            mem_sizes.dynsym += size_of::<elf::SymtabEntry>() as u64;
+            mem_sizes.dynstr += sym.name_bytes()?.len() as u64 + 1;
+            mem_sizes.hash += size_of::<u32>() as u64;
+            if let Some((alignment, size)) = copy_allocation(&sym, symbol_db.args)? {
+                *mem_sizes.regular_mut(output_section_id::BSS, alignment) += size;
+                mem_sizes.rela_dyn_general += elf::RELA_ENTRY_SIZE;
+            }
+            match self.common.symbol_states[local_index.0] {
+                TargetResolutionKind::Plt => {
+                    mem_sizes.got += elf::GOT_ENTRY_SIZE;
+                    mem_sizes.plt += elf::PLT_ENTRY_SIZE;
+                    mem_sizes.rela_plt += elf::RELA_ENTRY_SIZE;
+                }
+                TargetResolutionKind::Got | TargetResolutionKind::GotTlsOffset => {
+                    mem_sizes.got += elf::GOT_ENTRY_SIZE;
+                    mem_sizes.rela_dyn_general += elf::RELA_ENTRY_SIZE;
+                }
+                TargetResolutionKind::GotTlsDouble => {
+                    mem_sizes.got += elf::GOT_ENTRY_SIZE * 2;
+                    mem_sizes.rela_dyn_general += elf::RELA_ENTRY_SIZE * 2;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the name that we should put in DT_NEEDED for this shared object. That's its
+    /// DT_SONAME if it has one, otherwise the filename that we were given.
+    fn soname(&self) -> Result<&'data [u8]> {
+        if let Some(dynamic) = self.object.section_by_name(".dynamic") {
+            for entry_bytes in dynamic.data()?.chunks_exact(size_of::<elf::DynamicEntry>()) {
+                let entry: elf::DynamicEntry = bytemuck::pod_read_unaligned(entry_bytes);
+                if entry.tag != elf::DynamicTag::SoName as u64 {
+                    continue;
+                }
+                let dynstr = self
+                    .object
+                    .section_by_name(".dynstr")
+                    .context("DT_SONAME present, but no .dynstr")?
+                    .data()?;
+                let name = dynstr
+                    .get(entry.value as usize..)
+                    .context("DT_SONAME outside of .dynstr")?;
+                let len = memchr::memchr(0, name).context("Unterminated DT_SONAME")?;
+                return Ok(&name[..len]);
+            }
+        }
+        Ok(self.input.file.filename.as_os_str().as_encoded_bytes())
+    }
+
+    fn finalise_layout(
+        self,
+        memory_offsets: &mut OutputSectionPartMap<u64>,
+        section_layouts: &OutputSectionMap<OutputRecordLayout>,
+        addresses_out: &mut Vec<GlobalSymbolAddress>,
+        symbol_db: &SymbolDb,
+    ) -> Result<DynamicLayout<'data>> {
+        let (dynsym_start_index, dynstr_start_offset) = self
+            .common
+            .finalise_dynamic_symbols_layout(memory_offsets, section_layouts);
+
+        let mut symbols = Vec::with_capacity(self.referenced_symbol_ids.len());
+        for &symbol_id in &self.referenced_symbol_ids {
+            let local_index = symbol_db
+                .symbol(symbol_id)
+                .local_index_for_file(self.file_id())?;
+            let sym = dynamic_symbol(&self.object, local_index)?;
+            let kind = self.common.symbol_states[local_index.0];
+            let mut resolution = Resolution {
+                address: 0,
+                got_address: None,
+                plt_address: None,
+                kind,
+                is_absolute: false,
+                dynamic_symbol_index: None,
+            };
+            let copy = copy_allocation(&sym, symbol_db.args)?;
+            if let Some((alignment, size)) = copy {
+                let offset = memory_offsets.regular_mut(output_section_id::BSS, alignment);
+                resolution.address = *offset;
+                *offset += size;
+            }
+            if symbol_db.is_dynamic(symbol_id) {
+                resolution.dynamic_symbol_index = Some(nonzero_dynsym_index(
+                    dynsym_start_index + symbols.len() as u64,
+                )?);
+            }
+            if kind.needs_got_entry() {
+                resolution.got_address = Some(NonZeroU64::new(memory_offsets.got).unwrap());
+                memory_offsets.got += elf::GOT_ENTRY_SIZE;
+                if kind == TargetResolutionKind::GotTlsDouble {
+                    memory_offsets.got += elf::GOT_ENTRY_SIZE;
+                }
+            }
+            if kind == TargetResolutionKind::Plt {
+                let plt_address = NonZeroU64::new(memory_offsets.plt).unwrap();
+                memory_offsets.plt += elf::PLT_ENTRY_SIZE;
+                resolution.plt_address = Some(plt_address);
+                resolution.address = plt_address.get();
+            }
+            addresses_out.push(GlobalSymbolAddress {
+                symbol_id,
+                resolution: SymbolResolution::Resolved(resolution),
+            });
+            let symbol_type = match sym.flags() {
+                object::SymbolFlags::Elf { st_info, .. } => st_info & 0xf,
+                _ => 0,
+            };
+            symbols.push(DynamicSymbol {
+                name: sym.name_bytes()?,
+                symbol_type,
+                resolution,
+                copy_size: copy.map(|(_, size)| size),
+                is_canonical_plt: self.address_taken[local_index.0],
+                is_weak: !self.strongly_referenced[local_index.0],
+            });
+        }
+
+        Ok(DynamicLayout {
+            input: self.input,
+            mem_sizes: self.common.mem_sizes,
+            soname: self.soname,
+            dynsym_start_index,
+            dynstr_start_offset,
+            symbols,
+        })
     }
 }
 
+fn dynamic_symbol<'data, 'file>(
+    object: &'file File<'data>,
+    index: object::SymbolIndex,
+) -> Result<elf::Symbol<'data, 'file>> {
+    Ok(object
+        .dynamic_symbol_table()
+        .context("Shared object has no dynamic symbol table")?
+        .symbol_by_index(index)?)
+}
+
+/// Returns the alignment and size of the copy that we need to make of `sym` in our .bss, or None
+/// if `sym` isn't a data symbol. Shared objects don't record the alignment of symbols, so we infer
+/// it from the symbol's address, capping it at the size of a cache line. Only executables make
+/// copies. When we're producing a shared object, references go via dynamic relocations against the
+/// original.
+fn copy_allocation(sym: &elf::Symbol, args: &Args) -> Result<Option<(Alignment, u64)>> {
+    if args.shared || sym.kind() != object::SymbolKind::Data {
+        return Ok(None);
+    }
+    let alignment = Alignment::new(1 << sym.address().trailing_zeros().min(6))?;
+    Ok(Some((alignment, alignment.align_up(sym.size()))))
+}
+
 impl<'data> ObjectLayout<'data> {
     pub(crate) fn global_id_for_symbol(&self, sym: &elf::Symbol) -> Option<GlobalSymbolId> {
         self.local_symbol_resolutions[sym.index().0].global_symbol_id()
@@ -2504,7 +3512,7 @@ impl<'data> std::fmt::Debug for FileLayoutState<'data> {
         match self {
             Self::Object(s) => f.debug_tuple("Object").field(&s.input).finish(),
             Self::Internal(_) => f.debug_tuple("Internal").finish(),
-            Self::Dynamic(_) => f.debug_tuple("Dynamic").finish(),
+            Self::Dynamic(s) => f.debug_tuple("Dynamic").field(&s.input).finish(),
             Self::NotLoaded => "<not loaded>".fmt(f),
         }
     }
@@ -2519,6 +3527,138 @@ fn print_symbol_info(symbol_db: &SymbolDb, files: &[resolution::ResolvedFile], n
     println!("Symbol `{name}` defined by {file}");
 }
 
+/// Merges strings from the string-merge sections of all loaded objects, discarding any that no live
+/// section references. The merged strings are then owned by the internal file.
+fn merge_strings<'data>(
+    layout_states: &mut [FileLayoutState<'data>],
+    output_sections: &OutputSections,
+    args: &Args,
+) -> Result {
+    let mut objects: Vec<resolution::ObjectStringsToMerge> = layout_states
+        .iter_mut()
+        .filter_map(|file| match file {
+            FileLayoutState::Object(object) if !object.merge_strings_sections.is_empty() => {
+                Some(object.strings_to_merge(args))
+            }
+            _ => None,
+        })
+        .collect();
+    let merged_strings = resolution::merge_strings(&mut objects, output_sections, args)?;
+    drop(objects);
+    let FileLayoutState::Internal(internal) = &mut layout_states[INTERNAL_FILE_ID.as_usize()]
+    else {
+        unreachable!();
+    };
+    merged_strings.for_each(|section_id, merged| {
+        if merged.len > 0 {
+            *internal
+                .common
+                .mem_sizes
+                .regular_mut(section_id, merged.alignment) += merged.len;
+        }
+    });
+    internal.merged_strings = merged_strings;
+    Ok(())
+}
+
+/// Reports each section of each loaded object that was discarded because nothing referenced it,
+/// together with any strings that were discarded from string-merge sections.
+fn report_gc_sections(files: &[FileLayoutState], args: &Args) -> Result {
+    for file in files {
+        let FileLayoutState::Object(object) = file else {
+            continue;
+        };
+        for (i, slot) in object.state.sections.iter().enumerate() {
+            if let SectionSlot::Unloaded(_) = slot {
+                let section = object.object.section_by_index(object::SectionIndex(i))?;
+                args.diagnostics.info(format_args!(
+                    "removing unused section '{}' in file '{}' (size {:#x})",
+                    String::from_utf8_lossy(section.name_bytes()?),
+                    object.input,
+                    section.size()
+                ));
+            }
+        }
+        for merge in &object.merge_strings_sections {
+            if merge.discarded_len == 0 {
+                continue;
+            }
+            let section = object.object.section_by_index(merge.section_index())?;
+            let name = String::from_utf8_lossy(section.name_bytes()?);
+            if merge.discarded_len == section.size() {
+                args.diagnostics.info(format_args!(
+                    "removing unused section '{name}' in file '{}' (size {:#x})",
+                    object.input,
+                    section.size()
+                ));
+            } else {
+                args.diagnostics.info(format_args!(
+                    "removing unused strings from section '{name}' in file '{}' (size {:#x})",
+                    object.input, merge.discarded_len
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// For each symbol matching one of the patterns passed to `--why-live`, prints the chain of
+/// references from a GC root to the section that defines the symbol.
+fn print_why_live(symbol_db: &SymbolDb, files: &[FileLayoutState]) -> Result {
+    let patterns = &symbol_db.args.why_live;
+    let mut symbol_ids: Vec<GlobalSymbolId> = symbol_db
+        .symbol_ids
+        .iter()
+        .filter(|(name, _)| {
+            patterns
+                .iter()
+                .any(|pattern| glob_matches(pattern.as_bytes(), name.bytes()))
+        })
+        .map(|(_, symbol_id)| *symbol_id)
+        .collect();
+    symbol_ids.sort();
+    for symbol_id in symbol_ids {
+        let name = symbol_db.symbol_name(symbol_id);
+        let symbol = symbol_db.symbol(symbol_id);
+        let FileLayoutState::Object(object) = &files[symbol.file_id.as_usize()] else {
+            println!("`{name}` is not defined by a loaded object");
+            continue;
+        };
+        let local_symbol = object
+            .object
+            .symbol_by_index(symbol.local_index_for_file(symbol.file_id)?)?;
+        let object::SymbolSection::Section(section_index) = local_symbol.section() else {
+            println!("`{name}` is not defined in a section of {}", object.input);
+            continue;
+        };
+        let Some(mut reason) = object.state.live_reasons[section_index.0] else {
+            println!("`{name}` is not live");
+            continue;
+        };
+        println!("`{name}` is live because:");
+        let mut section = object.section_description(section_index)?;
+        loop {
+            let via = reason
+                .symbol_id
+                .map(|symbol_id| format!(" via `{}`", symbol_db.symbol_name(symbol_id)))
+                .unwrap_or_default();
+            let Requester::Section(file_id, section_index) = reason.requester else {
+                println!("  {section} is a GC root{via}");
+                break;
+            };
+            let FileLayoutState::Object(requester) = &files[file_id.as_usize()] else {
+                bail!("Internal error: Section requested by non-object {file_id}");
+            };
+            let requester_section = requester.section_description(section_index)?;
+            println!("  {section} is referenced{via} by {requester_section}");
+            reason = requester.state.live_reasons[section_index.0]
+                .context("Internal error: Requesting section has no live reason")?;
+            section = requester_section;
+        }
+    }
+    Ok(())
+}
+
 /// Performs layout of sections and segments then makes sure that the loadable segments don't
 /// overlap and that sections don't overlap.
 #[test]
@@ -2589,3 +3729,36 @@ fn test_no_disallowed_overlaps() {
         last_file = seg_layout.sizes.file_offset + seg_layout.sizes.file_size;
     }
 }
+
+#[test]
+fn test_undefined_symbols_error() {
+    let reference = |symbol_name: &str, location: &str| UndefinedReference {
+        symbol_name: symbol_name.to_owned(),
+        location: location.to_owned(),
+    };
+    let error = undefined_symbols_error(vec![
+        reference("foo", "b.o:(.text+0x4) in function `main`"),
+        reference("bar", "a.o:(.text.baz+0x12) in function `baz`"),
+        reference("foo", "a.o:(.text.baz+0x8) in function `baz`"),
+        reference("foo", "b.o:(.text+0x4) in function `main`"),
+    ]);
+    assert_eq!(
+        error.to_string(),
+        "undefined symbol: bar\n\
+         >>> referenced by a.o:(.text.baz+0x12) in function `baz`\n\
+         undefined symbol: foo\n\
+         >>> referenced by a.o:(.text.baz+0x8) in function `baz`\n\
+         >>> referenced by b.o:(.text+0x4) in function `main`"
+    );
+}
+
+#[test]
+fn test_hash_bucket_count() {
+    assert_eq!(hash_bucket_count(1), 1);
+    assert_eq!(hash_bucket_count(2), 1);
+    assert_eq!(hash_bucket_count(3), 3);
+    assert_eq!(hash_bucket_count(16), 3);
+    assert_eq!(hash_bucket_count(17), 17);
+    assert_eq!(hash_bucket_count(1000), 521);
+    assert_eq!(hash_bucket_count(10_000_000), 262147);
+}
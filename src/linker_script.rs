@@ -3,6 +3,8 @@
 //! don't currently support those. It's just for supporting small linker scripts that are put in
 //! place of .so files to tell the linker to load some other input file(s).
 
+use crate::args::apply_sysroot;
+use crate::args::prefix_with_sysroot;
 use crate::args::Input;
 use crate::args::InputSpec;
 use crate::error::Result;
@@ -11,21 +13,41 @@ use anyhow::bail;
 use anyhow::Context;
 use std::path::Path;
 
+/// The parts of a linker script that we make use of.
+pub(crate) struct LinkerScript {
+    pub(crate) inputs: Vec<Input>,
+
+    /// The symbol (or address) specified with `ENTRY(...)`, if any.
+    pub(crate) entry: Option<String>,
+}
+
 /// Parse the kind of linker script that's put in place of a shared object to specify that the
-/// linker should load several files.
-pub(crate) fn linker_script_to_inputs(bytes: &[u8], path: &Path) -> Result<Vec<Input>> {
+/// linker should load several files. As with ld, if the script is inside `sysroot`, then absolute
+/// paths within it are taken to be relative to the sysroot.
+pub(crate) fn parse_linker_script(
+    bytes: &[u8],
+    path: &Path,
+    sysroot: Option<&Path>,
+) -> Result<LinkerScript> {
     let text = std::str::from_utf8(bytes)?;
     let directory = path
         .parent()
         .ok_or_else(|| anyhow!("Need directory for path `{}`", path.display()))?;
-    Ok(inputs_from_script(text)
-        .with_context(|| format!("Failed to parse linker script `{}`", path.display()))?
-        .into_iter()
-        .map(|mut input| {
-            input.search_first = Some(directory.to_owned());
-            input
-        })
-        .collect())
+    let mut script = parse_script(text)
+        .with_context(|| format!("Failed to parse linker script `{}`", path.display()))?;
+    let in_sysroot = sysroot.is_some_and(|sysroot| path.starts_with(sysroot));
+    for input in &mut script.inputs {
+        input.search_first = Some(directory.to_owned());
+        if let InputSpec::File(file) = &input.spec {
+            if let Some(rewritten) = file.to_str().and_then(|file| apply_sysroot(file, sysroot)) {
+                input.spec = InputSpec::File(Box::from(rewritten.as_path()));
+            } else if in_sysroot && file.is_absolute() {
+                input.spec =
+                    InputSpec::File(Box::from(prefix_with_sysroot(file, sysroot).as_path()));
+            }
+        }
+    }
+    Ok(script)
 }
 
 struct Tokeniser<'a> {
@@ -77,6 +99,7 @@ enum Command<'a> {
     Arg(&'a str),
     Group(Vec<Command<'a>>),
     AsNeeded(Vec<Command<'a>>),
+    Entry(&'a str),
     Ignored(Vec<Command<'a>>),
 }
 
@@ -116,34 +139,47 @@ fn parse_command<'a>(tokens: &mut Tokeniser<'a>, token: &str) -> Result<Command<
             tokens.expect("(")?;
             Ok(Command::AsNeeded(parse_commands_up_to(tokens, Some(")"))?))
         }
+        "ENTRY" => {
+            tokens.expect("(")?;
+            let symbol = tokens
+                .next()
+                .ok_or_else(|| anyhow!("Got end of script, expected entry symbol"))?;
+            tokens.expect(")")?;
+            Ok(Command::Entry(symbol))
+        }
         _ => bail!("Unsupported linker script command `{token}`"),
     }
 }
 
-fn inputs_from_script(text: &str) -> Result<Vec<Input>> {
+fn parse_script(text: &str) -> Result<LinkerScript> {
     let mut tokens = Tokeniser::new(text);
     let commands = parse_commands_up_to(&mut tokens, None)?;
-    let mut inputs = Vec::new();
-    collect_inputs(&commands, &mut inputs);
-    Ok(inputs)
+    let mut script = LinkerScript {
+        inputs: Vec::new(),
+        entry: None,
+    };
+    apply_commands(&commands, &mut script);
+    Ok(script)
 }
 
-fn collect_inputs(commands: &[Command], inputs: &mut Vec<Input>) {
+fn apply_commands(commands: &[Command], script: &mut LinkerScript) {
     for command in commands {
         match command {
             Command::Arg(arg) => {
                 let spec = if let Some(lib_name) = arg.strip_prefix("-l") {
-                    InputSpec::Lib(Box::from(lib_name))
+                    InputSpec::lib(lib_name)
                 } else {
                     InputSpec::File(Box::from(Path::new(arg)))
                 };
-                inputs.push(Input {
+                script.inputs.push(Input {
                     spec,
                     search_first: None,
+                    modifiers: Default::default(),
                 });
             }
-            Command::Group(subs) => collect_inputs(subs, inputs),
-            Command::AsNeeded(subs) => collect_inputs(subs, inputs),
+            Command::Group(subs) => apply_commands(subs, script),
+            Command::AsNeeded(subs) => apply_commands(subs, script),
+            Command::Entry(symbol) => script.entry = Some((*symbol).to_owned()),
             Command::Ignored(_) => {}
         }
     }
@@ -194,12 +230,13 @@ mod tests {
 
     #[test]
     fn test_inputs_from_script() {
-        let inputs = inputs_from_script(
+        let inputs = parse_script(
             r#"/* GNU ld script */
             GROUP ( libgcc_s.so.1 -lgcc )
         "#,
         )
-        .unwrap();
+        .unwrap()
+        .inputs;
         assert_eq!(
             inputs.into_iter().map(|i| i.spec).collect::<Vec<_>>(),
             vec![
@@ -211,12 +248,13 @@ mod tests {
 
     #[test]
     fn test_test_inputs_from_script() {
-        let inputs = inputs_from_script(
+        let inputs = parse_script(
             r#"OUTPUT_FORMAT(elf64-x86-64)
             GROUP ( /lib/x86_64-linux-gnu/libc.so.6 /usr/lib/x86_64-linux-gnu/libc_nonshared.a  AS_NEEDED ( /lib64/ld-linux-x86-64.so.2 ) )
         "#,
         )
-        .unwrap();
+        .unwrap()
+        .inputs;
         assert_eq!(
             inputs.into_iter().map(|i| i.spec).collect::<Vec<_>>(),
             vec![
@@ -228,4 +266,11 @@ mod tests {
             ]
         )
     }
+
+    #[test]
+    fn test_entry() {
+        let script = parse_script("ENTRY(my_start) GROUP ( foo.o )").unwrap();
+        assert_eq!(script.entry.as_deref(), Some("my_start"));
+        assert_eq!(script.inputs.len(), 1);
+    }
 }
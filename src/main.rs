@@ -2,6 +2,7 @@ mod alignment;
 mod archive;
 mod archive_splitter;
 mod args;
+mod diagnostics;
 mod elf;
 mod elf_writer;
 mod error;
@@ -10,8 +11,10 @@ mod fs;
 mod hash;
 mod identity;
 mod input_data;
+mod jobserver;
 mod layout;
 mod linker_script;
+mod map_file;
 mod output_section_id;
 mod output_section_map;
 mod output_section_part_map;
@@ -21,27 +24,80 @@ mod resolution;
 mod save_dir;
 mod shutdown;
 mod slice;
+mod subprocess;
 mod symbol;
 mod symbol_db;
 mod timing;
+mod version_script;
+
+use anyhow::Context;
 
 fn main() -> crate::error::Result {
-    let args = args::Args::from_env()?;
+    let args = match args::Action::from_env()? {
+        args::Action::Link(args) => args,
+        args::Action::PrintHelp => {
+            print!("{}", args::help_text());
+            return Ok(());
+        }
+        args::Action::PrintVersion => {
+            println!("{}", args::version_text());
+            return Ok(());
+        }
+    };
+    if args.print_version {
+        println!("{}", args::version_text());
+    }
+    // This needs to happen before we start any threads.
+    let done_signal = if args.should_fork {
+        Some(subprocess::run_in_subprocess()?)
+    } else {
+        None
+    };
     timing::init_tracing(&args);
-    link(&args)
+    let result = link(&args, done_signal);
+    if result.is_err() {
+        // Any warnings that we issued before failing may help explain the failure.
+        args.diagnostics.print_pending();
+    }
+    result
+}
+
+fn write_map(args: &args::Args, layout: &layout::Layout) -> crate::error::Result {
+    if let Some(path) = &args.map_file {
+        let file = std::fs::File::create(path)
+            .with_context(|| format!("Failed to create map file `{}`", path.display()))?;
+        map_file::write_map(layout, &mut std::io::BufWriter::new(file))
+            .with_context(|| format!("Failed to write map file `{}`", path.display()))?;
+    }
+    if args.print_map {
+        map_file::write_map(layout, &mut std::io::stdout().lock())?;
+    }
+    Ok(())
 }
 
 #[tracing::instrument(skip_all, name = "Link")]
-fn link(args: &args::Args) -> crate::error::Result {
-    args.setup_thread_pool()?;
+fn link(args: &args::Args, done_signal: Option<subprocess::DoneSignal>) -> crate::error::Result {
+    let jobserver_tokens = args.setup_thread_pool()?;
     let mut output = elf_writer::Output::new(args);
     let input_data = input_data::InputData::from_args(args)?;
     let inputs = archive_splitter::split_archives(&input_data)?;
-    let (mut symbol_db, file_states) = symbol_db::SymbolDb::build(&inputs, args)?;
+    let (mut symbol_db, file_states) = symbol_db::SymbolDb::build(&inputs, &input_data, args)?;
     let (resolved_files, output_sections) =
         resolution::resolve_symbols_and_sections(file_states, &mut symbol_db)?;
     let layout = layout::compute(&symbol_db, resolved_files, output_sections, &mut output)?;
+    // Report warnings before writing our output, so that we don't leave behind an output file if
+    // warnings are fatal.
+    args.diagnostics.flush()?;
     output.write(&layout)?;
+    write_map(args, &layout)?;
+    // Cleanup doesn't need many threads and whoever invoked us may be waiting for job slots in
+    // order to start using our output, so give back our tokens before we report that we're done.
+    drop(jobserver_tokens);
+    // Our output file is now written and closed, so if we're in a subprocess, let our parent exit
+    // while we clean up.
+    if let Some(done_signal) = done_signal {
+        done_signal.notify();
+    }
 
     let scope = tracing::span!(tracing::Level::INFO, "Shutdown");
     let _scope = scope.enter();
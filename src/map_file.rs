@@ -0,0 +1,234 @@
+//! Writes a map file describing the layout of our output, as requested by `-Map=<file>` or
+//! `--print-map`. We list each segment, then each output section, followed by the input sections
+//! that contributed to it and the symbols that those input sections define.
+
+use crate::error::Result;
+use crate::layout::DynamicLayout;
+use crate::layout::FileLayout;
+use crate::layout::InternalLayout;
+use crate::layout::Layout;
+use crate::layout::ObjectLayout;
+use crate::output_section_id;
+use crate::output_section_map::OutputSectionMap;
+use crate::resolution::SectionSlot;
+use object::Object;
+use object::ObjectSection;
+use object::ObjectSymbol;
+use std::io::Write;
+
+/// Something that occupies part of an output section.
+struct Contribution {
+    address: u64,
+    size: u64,
+    /// None if the alignment isn't known, e.g. for copies of symbols from shared objects.
+    alignment: Option<u64>,
+    description: String,
+    /// The address and name of each symbol defined within this contribution.
+    symbols: Vec<(u64, String)>,
+}
+
+#[tracing::instrument(skip_all, name = "Write map file")]
+pub(crate) fn write_map(layout: &Layout, out: &mut impl Write) -> Result {
+    let mut contributions = OutputSectionMap::with_size(layout.output_sections.len());
+    for file in &layout.file_layouts {
+        match file {
+            FileLayout::Internal(internal) => {
+                add_internal_contributions(internal, layout, &mut contributions)
+            }
+            FileLayout::Object(object) => {
+                add_object_contributions(object, &mut contributions)?;
+            }
+            FileLayout::Dynamic(dynamic) => add_dynamic_contributions(dynamic, &mut contributions),
+        }
+    }
+
+    writeln!(out, "Segments:")?;
+    writeln!(
+        out,
+        "{:<10} {:>10} {:>16} {:>10} {:>10} {:>6}",
+        "Type", "Offset", "Address", "FileSize", "MemSize", "Align"
+    )?;
+    for segment in &layout.segment_layouts.segments {
+        let sizes = &segment.sizes;
+        writeln!(
+            out,
+            "{:<10} {:>10x} {:>16x} {:>10x} {:>10x} {:>6}",
+            format!("{:?}", segment.id.segment_type()),
+            sizes.file_offset,
+            sizes.mem_offset,
+            sizes.file_size,
+            sizes.mem_size,
+            sizes.alignment.value()
+        )?;
+    }
+
+    writeln!(out)?;
+    writeln!(
+        out,
+        "{:>16} {:>10} {:>10} {:>6} Out     In      Symbol",
+        "Address", "Offset", "Size", "Align"
+    )?;
+    let mut section_ids = Vec::new();
+    layout
+        .output_sections
+        .sections_do(|section_id, _| section_ids.push(section_id));
+    for section_id in section_ids {
+        if layout
+            .output_sections
+            .output_index_of_section(section_id)
+            .is_none()
+        {
+            continue;
+        }
+        let section = layout.section_layouts.get(section_id);
+        // Sections like .bss don't occupy any space in the file, so we don't give an offset.
+        let has_data_in_file = layout.output_sections.has_data_in_file(section_id);
+        let file_offset = |offset: u64| {
+            if has_data_in_file {
+                format!("{offset:x}")
+            } else {
+                String::new()
+            }
+        };
+        writeln!(
+            out,
+            "{:>16x} {:>10} {:>10x} {:>6} {}",
+            section.mem_offset,
+            file_offset(section.file_offset as u64),
+            section.mem_size,
+            section.alignment.value(),
+            String::from_utf8_lossy(layout.output_sections.name(section_id))
+        )?;
+        let section_contributions: &mut Vec<Contribution> = contributions.get_mut(section_id);
+        section_contributions.sort_by_key(|c| c.address);
+        for contribution in section_contributions.iter() {
+            let alignment = contribution
+                .alignment
+                .map(|a| a.to_string())
+                .unwrap_or_default();
+            writeln!(
+                out,
+                "{:>16x} {:>10} {:>10x} {:>6} {:8}{}",
+                contribution.address,
+                file_offset(
+                    section.file_offset as u64 + (contribution.address - section.mem_offset)
+                ),
+                contribution.size,
+                alignment,
+                "",
+                contribution.description
+            )?;
+            for (address, name) in &contribution.symbols {
+                writeln!(out, "{address:>16x} {:41}{name}", "")?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn add_internal_contributions(
+    internal: &InternalLayout,
+    layout: &Layout,
+    contributions: &mut OutputSectionMap<Vec<Contribution>>,
+) {
+    internal.merged_strings.for_each(|section_id, merged| {
+        if merged.len > 0 {
+            contributions.get_mut(section_id).push(Contribution {
+                address: layout
+                    .merged_string_start_addresses
+                    .section_address(section_id),
+                size: merged.len,
+                alignment: Some(merged.alignment.value()),
+                description: "<merged strings>".to_owned(),
+                symbols: Vec::new(),
+            });
+        }
+    });
+}
+
+fn add_object_contributions(
+    object: &ObjectLayout,
+    contributions: &mut OutputSectionMap<Vec<Contribution>>,
+) -> Result {
+    let mut symbols_by_section = vec![Vec::new(); object.sections.len()];
+    for sym in object.object.symbols() {
+        let object::SymbolSection::Section(section_index) = sym.section() else {
+            continue;
+        };
+        if matches!(
+            sym.kind(),
+            object::SymbolKind::Section | object::SymbolKind::File
+        ) {
+            continue;
+        }
+        let name = sym.name_bytes()?;
+        if !crate::layout::should_copy_symbol(name) {
+            continue;
+        }
+        if let Some(Some(res)) = object.section_resolutions.get(section_index.0) {
+            symbols_by_section[section_index.0].push((
+                res.address + sym.address(),
+                String::from_utf8_lossy(name).into_owned(),
+            ));
+        }
+    }
+    let mut eh_frame_added = false;
+    for (slot, mut symbols) in object.sections.iter().zip(symbols_by_section) {
+        match slot {
+            SectionSlot::Loaded(section) => {
+                let (Some(output_section_id), Some(res)) = (
+                    section.output_section_id,
+                    object.section_resolutions[section.index.0],
+                ) else {
+                    continue;
+                };
+                let name = object.object.section_by_index(section.index)?.name()?;
+                symbols.sort();
+                contributions.get_mut(output_section_id).push(Contribution {
+                    address: res.address,
+                    size: section.size,
+                    alignment: Some(section.alignment.value()),
+                    description: format!("{}:({name})", object.input),
+                    symbols,
+                });
+            }
+            // All of an object's .eh_frame data is allocated together, even if it came from more
+            // than one input section.
+            SectionSlot::EhFrameData(_) if !eh_frame_added && object.mem_sizes.eh_frame > 0 => {
+                eh_frame_added = true;
+                contributions
+                    .get_mut(output_section_id::EH_FRAME)
+                    .push(Contribution {
+                        address: object.eh_frame_start_address,
+                        size: object.mem_sizes.eh_frame,
+                        alignment: None,
+                        description: format!("{}:(.eh_frame)", object.input),
+                        symbols: Vec::new(),
+                    });
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Data symbols from shared objects get copied into our .bss.
+fn add_dynamic_contributions(
+    dynamic: &DynamicLayout,
+    contributions: &mut OutputSectionMap<Vec<Contribution>>,
+) {
+    for sym in &dynamic.symbols {
+        if let Some(size) = sym.copy_size {
+            let address = sym.resolution.address;
+            contributions
+                .get_mut(output_section_id::BSS)
+                .push(Contribution {
+                    address,
+                    size,
+                    alignment: None,
+                    description: format!("{}:(copy relocation)", dynamic.input),
+                    symbols: vec![(address, String::from_utf8_lossy(sym.name).into_owned())],
+                });
+        }
+    }
+}
@@ -65,9 +65,10 @@ pub(crate) const DYNAMIC: OutputSectionId = OutputSectionId(9);
 pub(crate) const DYNSYM: OutputSectionId = OutputSectionId(10);
 pub(crate) const DYNSTR: OutputSectionId = OutputSectionId(11);
 pub(crate) const RELA_DYN: OutputSectionId = OutputSectionId(12);
+pub(crate) const HASH: OutputSectionId = OutputSectionId(13);
 
 /// Regular sections are sections that come from input files and can contain a mix of alignments.
-pub(crate) const NUM_GENERATED_SECTIONS: usize = 13;
+pub(crate) const NUM_GENERATED_SECTIONS: usize = 14;
 
 // Sections that need to be referenced from code. When adding new sections here, be sure to update
 // `test_constant_ids`.
@@ -83,8 +84,9 @@ pub(crate) const TDATA: OutputSectionId = OutputSectionId::regular(8);
 pub(crate) const TBSS: OutputSectionId = OutputSectionId::regular(9);
 pub(crate) const BSS: OutputSectionId = OutputSectionId::regular(10);
 pub(crate) const COMMENT: OutputSectionId = OutputSectionId::regular(11);
+pub(crate) const INTERP: OutputSectionId = OutputSectionId::regular(12);
 
-pub(crate) const NUM_REGULAR_SECTIONS: usize = 12;
+pub(crate) const NUM_REGULAR_SECTIONS: usize = 13;
 
 // pub(crate) const DYNSTR: BuiltInId = BuiltInId(14);
 
@@ -108,6 +110,10 @@ pub(crate) struct OutputSections<'data> {
     pub(crate) exec_custom: Vec<OutputSectionId>,
     pub(crate) data_custom: Vec<OutputSectionId>,
     pub(crate) bss_custom: Vec<OutputSectionId>,
+
+    /// Whether to put the sections that only the dynamic loader writes to at the start of the
+    /// writable segment and cover them with a PT_GNU_RELRO segment.
+    relro: bool,
 }
 
 impl<'data> OutputSections<'data> {
@@ -267,10 +273,12 @@ const SECTION_DEFINITIONS: [BuiltInSectionDetails; NUM_BUILT_IN_SECTIONS] = [
     BuiltInSectionDetails {
         details: SectionDetails {
             name: ".dynamic".as_bytes(),
-            ty: elf::Sht::Progbits,
-            section_flags: elf::shf::ALLOC,
+            ty: elf::Sht::Dynamic,
+            section_flags: elf::shf::ALLOC | elf::shf::WRITE,
+            element_size: size_of::<elf::DynamicEntry>() as u64,
             ..SectionDetails::default()
         },
+        link: Some(DYNSTR),
         min_alignment: alignment::USIZE,
         start_symbol_name: Some("_DYNAMIC"),
         ..DEFAULT_DEFS
@@ -310,6 +318,18 @@ const SECTION_DEFINITIONS: [BuiltInSectionDetails; NUM_BUILT_IN_SECTIONS] = [
         link: Some(DYNSYM),
         ..DEFAULT_DEFS
     },
+    BuiltInSectionDetails {
+        details: SectionDetails {
+            name: ".hash".as_bytes(),
+            ty: elf::Sht::Hash,
+            section_flags: elf::shf::ALLOC,
+            element_size: size_of::<u32>() as u64,
+            ..SectionDetails::default()
+        },
+        min_alignment: alignment::HASH_ENTRY,
+        link: Some(DYNSYM),
+        ..DEFAULT_DEFS
+    },
     // Start of regular sections
     BuiltInSectionDetails {
         details: SectionDetails {
@@ -433,6 +453,14 @@ const SECTION_DEFINITIONS: [BuiltInSectionDetails; NUM_BUILT_IN_SECTIONS] = [
         },
         ..DEFAULT_DEFS
     },
+    BuiltInSectionDetails {
+        details: SectionDetails {
+            name: ".interp".as_bytes(),
+            ty: elf::Sht::Progbits,
+            ..SectionDetails::default()
+        },
+        ..DEFAULT_DEFS
+    },
     // OutputSectionDef {
     //     name: ".dynamic",
     //     ty: elf::Sht::Dynamic,
@@ -552,7 +580,7 @@ fn should_merge_strings(section: &Section, args: &Args) -> bool {
     };
     (sh_flags & crate::elf::shf::MERGE) != 0
         && (sh_flags & crate::elf::shf::STRINGS) != 0
-        && section.align() <= 1
+        && matches!(elf::section_entsize(section), 1 | 2 | 4)
 }
 
 pub(crate) fn built_in_section_ids(
@@ -607,6 +635,7 @@ pub(crate) enum OrderEvent<'data> {
 
 pub(crate) struct OutputSectionsBuilder<'data> {
     base_address: u64,
+    relro: bool,
     custom: BTreeMap<&'data [u8], SectionDetails<'data>>,
 }
 
@@ -656,6 +685,7 @@ impl<'data> OutputSectionsBuilder<'data> {
             data_custom,
             bss_custom,
             output_section_indexes: Default::default(),
+            relro: self.relro,
         };
         let mut extra = vec![None; output_sections.section_infos.len()];
         let mut load_seg_id = None;
@@ -719,9 +749,16 @@ impl<'data> OutputSectionsBuilder<'data> {
     pub(crate) fn with_base_address(base_address: u64) -> Self {
         Self {
             base_address,
+            relro: false,
             custom: Default::default(),
         }
     }
+
+    /// Sets whether to emit a PT_GNU_RELRO segment. Only the dynamic loader applies it, so this
+    /// should only be set for output that the dynamic loader will load.
+    pub(crate) fn set_relro(&mut self, relro: bool) {
+        self.relro = relro;
+    }
 }
 
 impl<'data> OutputSections<'data> {
@@ -729,9 +766,15 @@ impl<'data> OutputSections<'data> {
     /// can overlap, so are represented as start and end events.
     pub(crate) fn sections_and_segments_do(&self, mut cb: impl FnMut(OrderEvent)) {
         cb(OrderEvent::SegmentStart(crate::program_segments::LOAD_RO));
+        cb(OrderEvent::SegmentStart(crate::program_segments::PHDR));
         cb(HEADERS.event());
+        cb(OrderEvent::SegmentEnd(crate::program_segments::PHDR));
+        cb(OrderEvent::SegmentStart(crate::program_segments::INTERP));
+        cb(INTERP.event());
+        cb(OrderEvent::SegmentEnd(crate::program_segments::INTERP));
         cb(DYNSYM.event());
         cb(DYNSTR.event());
+        cb(HASH.event());
         cb(RELA_DYN.event());
         cb(RODATA.event());
         cb(OrderEvent::SegmentStart(crate::program_segments::EH_FRAME));
@@ -754,14 +797,29 @@ impl<'data> OutputSections<'data> {
         cb(OrderEvent::SegmentEnd(crate::program_segments::LOAD_EXEC));
 
         cb(OrderEvent::SegmentStart(crate::program_segments::LOAD_RW));
-        cb(GOT.event());
-        cb(INIT_ARRAY.event());
-        cb(FINI_ARRAY.event());
-        cb(DATA.event());
-        cb(EH_FRAME.event());
-        cb(OrderEvent::SegmentStart(crate::program_segments::DYNAMIC));
-        cb(DYNAMIC.event());
-        cb(OrderEvent::SegmentEnd(crate::program_segments::DYNAMIC));
+        if self.relro {
+            // Sections that are only written to by the dynamic loader while it's applying
+            // relocations go first, so that they can be made read-only afterwards.
+            cb(OrderEvent::SegmentStart(crate::program_segments::RELRO));
+            cb(GOT.event());
+            cb(INIT_ARRAY.event());
+            cb(FINI_ARRAY.event());
+            cb(OrderEvent::SegmentStart(crate::program_segments::DYNAMIC));
+            cb(DYNAMIC.event());
+            cb(OrderEvent::SegmentEnd(crate::program_segments::DYNAMIC));
+            cb(OrderEvent::SegmentEnd(crate::program_segments::RELRO));
+            cb(DATA.event());
+            cb(EH_FRAME.event());
+        } else {
+            cb(GOT.event());
+            cb(INIT_ARRAY.event());
+            cb(FINI_ARRAY.event());
+            cb(DATA.event());
+            cb(EH_FRAME.event());
+            cb(OrderEvent::SegmentStart(crate::program_segments::DYNAMIC));
+            cb(DYNAMIC.event());
+            cb(OrderEvent::SegmentEnd(crate::program_segments::DYNAMIC));
+        }
         self.ids_do(&self.data_custom, &mut cb);
         cb(OrderEvent::SegmentStart(crate::program_segments::TLS));
         cb(TDATA.event());
@@ -897,7 +955,7 @@ fn symtab_info(layout: &Layout) -> u32 {
 }
 
 fn dynsym_info(_layout: &Layout) -> u32 {
-    // For now, we're not putting anything in dynstr, so the only "local" is the null symbol.
+    // All the symbols that we put in .dynsym are global, so the only "local" is the null symbol.
     1
 }
 
@@ -929,6 +987,8 @@ fn test_constant_ids() {
         (DYNSYM, ".dynsym"),
         (DYNSTR, ".dynstr"),
         (RELA_DYN, ".rela.dyn"),
+        (HASH, ".hash"),
+        (INTERP, ".interp"),
     ];
     for (id, name) in check {
         assert_eq!(
@@ -939,6 +999,28 @@ fn test_constant_ids() {
     assert_eq!(NUM_BUILT_IN_SECTIONS, check.len());
 }
 
+#[test]
+fn test_relro_ordering() {
+    for relro in [false, true] {
+        let mut builder = OutputSectionsBuilder::with_base_address(0x1000);
+        builder.set_relro(relro);
+        let output_sections = builder.build().unwrap();
+        let mut has_relro_segment = false;
+        let mut order = Vec::new();
+        output_sections.sections_and_segments_do(|event| match event {
+            OrderEvent::SegmentStart(id) if id == crate::program_segments::RELRO => {
+                has_relro_segment = true;
+            }
+            OrderEvent::Section(id, _) => order.push(id),
+            _ => {}
+        });
+        assert_eq!(has_relro_segment, relro);
+        // .dynamic only moves ahead of .data when it needs to be covered by PT_GNU_RELRO.
+        let position = |id| order.iter().position(|o| *o == id).unwrap();
+        assert_eq!(position(DYNAMIC) < position(DATA), relro);
+    }
+}
+
 impl std::fmt::Display for OutputSectionId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         std::fmt::Display::fmt(&self.as_usize(), f)
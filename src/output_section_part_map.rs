@@ -27,7 +27,12 @@ pub(crate) struct OutputSectionPartMap<T> {
     pub(crate) dynamic: T,
     pub(crate) dynsym: T,
     pub(crate) dynstr: T,
-    pub(crate) rela_dyn: T,
+    /// R_X86_64_RELATIVE relocations. These need to come first, since DT_RELACOUNT says how many
+    /// relative relocations are at the start of .rela.dyn.
+    pub(crate) rela_dyn_relative: T,
+    /// All other dynamic relocations - e.g. GLOB_DAT, COPY.
+    pub(crate) rela_dyn_general: T,
+    pub(crate) hash: T,
 }
 
 impl<T: Default> OutputSectionPartMap<T> {
@@ -49,7 +54,9 @@ impl<T: Default> OutputSectionPartMap<T> {
             dynamic: Default::default(),
             dynsym: Default::default(),
             dynstr: Default::default(),
-            rela_dyn: Default::default(),
+            rela_dyn_relative: Default::default(),
+            rela_dyn_general: Default::default(),
+            hash: Default::default(),
         }
     }
 }
@@ -88,6 +95,7 @@ impl<T: Default + PartialEq> OutputSectionPartMap<T> {
             output_section_id::HEADERS.min_alignment(),
             &self.file_headers,
         );
+        self.map_regular(output_section_id::INTERP, &mut cb, &mut regular);
         let dynsym = cb(
             output_section_id::DYNSYM,
             output_section_id::DYNSYM.min_alignment(),
@@ -98,10 +106,20 @@ impl<T: Default + PartialEq> OutputSectionPartMap<T> {
             output_section_id::DYNSTR.min_alignment(),
             &self.dynstr,
         );
-        let rela_dyn = cb(
+        let hash = cb(
+            output_section_id::HASH,
+            output_section_id::HASH.min_alignment(),
+            &self.hash,
+        );
+        let rela_dyn_relative = cb(
+            output_section_id::RELA_DYN,
+            output_section_id::RELA_DYN.min_alignment(),
+            &self.rela_dyn_relative,
+        );
+        let rela_dyn_general = cb(
             output_section_id::RELA_DYN,
             output_section_id::RELA_DYN.min_alignment(),
-            &self.rela_dyn,
+            &self.rela_dyn_general,
         );
         self.map_regular(output_section_id::RODATA, &mut cb, &mut regular);
         let eh_frame_hdr = cb(
@@ -193,7 +211,9 @@ impl<T: Default + PartialEq> OutputSectionPartMap<T> {
             dynamic,
             dynsym,
             dynstr,
-            rela_dyn,
+            rela_dyn_relative,
+            rela_dyn_general,
+            hash,
         }
     }
 
@@ -260,7 +280,9 @@ impl<T: Default + PartialEq> OutputSectionPartMap<T> {
             dynamic: cb(&mut self.dynamic, &other.dynamic),
             dynsym: cb(&mut self.dynsym, &other.dynsym),
             dynstr: cb(&mut self.dynstr, &other.dynstr),
-            rela_dyn: cb(&mut self.rela_dyn, &other.rela_dyn),
+            rela_dyn_relative: cb(&mut self.rela_dyn_relative, &other.rela_dyn_relative),
+            rela_dyn_general: cb(&mut self.rela_dyn_general, &other.rela_dyn_general),
+            hash: cb(&mut self.hash, &other.hash),
         }
     }
 }
@@ -327,7 +349,11 @@ impl<T: Copy> OutputSectionPartMap<T> {
         update(output_section_id::DYNAMIC, &[self.dynamic]);
         update(output_section_id::DYNSYM, &[self.dynsym]);
         update(output_section_id::DYNSTR, &[self.dynstr]);
-        update(output_section_id::RELA_DYN, &[self.rela_dyn]);
+        update(
+            output_section_id::RELA_DYN,
+            &[self.rela_dyn_relative, self.rela_dyn_general],
+        );
+        update(output_section_id::HASH, &[self.hash]);
         values_out.extend(self.regular.iter().map(|parts| cb(parts.raw_values())));
         debug_assert!(
             values_out.len() == values_out.capacity(),
@@ -359,7 +385,9 @@ impl<T: AddAssign + Copy + Default> OutputSectionPartMap<T> {
         self.dynamic += rhs.dynamic;
         self.dynsym += rhs.dynsym;
         self.dynstr += rhs.dynstr;
-        self.rela_dyn += rhs.rela_dyn;
+        self.rela_dyn_relative += rhs.rela_dyn_relative;
+        self.rela_dyn_general += rhs.rela_dyn_general;
+        self.hash += rhs.hash;
     }
 }
 
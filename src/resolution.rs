@@ -2,8 +2,11 @@
 //! entries are needed. We also resolve which output section, if any, each input section should be
 //! assigned to.
 
+use crate::alignment::Alignment;
 use crate::args::Args;
 use crate::elf::File;
+use crate::elf::RelocationKind;
+use crate::elf::RelocationKindInfo;
 use crate::error::Error;
 use crate::error::Result;
 use crate::input_data;
@@ -26,12 +29,14 @@ use crate::symbol_db::InternalSymbols;
 use crate::symbol_db::ObjectSymbols;
 use crate::symbol_db::SymbolDb;
 use ahash::AHashMap;
+use ahash::AHashSet;
 use anyhow::bail;
 use anyhow::Context;
 use crossbeam_queue::ArrayQueue;
 use crossbeam_queue::SegQueue;
 use crossbeam_utils::atomic::AtomicCell;
 use object::Object;
+use object::ObjectComdat;
 use object::ObjectSection;
 use object::ObjectSymbol;
 use std::collections::BTreeMap;
@@ -45,9 +50,9 @@ pub(crate) fn resolve_symbols_and_sections<'data>(
     let (mut resolved, start_stop_sets, mut internal) =
         resolve_symbols_in_files(file_states, symbol_db)?;
 
-    let output_sections = assign_section_ids(&resolved, symbol_db.args)?;
+    resolve_section_groups(&mut resolved);
 
-    let merged_strings = merge_strings(&mut resolved, &output_sections)?;
+    let output_sections = assign_section_ids(&resolved, symbol_db)?;
 
     allocate_start_stop_symbol_ids(
         start_stop_sets,
@@ -60,12 +65,17 @@ pub(crate) fn resolve_symbols_and_sections<'data>(
     resolve_alternative_symbol_definitions(symbol_db, &resolved)?;
     filter_overridden_internal_symbols(&mut internal, symbol_db);
 
+    if symbol_db.args.shared {
+        allocate_undefined_symbol_ids(&mut internal, &mut resolved, symbol_db)?;
+        mark_imported_symbols_dynamic(&resolved, symbol_db);
+        mark_interposable_symbols_dynamic(&resolved, symbol_db)?;
+    }
+
     resolved[INTERNAL_FILE_ID.as_usize()] = ResolvedFile::Internal(ResolvedInternal {
         dynamic_linker: internal.dynamic_linker,
         symbol_definitions: internal.symbol_definitions,
         defined: internal.defined,
         file_id: internal.file_id,
-        merged_strings,
     });
     Ok((resolved, output_sections))
 }
@@ -105,6 +115,9 @@ pub(crate) fn resolve_symbols_in_files<'data>(
                 archive_entries[s.file_id.as_usize()].store(Some(Box::new(s)));
                 ResolvedFile::NotLoaded
             }
+            // Shared objects are always "loaded", even if we don't end up referencing any of their
+            // symbols, since they'll be loaded at runtime by the dynamic loader.
+            FileSymbols::Dynamic(s) => ResolvedFile::Dynamic(ResolvedDynamic::new(s)),
         })
         .collect();
     let internal = internal.unwrap();
@@ -119,6 +132,18 @@ pub(crate) fn resolve_symbols_in_files<'data>(
                 }
             })
         }
+        // Symbols passed to -u or --require-defined need to be defined even if nothing references
+        // them, so load any archive entries that define them.
+        for name in symbol_db
+            .args
+            .undefined
+            .iter()
+            .chain(&symbol_db.args.require_defined)
+        {
+            if let Some(&symbol_id) = symbol_db.symbol_ids.get(&SymbolName::new(name.as_bytes())) {
+                request_archive_entry(symbol_id, None, symbol_db, &archive_entries, s, &outputs);
+            }
+        }
     });
     if let Some(e) = outputs.errors.pop() {
         return Err(e);
@@ -127,9 +152,44 @@ pub(crate) fn resolve_symbols_in_files<'data>(
         let file_id = obj.file_id;
         resolved[file_id.as_usize()] = ResolvedFile::Object(obj);
     }
+    if symbol_db.args.trace {
+        print_trace(&resolved, outputs.load_reasons, symbol_db);
+    }
     Ok((resolved, outputs.start_stop_sets, internal))
 }
 
+/// Prints each file that we loaded and for archive entries, the symbol reference that caused them
+/// to be loaded. Files are listed in the order that they appeared on the command line.
+fn print_trace(
+    resolved: &[ResolvedFile],
+    load_reasons: SegQueue<LoadReason>,
+    symbol_db: &SymbolDb,
+) {
+    let mut reasons = vec![None; resolved.len()];
+    for reason in load_reasons {
+        reasons[symbol_db.symbol(reason.symbol_id).file_id.as_usize()] = Some(reason);
+    }
+    for (file, reason) in resolved.iter().zip(reasons) {
+        let input = match file {
+            ResolvedFile::Object(obj) => obj.input,
+            ResolvedFile::Dynamic(obj) => obj.input,
+            ResolvedFile::NotLoaded | ResolvedFile::Internal(_) => continue,
+        };
+        let Some(reason) = reason else {
+            println!("{input}");
+            continue;
+        };
+        let name = symbol_db.symbol_name(reason.symbol_id);
+        match reason.referenced_by {
+            Some(file_id) => println!(
+                "{input} (needed for `{name}`, referenced by {})",
+                resolved[file_id.as_usize()]
+            ),
+            None => println!("{input} (needed for `{name}`)"),
+        }
+    }
+}
+
 /// For each symbol that has multiple definitions, some of which may be weak, some strong, some
 /// "common" symbols and some in archive entries that weren't loaded, resolve which version of the
 /// symbol we're using. The symbol we select will be the first strongly defined symbol in a loaded
@@ -145,56 +205,267 @@ fn resolve_alternative_symbol_definitions<'data>(
     // significant time, then we could parallelise this without too much work.
     let alternate_definitions =
         core::mem::replace(&mut symbol_db.alternate_definitions, AHashMap::new());
+    let traced: AHashSet<GlobalSymbolId> = symbol_db
+        .args
+        .trace_symbols
+        .iter()
+        .filter_map(|name| symbol_db.symbol_ids.get(&SymbolName::new(name.as_bytes())))
+        .copied()
+        .collect();
+    let mut selections = AHashMap::new();
+    let mut duplicates = Vec::new();
     for (symbol_id, alternatives) in alternate_definitions {
-        if let Some(selected) = select_symbol(symbol_db, symbol_id, resolved, &alternatives) {
+        // Symbols defined with --defsym take precedence over definitions from input files.
+        if symbol_db.is_defsym(symbol_id) {
+            continue;
+        }
+        if !symbol_db.args.allow_multiple_definitions {
+            if let Some(message) =
+                check_for_duplicate_definitions(symbol_db, symbol_id, resolved, &alternatives)
+            {
+                duplicates.push((symbol_id, message));
+            }
+        }
+        let (selected, reason) = select_symbol(symbol_db, symbol_id, resolved, &alternatives);
+        if traced.contains(&symbol_id) {
+            let first = *symbol_db.symbol(symbol_id);
+            selections.insert(
+                symbol_id,
+                SymbolSelection {
+                    selected: selected.unwrap_or(first),
+                    definitions: std::iter::once(first).chain(alternatives).collect(),
+                    reason,
+                },
+            );
+        }
+        if let Some(selected) = selected {
             symbol_db.replace_symbol(symbol_id, selected);
         }
     }
+    // Print traces before we report any duplicate definitions, since that's when they're likely to
+    // be most useful.
+    for name in &symbol_db.args.trace_symbols {
+        print_symbol_trace(symbol_db, resolved, &selections, name);
+    }
+    if !duplicates.is_empty() {
+        // Our hashmap iteration order isn't deterministic, so sort to give consistent output.
+        duplicates.sort_by_key(|(symbol_id, _)| *symbol_id);
+        let messages: Vec<String> = duplicates.into_iter().map(|(_, m)| m).collect();
+        bail!("{}", messages.join("\n"));
+    }
     Ok(())
 }
 
+/// Returns an error message if the symbol is strongly defined by more than one loaded object.
+fn check_for_duplicate_definitions(
+    symbol_db: &SymbolDb,
+    symbol_id: GlobalSymbolId,
+    objects: &[ResolvedFile],
+    alternatives: &[crate::symbol::Symbol],
+) -> Option<String> {
+    let mut strong_definitions: Vec<FileId> = std::iter::once(symbol_db.symbol(symbol_id))
+        .chain(alternatives)
+        .filter(|sym| SymbolStrength::determine(objects, sym) == SymbolStrength::Strong)
+        .map(|sym| sym.file_id)
+        .collect();
+    if strong_definitions.len() < 2 {
+        return None;
+    }
+    strong_definitions.sort();
+    let name = symbol_db.symbol_name(symbol_id);
+    let first = &objects[strong_definitions[0].as_usize()];
+    Some(
+        strong_definitions[1..]
+            .iter()
+            .map(|file_id| {
+                format!(
+                    "multiple definition of `{name}`; first defined in {first}, also defined in {}",
+                    objects[file_id.as_usize()]
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+    )
+}
+
 /// Selects which version of the symbol to use. Returns None if we should leave things alone and
-/// continue using the first definition of the symbol.
+/// continue using the first definition of the symbol. Definitions from regular objects take
+/// precedence over definitions from shared objects, even if they're weak. Also returns why we made
+/// the choice that we did, which is reported if the symbol is being traced.
 fn select_symbol<'data>(
     symbol_db: &SymbolDb<'data>,
     symbol_id: GlobalSymbolId,
     objects: &[ResolvedFile<'data>],
     alternatives: &[crate::symbol::Symbol],
-) -> Option<crate::symbol::Symbol> {
+) -> (Option<crate::symbol::Symbol>, SelectionReason) {
     let first_symbol = symbol_db.symbol(symbol_id);
     let first_strength = SymbolStrength::determine(objects, first_symbol);
     if first_strength == SymbolStrength::Strong {
-        return None;
+        return (None, SelectionReason::FirstStrong);
     }
-    let mut max_common = None;
+    // If the first definition is common, an alternative only replaces it if it's larger.
+    let mut max_common = match first_strength {
+        SymbolStrength::Common(size) => Some((size, None)),
+        _ => None,
+    };
     for alt in alternatives {
         let strength = SymbolStrength::determine(objects, alt);
         match strength {
-            SymbolStrength::Strong => return Some(*alt),
+            SymbolStrength::Strong => return (Some(*alt), SelectionReason::FirstStrong),
             SymbolStrength::Common(size) => {
                 if let Some((previous_size, _)) = max_common {
                     if size <= previous_size {
                         continue;
                     }
                 }
-                max_common = Some((size, alt));
+                max_common = Some((size, Some(alt)));
             }
             _ => {}
         }
     }
     if let Some((_, alt)) = max_common {
-        return Some(*alt);
+        return (alt.copied(), SelectionReason::LargestCommon);
     }
-    if first_strength != SymbolStrength::Undefined {
-        return None;
+    if first_strength == SymbolStrength::Weak {
+        return (None, SelectionReason::FirstWeak);
+    }
+    if let Some(alt) = alternatives
+        .iter()
+        .find(|alt| SymbolStrength::determine(objects, alt) == SymbolStrength::Weak)
+    {
+        return (Some(*alt), SelectionReason::FirstWeak);
+    }
+    if first_strength == SymbolStrength::Dynamic {
+        return (None, SelectionReason::FirstDynamic);
     }
     for alt in alternatives {
         let strength = SymbolStrength::determine(objects, alt);
         if strength != SymbolStrength::Undefined {
-            return Some(*alt);
+            return (Some(*alt), SelectionReason::FirstDynamic);
         }
     }
-    None
+    (None, SelectionReason::NoneLoaded)
+}
+
+/// How we chose between multiple definitions of a symbol. Only recorded for symbols that are being
+/// traced with `--trace-symbol`.
+struct SymbolSelection {
+    /// All the definitions that we chose between, including ones from files that weren't loaded.
+    definitions: Vec<crate::symbol::Symbol>,
+    selected: crate::symbol::Symbol,
+    reason: SelectionReason,
+}
+
+/// Why `select_symbol` chose the definition that it did.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SelectionReason {
+    FirstStrong,
+    LargestCommon,
+    FirstWeak,
+    FirstDynamic,
+    NoneLoaded,
+}
+
+impl std::fmt::Display for SelectionReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(
+            match self {
+                SelectionReason::FirstStrong => "it's the first strong definition",
+                SelectionReason::LargestCommon => {
+                    "there's no strong definition and it's the largest common definition"
+                }
+                SelectionReason::FirstWeak => {
+                    "there's no strong or common definition and it's the first weak definition"
+                }
+                SelectionReason::FirstDynamic => {
+                    "no regular object defines it and it's the first shared object that does"
+                }
+                SelectionReason::NoneLoaded => "none of the files that define it were loaded",
+            },
+            f,
+        )
+    }
+}
+
+/// Prints each loaded file that defines or references the symbol `name`, in the style of GNU ld's
+/// `--trace-symbol`. If there were multiple definitions, also prints which one we selected and why.
+fn print_symbol_trace(
+    symbol_db: &SymbolDb,
+    resolved: &[ResolvedFile],
+    selections: &AHashMap<GlobalSymbolId, SymbolSelection>,
+    name: &str,
+) {
+    for file in resolved {
+        let (input, symbols) = match file {
+            ResolvedFile::Object(obj) => (obj.input, obj.object.symbols()),
+            ResolvedFile::Dynamic(obj) => (obj.input, obj.object.dynamic_symbols()),
+            ResolvedFile::NotLoaded | ResolvedFile::Internal(_) => continue,
+        };
+        for symbol in symbols {
+            if symbol.is_local() || symbol.name_bytes().ok() != Some(name.as_bytes()) {
+                continue;
+            }
+            let kind = if symbol.is_undefined() {
+                if symbol.is_weak() {
+                    "weak reference to"
+                } else {
+                    "reference to"
+                }
+            } else if symbol.is_common() {
+                "common definition of"
+            } else if symbol.is_weak() {
+                "weak definition of"
+            } else {
+                "definition of"
+            };
+            println!("{input}: {kind} {name}");
+        }
+    }
+    let Some(&symbol_id) = symbol_db.symbol_ids.get(&SymbolName::new(name.as_bytes())) else {
+        return;
+    };
+    if symbol_db.is_defsym(symbol_id) {
+        println!("{name}: defined by --defsym, which takes precedence over other definitions");
+        return;
+    }
+    let Some(selection) = selections.get(&symbol_id) else {
+        return;
+    };
+    let candidates: Vec<String> = selection
+        .definitions
+        .iter()
+        .filter_map(|definition| {
+            let strength = SymbolStrength::determine(resolved, definition);
+            let description = match strength {
+                SymbolStrength::Undefined => return None,
+                SymbolStrength::Weak => "weak".to_owned(),
+                SymbolStrength::Strong => "strong".to_owned(),
+                SymbolStrength::Common(size) => format!("common, size {size}"),
+                SymbolStrength::Dynamic => "shared object".to_owned(),
+            };
+            Some(format!(
+                "{} ({description})",
+                resolved[definition.file_id.as_usize()]
+            ))
+        })
+        .collect();
+    println!(
+        "{name}: {} candidate definitions: {}",
+        candidates.len(),
+        candidates.join(", ")
+    );
+    if selection.reason == SelectionReason::NoneLoaded {
+        println!(
+            "{name}: no definition selected because {}",
+            selection.reason
+        );
+    } else {
+        println!(
+            "{name}: selected the definition in {} because {}",
+            resolved[selection.selected.file_id.as_usize()],
+            selection.reason
+        );
+    }
 }
 
 /// Filter out any internally defined symbols that have been overridden by user code.
@@ -210,7 +481,8 @@ fn filter_overridden_internal_symbols(
 
 #[derive(PartialEq, Eq, Clone, Copy)]
 enum SymbolStrength {
-    /// The object containing this symbol wasn't loaded, so the definition can be ignored.
+    /// The object containing this symbol wasn't loaded, or the symbol is in a section group that
+    /// was discarded, so the definition can be ignored.
     Undefined,
 
     /// The object weakly defines the symbol.
@@ -222,6 +494,10 @@ enum SymbolStrength {
     /// The symbol is a "common" symbol with the specified size. The definition with the largest
     /// size will be selected.
     Common(u64),
+
+    /// The symbol is defined by a shared object. Any definition from a regular object is
+    /// preferred.
+    Dynamic,
 }
 
 impl SymbolStrength {
@@ -234,13 +510,23 @@ impl SymbolStrength {
                 // Errors from this function should have been reported elsewhere.
                 return SymbolStrength::Undefined;
             };
-            if obj_symbol.is_weak() {
+            let in_discarded_group = obj_symbol.section_index().is_some_and(|section_index| {
+                matches!(
+                    obj.sections[section_index.0],
+                    SectionSlot::DiscardedGroupMember
+                )
+            });
+            if in_discarded_group {
+                SymbolStrength::Undefined
+            } else if obj_symbol.is_weak() {
                 SymbolStrength::Weak
             } else if obj_symbol.is_common() {
                 SymbolStrength::Common(obj_symbol.size())
             } else {
                 SymbolStrength::Strong
             }
+        } else if let ResolvedFile::Dynamic(_) = &objects[symbol.file_id.as_usize()] {
+            SymbolStrength::Dynamic
         } else {
             SymbolStrength::Undefined
         }
@@ -249,14 +535,18 @@ impl SymbolStrength {
 
 pub(crate) enum ResolvedFile<'data> {
     NotLoaded,
-    Internal(ResolvedInternal<'data>),
+    Internal(ResolvedInternal),
     Object(ResolvedObject<'data>),
+    Dynamic(ResolvedDynamic<'data>),
 }
 
 /// A section, but where we may or may not yet have decided to load it.
 #[derive(Debug)]
 pub(crate) enum SectionSlot<'data> {
     Discard,
+    /// The section is a member of a section group (e.g. a COMDAT group) for which we selected a
+    /// copy from some other object.
+    DiscardedGroupMember,
     Unloaded(UnloadedSection<'data>),
     Loaded(crate::layout::Section<'data>),
     EhFrameData(object::SectionIndex),
@@ -282,14 +572,11 @@ pub(crate) struct MergedStringResolution {
     pub(crate) offset: u64,
 }
 
-pub(crate) struct ResolvedInternal<'data> {
-    // TODO: Use this - when we implement dynamic linking
-    #[allow(dead_code)]
+pub(crate) struct ResolvedInternal {
     pub(crate) dynamic_linker: Option<CString>,
     pub(crate) symbol_definitions: Vec<InternalSymDefInfo>,
     pub(crate) defined: Vec<GlobalSymbolId>,
     pub(crate) file_id: FileId,
-    pub(crate) merged_strings: OutputSectionMap<MergedStringsSection<'data>>,
 }
 
 pub(crate) struct ResolvedObject<'data> {
@@ -298,22 +585,96 @@ pub(crate) struct ResolvedObject<'data> {
     pub(crate) file_id: FileId,
     pub(crate) local_symbol_resolutions: Vec<LocalSymbolResolution>,
     pub(crate) sections: Vec<SectionSlot<'data>>,
-    merge_strings_sections: Vec<MergeStringsFileSection<'data>>,
+    pub(crate) merge_strings_sections: Vec<MergeStringsFileSection<'data>>,
 
     /// Details about each custom section that is defined in this object. The index is an index into
     /// self.sections.
     custom_sections: Vec<(object::SectionIndex, SectionDetails<'data>)>,
+
+    /// The section groups defined by this object. Only present temporarily during resolution.
+    section_groups: Vec<SectionGroup<'data>>,
+}
+
+/// A group of sections that will be either loaded or discarded as a unit, depending on whether
+/// another object defined a group with the same signature first.
+struct SectionGroup<'data> {
+    signature: &'data [u8],
+    sections: Vec<object::SectionIndex>,
+}
+
+pub(crate) struct ResolvedDynamic<'data> {
+    pub(crate) input: InputRef<'data>,
+    pub(crate) object: Box<File<'data>>,
+    pub(crate) file_id: FileId,
 }
 
 #[derive(Debug)]
 pub(crate) struct MergeStringsFileSection<'data> {
+    section_index: object::SectionIndex,
+
     output_section_id: OutputSectionId,
 
-    /// The strings from this section. Only present temporarily during resolution.
+    /// The alignment of the input section, which each of its strings must retain.
+    alignment: Alignment,
+
+    /// The strings from this section. Only present until strings are merged.
     strings: Vec<StringToMerge<'data>>,
 
-    /// References into this section. Only present temporarily during resolution.
+    /// References into this section. Only present until strings are merged.
     references: Vec<RefToMergeString>,
+
+    /// The total size of the strings from this section that were discarded because no live
+    /// section referenced them. Filled in when strings are merged.
+    pub(crate) discarded_len: u64,
+}
+
+/// A reference to a string-merge section via the section's symbol. Assemblers emit these in place
+/// of references to local labels such as `.LC0`, e.g. `lea .LC0(%rip)` becomes
+/// `lea .rodata.str1.1-4+off(%rip)`, so which string is referenced depends on the addend. Only
+/// references from live sections are recorded.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MergeSectionRef {
+    pub(crate) section_index: object::SectionIndex,
+    /// The referenced offset within the input section. See `MergeSectionRef::referenced_offset`.
+    pub(crate) input_offset: u64,
+    /// Where the referenced byte ended up. Filled in when strings are merged.
+    pub(crate) resolution: Option<MergedStringResolution>,
+}
+
+impl MergeSectionRef {
+    /// Returns the offset within a string-merge section that `rel`, a relocation against the
+    /// section's symbol, references. That's the symbol's value plus the addend, except that for
+    /// PC-relative relocations the addend also includes the distance from the relocation to the
+    /// end of the instruction, which we take back off. Otherwise a reference to the first string
+    /// in the section would appear to be before the section and a reference to any other string
+    /// would appear to be to the end of the previous string.
+    pub(crate) fn referenced_offset(symbol_value: u64, rel: &object::Relocation) -> Result<u64> {
+        let object::RelocationFlags::Elf { r_type } = rel.flags() else {
+            unreachable!();
+        };
+        let rel_info = RelocationKindInfo::from_raw(r_type)?;
+        let pc_bias = match rel_info.kind {
+            RelocationKind::Relative
+            | RelocationKind::PltRelative
+            | RelocationKind::GotRelative => rel_info.byte_size as u64,
+            _ => 0,
+        };
+        Ok(symbol_value
+            .wrapping_add(rel.addend() as u64)
+            .wrapping_add(pc_bias))
+    }
+}
+
+/// An object's string-merge sections, together with what `merge_strings` needs from the rest of the
+/// object.
+pub(crate) struct ObjectStringsToMerge<'data, 'a> {
+    pub(crate) sections: &'a mut [MergeStringsFileSection<'data>],
+    pub(crate) local_symbol_resolutions: &'a mut [LocalSymbolResolution],
+    /// Sorted by section index then offset.
+    pub(crate) section_refs: &'a mut [MergeSectionRef],
+    /// Returns whether the specified symbol, which is defined in one of `sections`, was referenced
+    /// by a live section. Strings without any live references are discarded.
+    pub(crate) is_live: Box<dyn Fn(object::SymbolIndex) -> bool + 'a>,
 }
 
 /// A reference to a section that is enabled for string-merging.
@@ -335,83 +696,205 @@ struct StringToMerge<'data> {
 
 #[derive(Default)]
 struct MergeStringsSection<'data> {
-    strings: Vec<&'data [u8]>,
-    next_offset: u64,
-    string_offsets: crate::hash::PassThroughHashMap<StringToMerge<'data>, u64>,
+    /// The distinct strings, in the order in which they were first added, each with the largest
+    /// alignment of any input section that contained it.
+    strings: Vec<(StringToMerge<'data>, Alignment)>,
+    string_indexes: crate::hash::PassThroughHashMap<StringToMerge<'data>, usize>,
 }
 
+#[derive(Default)]
 pub(crate) struct MergedStringsSection<'data> {
     pub(crate) len: u64,
-    pub(crate) strings: Vec<&'data [u8]>,
+    pub(crate) alignment: Alignment,
+    /// The strings to be written, each with its offset into the section. Strings that are stored
+    /// as the tail of another string aren't included.
+    pub(crate) strings: Vec<(u64, &'data [u8])>,
+}
+
+/// A reference to a string that we're keeping. The reference can only be resolved once all strings
+/// have been added, since with tail merging, where a string goes depends on what other strings
+/// there are.
+struct LiveStringRef {
+    object_index: usize,
+    target: LiveStringRefTarget,
+    output_section_id: OutputSectionId,
+    string_index: usize,
+    offset_in_string: u64,
+}
+
+enum LiveStringRefTarget {
+    Symbol {
+        symbol_index: object::SymbolIndex,
+        global_symbol_id: Option<GlobalSymbolId>,
+    },
+    /// An index into the object's `section_refs`.
+    SectionRef(usize),
 }
 
 impl<'data> MergeStringsSection<'data> {
     /// Adds `string`, deduplicating with an existing string if an identical string is already
-    /// present. Returns the offset into the section.
-    fn add_string(&mut self, string: StringToMerge<'data>) -> u64 {
-        *self.string_offsets.entry(string).or_insert_with(|| {
-            let offset = self.next_offset;
-            self.next_offset += string.bytes.len() as u64;
-            self.strings.push(string.bytes);
-            offset
-        })
+    /// present. Returns the index of the string.
+    fn add_string(&mut self, string: StringToMerge<'data>, alignment: Alignment) -> usize {
+        let index = *self.string_indexes.entry(string).or_insert_with(|| {
+            self.strings.push((string, alignment));
+            self.strings.len() - 1
+        });
+        let existing = &mut self.strings[index].1;
+        *existing = (*existing).max(alignment);
+        index
+    }
+
+    /// Decides where each string goes, returning the offset of each string by index. If
+    /// `tail_merge` is set, a string that ends another string, e.g. "bar\0" and "foobar\0", is
+    /// stored as part of that string, provided the result is suitably aligned.
+    fn assign_offsets(self, tail_merge: bool) -> (Vec<u64>, MergedStringsSection<'data>) {
+        let mut order: Vec<usize> = (0..self.strings.len()).collect();
+        if tail_merge {
+            // Sorting by reversed bytes, largest first, puts each string immediately after a string
+            // that it's a suffix of, if there is one.
+            order.sort_unstable_by(|&a, &b| {
+                let a = self.strings[a].0.bytes.iter().rev();
+                let b = self.strings[b].0.bytes.iter().rev();
+                b.cmp(a)
+            });
+        }
+        let mut offsets = vec![0; self.strings.len()];
+        let mut merged = MergedStringsSection::default();
+        // The last string that we stored in full and its offset.
+        let mut previous: Option<(&[u8], u64)> = None;
+        for index in order {
+            let (string, alignment) = self.strings[index];
+            merged.alignment = merged.alignment.max(alignment);
+            if let Some((previous_bytes, previous_offset)) = previous {
+                if previous_bytes.ends_with(string.bytes) {
+                    let offset =
+                        previous_offset + (previous_bytes.len() - string.bytes.len()) as u64;
+                    if alignment.align_up(offset) == offset {
+                        offsets[index] = offset;
+                        continue;
+                    }
+                }
+            }
+            let offset = alignment.align_up(merged.len);
+            offsets[index] = offset;
+            merged.len = offset + string.bytes.len() as u64;
+            merged.strings.push((offset, string.bytes));
+            if tail_merge {
+                previous = Some((string.bytes, offset));
+            }
+        }
+        (offsets, merged)
     }
 }
 
 /// Merges identical strings from all loaded objects where those strings are from input sections
-/// that are marked with both the SHF_MERGE and SHF_STRINGS flags.
+/// that are marked with both the SHF_MERGE and SHF_STRINGS flags. This is done once we know which
+/// sections are live, so that strings that are only referenced from discarded sections can
+/// themselves be discarded. `objects` should be in file order, so that our output is deterministic.
 #[tracing::instrument(skip_all, name = "Merge strings")]
-fn merge_strings<'data>(
-    resolved: &mut [ResolvedFile<'data>],
+pub(crate) fn merge_strings<'data>(
+    objects: &mut [ObjectStringsToMerge<'data, '_>],
     output_sections: &OutputSections,
+    args: &Args,
 ) -> Result<OutputSectionMap<MergedStringsSection<'data>>> {
     let mut strings_by_section: OutputSectionMap<MergeStringsSection> =
         OutputSectionMap::with_size(output_sections.len());
-    for file in resolved {
-        let ResolvedFile::Object(obj) = file else {
-            continue;
-        };
-        for sec in &mut obj.merge_strings_sections {
-            let string_to_offset = strings_by_section.get_mut(sec.output_section_id);
-            let mut symbols = sec.references.iter().peekable();
+    let mut live_refs = Vec::new();
+    for (object_index, obj) in objects.iter_mut().enumerate() {
+        for sec in obj.sections.iter_mut() {
+            let section_strings = strings_by_section.get_mut(sec.output_section_id);
+            // References are sorted by offset, so the references to each string immediately follow
+            // those to the previous string.
+            let mut remaining_refs = sec.references.as_slice();
+            let mut next_section_ref = obj
+                .section_refs
+                .partition_point(|r| r.section_index.0 < sec.section_index.0);
             // The offset within the input section of the current string.
             let mut input_offset = 0;
             for string in &sec.strings {
-                let output_offset = string_to_offset.add_string(*string);
-                while let Some(merge_ref) = symbols.peek() {
-                    debug_assert!(
-                        merge_ref.offset >= input_offset,
-                        "String-merge symbol offsets went backwards"
-                    );
-                    let offset_into_string = merge_ref.offset - input_offset;
-                    if offset_into_string >= string.bytes.len() as u64 {
-                        // This reference belongs to a subsequent string.
-                        break;
-                    }
-                    obj.local_symbol_resolutions[merge_ref.symbol_index.0] =
-                        LocalSymbolResolution::MergedString(MergedStringResolution {
-                            symbol_id: merge_ref.global_symbol_id,
+                let string_end = input_offset + string.bytes.len() as u64;
+                let num_refs = remaining_refs
+                    .iter()
+                    .take_while(|merge_ref| merge_ref.offset < string_end)
+                    .count();
+                let (string_refs, rest) = remaining_refs.split_at(num_refs);
+                remaining_refs = rest;
+                let section_refs_start = next_section_ref;
+                while obj.section_refs.get(next_section_ref).is_some_and(|r| {
+                    r.section_index == sec.section_index && r.input_offset < string_end
+                }) {
+                    next_section_ref += 1;
+                }
+                if next_section_ref > section_refs_start
+                    || string_refs
+                        .iter()
+                        .any(|merge_ref| (obj.is_live)(merge_ref.symbol_index))
+                {
+                    let string_index = section_strings.add_string(*string, sec.alignment);
+                    let mut add_live_ref = |target, offset: u64| {
+                        live_refs.push(LiveStringRef {
+                            object_index,
+                            target,
                             output_section_id: sec.output_section_id,
-                            offset: output_offset + offset_into_string,
-                        });
-                    symbols.next();
+                            string_index,
+                            offset_in_string: offset - input_offset,
+                        })
+                    };
+                    for merge_ref in string_refs {
+                        let target = LiveStringRefTarget::Symbol {
+                            symbol_index: merge_ref.symbol_index,
+                            global_symbol_id: merge_ref.global_symbol_id,
+                        };
+                        add_live_ref(target, merge_ref.offset);
+                    }
+                    for index in section_refs_start..next_section_ref {
+                        let target = LiveStringRefTarget::SectionRef(index);
+                        add_live_ref(target, obj.section_refs[index].input_offset);
+                    }
+                } else {
+                    sec.discarded_len += string.bytes.len() as u64;
                 }
-                input_offset += string.bytes.len() as u64;
+                input_offset = string_end;
             }
         }
     }
-    Ok(strings_by_section.into_map(|s| MergedStringsSection {
-        len: s.next_offset,
-        strings: s.strings,
-    }))
+    let assigned = strings_by_section.into_map(|s| s.assign_offsets(args.tail_merge_strings));
+    for live_ref in live_refs {
+        let (offsets, _) = assigned.get(live_ref.output_section_id);
+        let obj = &mut objects[live_ref.object_index];
+        let offset = offsets[live_ref.string_index] + live_ref.offset_in_string;
+        match live_ref.target {
+            LiveStringRefTarget::Symbol {
+                symbol_index,
+                global_symbol_id,
+            } => {
+                obj.local_symbol_resolutions[symbol_index.0] =
+                    LocalSymbolResolution::MergedString(MergedStringResolution {
+                        symbol_id: global_symbol_id,
+                        output_section_id: live_ref.output_section_id,
+                        offset,
+                    });
+            }
+            LiveStringRefTarget::SectionRef(index) => {
+                obj.section_refs[index].resolution = Some(MergedStringResolution {
+                    symbol_id: None,
+                    output_section_id: live_ref.output_section_id,
+                    offset,
+                });
+            }
+        }
+    }
+    Ok(assigned.into_map(|(_, merged)| merged))
 }
 
 #[tracing::instrument(skip_all, name = "Assign section IDs")]
 fn assign_section_ids<'data>(
     resolved: &[ResolvedFile<'data>],
-    args: &Args,
+    symbol_db: &SymbolDb,
 ) -> Result<OutputSections<'data>> {
+    let args = symbol_db.args;
     let mut output_sections_builder = OutputSectionsBuilder::with_base_address(args.base_address());
+    output_sections_builder.set_relro(args.relro && symbol_db.needs_dynamic());
     for s in resolved {
         if let ResolvedFile::Object(s) = s {
             output_sections_builder.add_sections(&s.custom_sections)?;
@@ -429,6 +912,17 @@ struct Outputs<'data> {
 
     /// Start/stop references to custom sections.
     start_stop_sets: SegQueue<StartStopSet<'data>>,
+
+    /// Why each archive entry was loaded. Only populated if `--trace` was given.
+    load_reasons: SegQueue<LoadReason>,
+}
+
+/// A symbol reference that caused an archive entry to be loaded.
+#[derive(Clone, Copy)]
+struct LoadReason {
+    symbol_id: GlobalSymbolId,
+    /// The file containing the reference. None if the symbol was requested on the command line.
+    referenced_by: Option<FileId>,
 }
 
 impl<'data> Outputs<'data> {
@@ -437,6 +931,7 @@ impl<'data> Outputs<'data> {
             loaded: ArrayQueue::new(num_objects),
             errors: ArrayQueue::new(1),
             start_stop_sets: SegQueue::new(),
+            load_reasons: SegQueue::new(),
         }
     }
 }
@@ -448,23 +943,51 @@ fn process_object<'scope, 'data: 'scope>(
     s: &rayon::Scope<'scope>,
     outputs: &'scope Outputs<'data>,
 ) -> Result {
-    let request_file_id = |file_id: FileId| {
-        if let Some(entry) = archive_entries[file_id.as_usize()].take() {
-            s.spawn(|s| {
-                let r = process_object(*entry, symbol_db, archive_entries, s, outputs);
-                if let Err(error) = r {
-                    let _ = outputs.errors.push(error);
-                }
-            });
-        }
+    let file_id = obj.file_id;
+    let request_symbol = |symbol_id: GlobalSymbolId| {
+        request_archive_entry(
+            symbol_id,
+            Some(file_id),
+            symbol_db,
+            archive_entries,
+            s,
+            outputs,
+        );
     };
     let input = obj.input;
-    let res = ResolvedObject::new(obj, symbol_db, request_file_id, &outputs.start_stop_sets)
+    let res = ResolvedObject::new(obj, symbol_db, request_symbol, &outputs.start_stop_sets)
         .with_context(|| format!("Failed to process {input}"))?;
     let _ = outputs.loaded.push(res);
     Ok(())
 }
 
+/// Starts processing the file that defines `symbol_id` if it's an archive entry that hasn't already
+/// been loaded. `referenced_by` is the file that referenced the symbol, if any.
+fn request_archive_entry<'scope, 'data: 'scope>(
+    symbol_id: GlobalSymbolId,
+    referenced_by: Option<FileId>,
+    symbol_db: &'scope SymbolDb<'data>,
+    archive_entries: &'scope [AtomicCell<Option<Box<ObjectSymbols<'data>>>>],
+    s: &rayon::Scope<'scope>,
+    outputs: &'scope Outputs<'data>,
+) {
+    let file_id = symbol_db.symbol(symbol_id).file_id;
+    if let Some(entry) = archive_entries[file_id.as_usize()].take() {
+        if symbol_db.args.trace {
+            outputs.load_reasons.push(LoadReason {
+                symbol_id,
+                referenced_by,
+            });
+        }
+        s.spawn(|s| {
+            let r = process_object(*entry, symbol_db, archive_entries, s, outputs);
+            if let Err(error) = r {
+                let _ = outputs.errors.push(error);
+            }
+        });
+    }
+}
+
 struct StartStopSet<'data> {
     file_id: FileId,
     start_stop_refs: AHashMap<&'data [u8], Vec<object::SymbolIndex>>,
@@ -516,7 +1039,7 @@ fn allocate_start_stop_symbol_ids<'data>(
             )
         };
 
-        let global_symbol_id = symbol_db.add_start_stop_symbol(symbol_name, local_index)?;
+        let global_symbol_id = symbol_db.add_internal_symbol(symbol_name, local_index)?;
         let def_info = if is_start {
             symbol_db::InternalSymDefInfo::SectionStart(section_id)
         } else {
@@ -542,6 +1065,109 @@ fn allocate_start_stop_symbol_ids<'data>(
     Ok(())
 }
 
+/// When producing a shared object, references to symbols that nothing defines aren't errors.
+/// Instead, each such symbol is given an ID owned by our internal file and is left undefined in
+/// .dynsym for the dynamic loader to resolve at runtime. This includes weak references, which
+/// would otherwise just resolve to zero. If all references to a symbol are weak, then the symbol
+/// is emitted as weak.
+#[tracing::instrument(skip_all, name = "Allocate undefined symbol IDs")]
+fn allocate_undefined_symbol_ids<'data>(
+    internal: &mut InternalSymbols,
+    objects: &mut [ResolvedFile<'data>],
+    symbol_db: &mut SymbolDb<'data>,
+) -> Result {
+    let mut names: BTreeMap<&'data [u8], Vec<(FileId, object::SymbolIndex)>> = Default::default();
+    for file in objects.iter() {
+        let ResolvedFile::Object(obj) = file else {
+            continue;
+        };
+        for (index, res) in obj.local_symbol_resolutions.iter().enumerate() {
+            if !matches!(
+                res,
+                LocalSymbolResolution::UndefinedSymbol | LocalSymbolResolution::UnresolvedWeak
+            ) {
+                continue;
+            }
+            let sym_index = object::SymbolIndex(index);
+            let symbol = obj.object.symbol_by_index(sym_index)?;
+            // Symbols in discarded COMDAT groups are also unresolved weak, but they're defined
+            // rather than being references to something outside of the object.
+            if !symbol.is_undefined() {
+                continue;
+            }
+            let name = symbol.name_bytes()?;
+            // References that `--wrap` redirected were looked up under a different name. Those
+            // still get reported as undefined.
+            if symbol_db.wrapped_reference_name(name).is_some() {
+                continue;
+            }
+            names
+                .entry(name)
+                .or_default()
+                .push((obj.file_id, sym_index));
+        }
+    }
+    for (symbol_name, refs) in names {
+        let local_index = object::SymbolIndex(internal.symbol_definitions.len());
+        let global_symbol_id = symbol_db.add_internal_symbol(symbol_name, local_index)?;
+        let def_info = if all_unresolved_weak(&refs, objects) {
+            symbol_db::InternalSymDefInfo::UndefinedWeak
+        } else {
+            symbol_db::InternalSymDefInfo::Undefined
+        };
+        internal.symbol_definitions.push(def_info);
+        internal.defined.push(global_symbol_id);
+        symbol_db.dynamic_symbols.insert(global_symbol_id);
+        for (file_id, sym_index) in refs {
+            if let ResolvedFile::Object(obj) = &mut objects[file_id.as_usize()] {
+                let res = &mut obj.local_symbol_resolutions[sym_index.0];
+                *res = match *res {
+                    LocalSymbolResolution::UnresolvedWeak => {
+                        LocalSymbolResolution::WeakRefToGlobal(global_symbol_id)
+                    }
+                    _ => LocalSymbolResolution::Global(global_symbol_id),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// When producing a shared object, symbols defined by other shared objects are bound by the dynamic
+/// loader. We reference them via .dynsym rather than making copies of them.
+fn mark_imported_symbols_dynamic(objects: &[ResolvedFile], symbol_db: &mut SymbolDb) {
+    let imported = symbol_db
+        .symbol_ids
+        .values()
+        .copied()
+        .filter(|&symbol_id| {
+            let file_id = symbol_db.symbol(symbol_id).file_id;
+            matches!(objects[file_id.as_usize()], ResolvedFile::Dynamic(_))
+        })
+        .collect::<Vec<_>>();
+    symbol_db.dynamic_symbols.extend(imported);
+}
+
+/// When producing a shared object, our exported symbols can be interposed by definitions that the
+/// dynamic loader finds first, so references to them need to go via .dynsym too.
+fn mark_interposable_symbols_dynamic(objects: &[ResolvedFile], symbol_db: &mut SymbolDb) -> Result {
+    let mut interposable = Vec::new();
+    for &symbol_id in symbol_db.symbol_ids.values() {
+        let symbol = symbol_db.symbol(symbol_id);
+        let ResolvedFile::Object(obj) = &objects[symbol.file_id.as_usize()] else {
+            continue;
+        };
+        let sym = obj
+            .object
+            .symbol_by_index(symbol.local_index_for_file(obj.file_id)?)?;
+        if symbol_db.is_interposable(&sym) {
+            interposable.push(symbol_id);
+        }
+    }
+    symbol_db.dynamic_symbols.extend(interposable);
+    Ok(())
+}
+
 /// Returns whether all the specified symbols in the specified files are unresolved weak references.
 fn all_unresolved_weak(
     refs: &[(FileId, object::SymbolIndex)],
@@ -563,16 +1189,17 @@ impl<'data> ResolvedObject<'data> {
     fn new(
         obj: symbol_db::ObjectSymbols<'data>,
         symbol_db: &SymbolDb<'data>,
-        request_file_id: impl FnMut(FileId),
+        request_symbol: impl FnMut(GlobalSymbolId),
         start_stop_sets: &SegQueue<StartStopSet<'data>>,
     ) -> Result<Self> {
         let mut custom_sections = Vec::new();
         let mut sections = resolve_sections(&obj, &mut custom_sections, symbol_db.args)?;
+        let section_groups = read_section_groups(&obj)?;
 
         let local_symbol_resolutions = resolve_symbols(
             &obj,
             symbol_db,
-            request_file_id,
+            request_symbol,
             start_stop_sets,
             &mut sections,
         )
@@ -597,8 +1224,95 @@ impl<'data> ResolvedObject<'data> {
             sections,
             custom_sections,
             merge_strings_sections,
+            section_groups,
         })
     }
+
+    /// Discards the members of `group`, since some other object's copy of the group was selected.
+    /// Any references from elsewhere in this object to local symbols in the discarded sections will
+    /// resolve to zero, the same as for an undefined weak symbol.
+    fn discard_group(&mut self, group: &SectionGroup) {
+        for &section_index in &group.sections {
+            self.sections[section_index.0] = SectionSlot::DiscardedGroupMember;
+        }
+        self.merge_strings_sections
+            .retain(|sec| !group.sections.contains(&sec.section_index));
+        self.custom_sections
+            .retain(|(section_index, _)| !group.sections.contains(section_index));
+        for symbol in self.object.symbols() {
+            let Some(section_index) = symbol.section_index() else {
+                continue;
+            };
+            if !group.sections.contains(&section_index) {
+                continue;
+            }
+            let resolution = &mut self.local_symbol_resolutions[symbol.index().0];
+            if matches!(resolution, LocalSymbolResolution::LocalSection(_)) {
+                *resolution = LocalSymbolResolution::UnresolvedWeak;
+            }
+        }
+    }
+}
+
+/// Returns the COMDAT groups in `obj` as well as any legacy `.gnu.linkonce.*` sections, each of
+/// which is treated as a group containing just that section, with the section name as the
+/// signature.
+fn read_section_groups<'data>(obj: &ObjectSymbols<'data>) -> Result<Vec<SectionGroup<'data>>> {
+    let mut groups = Vec::new();
+    for comdat in obj.object.comdats() {
+        let symbol = obj.object.symbol_by_index(comdat.symbol())?;
+        let mut signature = symbol.name_bytes()?;
+        if signature.is_empty() {
+            // The signature symbol can be a section symbol, in which case the signature is the name
+            // of the section.
+            if let Some(section_index) = symbol.section_index() {
+                signature = obj.object.section_by_index(section_index)?.name_bytes()?;
+            }
+        }
+        groups.push(SectionGroup {
+            signature,
+            sections: comdat.sections().collect(),
+        });
+    }
+    for section in obj.object.sections() {
+        let name = section.name_bytes()?;
+        if name.starts_with(b".gnu.linkonce.") {
+            groups.push(SectionGroup {
+                signature: name,
+                sections: vec![section.index()],
+            });
+        }
+    }
+    Ok(groups)
+}
+
+/// Selects which copy of each section group to use and discards the rest. The first loaded object
+/// that defines a group with a particular signature wins. This needs to happen after all objects
+/// have been loaded, since we load objects in parallel and the order in which we do so isn't
+/// deterministic.
+#[tracing::instrument(skip_all, name = "Resolve section groups")]
+fn resolve_section_groups(resolved: &mut [ResolvedFile]) {
+    let mut selected = AHashSet::new();
+    for file in resolved {
+        let ResolvedFile::Object(obj) = file else {
+            continue;
+        };
+        for group in core::mem::take(&mut obj.section_groups) {
+            if !selected.insert(group.signature) {
+                obj.discard_group(&group);
+            }
+        }
+    }
+}
+
+impl<'data> ResolvedDynamic<'data> {
+    fn new(obj: symbol_db::ObjectSymbols<'data>) -> Self {
+        Self {
+            input: obj.input,
+            object: obj.object,
+            file_id: obj.file_id,
+        }
+    }
 }
 
 fn resolve_sections<'data>(
@@ -638,7 +1352,7 @@ fn resolve_sections<'data>(
 fn resolve_symbols<'data>(
     obj: &symbol_db::ObjectSymbols<'data>,
     symbol_db: &SymbolDb<'data>,
-    mut request_file_id: impl FnMut(FileId),
+    mut request_symbol: impl FnMut(GlobalSymbolId),
     start_stop_sets: &SegQueue<StartStopSet<'data>>,
     sections: &mut [SectionSlot<'data>],
 ) -> Result<Vec<LocalSymbolResolution>> {
@@ -656,12 +1370,18 @@ fn resolve_symbols<'data>(
                     LocalSymbolResolution::Null
                 }
             } else {
-                match symbol_db.symbol_ids.get(&SymbolName::new(name_bytes)) {
+                let wrapped_name = if local_symbol.is_undefined() {
+                    symbol_db.wrapped_reference_name(name_bytes)
+                } else {
+                    None
+                };
+                let lookup_name = wrapped_name.as_deref().unwrap_or(name_bytes);
+                match symbol_db.symbol_ids.get(&SymbolName::new(lookup_name)) {
                     Some(&symbol_id) => {
                         global_symbol_id = Some(symbol_id);
                         let symbol = symbol_db.symbol(symbol_id);
                         if symbol.file_id != obj.file_id && !local_symbol.is_weak() {
-                            request_file_id(symbol.file_id);
+                            request_symbol(symbol_id);
                         }
 
                         if local_symbol.is_weak() {
@@ -699,7 +1419,12 @@ fn resolve_symbols<'data>(
                     }
                 }
             };
-            if let Some(local_section_index) = local_symbol.section_index() {
+            // References via section symbols are handled separately, since which string they refer
+            // to depends on the addend.
+            if let (Some(local_section_index), false) = (
+                local_symbol.section_index(),
+                local_symbol.kind() == object::SymbolKind::Section,
+            ) {
                 if let SectionSlot::MergeStrings(merge) = &mut sections[local_section_index.0] {
                     merge.references.push(RefToMergeString {
                         global_symbol_id,
@@ -728,6 +1453,15 @@ impl LocalSymbolResolution {
             _ => None,
         }
     }
+
+    /// Returns the global symbol whose resolution references get, if any. Unlike
+    /// `global_symbol_id`, this includes global symbols that point into string-merge sections.
+    pub(crate) fn referenced_symbol_id(self) -> Option<GlobalSymbolId> {
+        match self {
+            LocalSymbolResolution::MergedString(res) => res.symbol_id,
+            other => other.global_symbol_id(),
+        }
+    }
 }
 
 impl<'data> std::fmt::Display for ResolvedObject<'data> {
@@ -736,12 +1470,19 @@ impl<'data> std::fmt::Display for ResolvedObject<'data> {
     }
 }
 
+impl<'data> std::fmt::Display for ResolvedDynamic<'data> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.input, f)
+    }
+}
+
 impl<'data> std::fmt::Display for ResolvedFile<'data> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ResolvedFile::NotLoaded => std::fmt::Display::fmt("<not loaded>", f),
             ResolvedFile::Internal(_) => std::fmt::Display::fmt("<internal>", f),
             ResolvedFile::Object(o) => std::fmt::Display::fmt(o, f),
+            ResolvedFile::Dynamic(o) => std::fmt::Display::fmt(o, f),
         }
     }
 }
@@ -758,37 +1499,57 @@ impl<'data> MergeStringsFileSection<'data> {
         output_section_id: OutputSectionId,
     ) -> Result<MergeStringsFileSection<'data>> {
         let mut remaining = input_section.data()?;
+        let entsize = crate::elf::section_entsize(&input_section) as usize;
         let mut strings = Vec::new();
         while !remaining.is_empty() {
-            let len = memchr::memchr(0, remaining)
-                .map(|i| i + 1)
-                .with_context(|| {
-                    format!(
-                        "String in section `{}` is not null-terminated",
-                        input_section.name().unwrap_or("??")
-                    )
-                })?;
+            let len = null_terminated_len(remaining, entsize).with_context(|| {
+                format!(
+                    "String in section `{}` is not null-terminated",
+                    input_section.name().unwrap_or("??")
+                )
+            })?;
             let (bytes, rest) = remaining.split_at(len);
             let hash = crate::hash::hash_bytes(bytes);
             strings.push(StringToMerge { bytes, hash });
             remaining = rest;
         }
         Ok(MergeStringsFileSection {
+            section_index: input_section.index(),
             output_section_id,
+            alignment: Alignment::new(input_section.align().max(1))?,
             strings,
             // This will get filled in when we read the symbol table.
             references: Default::default(),
+            discarded_len: 0,
         })
     }
 
     /// Returns an owned version of `self` with the heap-allocated parts of `self` cleared.
     fn take(&mut self) -> MergeStringsFileSection<'data> {
         MergeStringsFileSection {
+            section_index: self.section_index,
             output_section_id: self.output_section_id,
+            alignment: self.alignment,
             strings: core::mem::take(&mut self.strings),
             references: core::mem::take(&mut self.references),
+            discarded_len: 0,
         }
     }
+
+    pub(crate) fn section_index(&self) -> object::SectionIndex {
+        self.section_index
+    }
+}
+
+/// Returns the length, including the terminator, of the string at the start of `data`, where each
+/// character is `entsize` bytes and the string ends with a character that's all zeros.
+fn null_terminated_len(data: &[u8], entsize: usize) -> Option<usize> {
+    if entsize <= 1 {
+        return memchr::memchr(0, data).map(|i| i + 1);
+    }
+    data.chunks_exact(entsize)
+        .position(|ch| ch.iter().all(|b| *b == 0))
+        .map(|i| (i + 1) * entsize)
 }
 
 impl<'data> std::hash::Hash for StringToMerge<'data> {
@@ -796,3 +1557,57 @@ impl<'data> std::hash::Hash for StringToMerge<'data> {
         state.write_u64(self.hash);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(bytes: &[u8]) -> StringToMerge {
+        StringToMerge {
+            bytes,
+            hash: crate::hash::hash_bytes(bytes),
+        }
+    }
+
+    #[test]
+    fn test_null_terminated_len() {
+        assert_eq!(null_terminated_len(b"ab\0cd\0", 1), Some(3));
+        assert_eq!(null_terminated_len(b"ab", 1), None);
+        assert_eq!(null_terminated_len(b"a\0\0\0\0\0", 2), Some(4));
+        assert_eq!(null_terminated_len(b"a\0\0\0b\0\0\0\0\0\0\0", 4), Some(12));
+        assert_eq!(null_terminated_len(b"a\0\0", 2), None);
+    }
+
+    #[test]
+    fn test_assign_offsets() {
+        let add_all = |strings: &[(&'static [u8], u64)]| {
+            let mut section = MergeStringsSection::default();
+            for (bytes, alignment) in strings {
+                section.add_string(string(bytes), Alignment::new(*alignment).unwrap());
+            }
+            section
+        };
+        let strings: &[(&[u8], u64)] = &[
+            (b"bar\0", 1),
+            (b"foobar\0", 1),
+            (b"bar\0", 1),
+            (b"ar\0", 4),
+            (b"x\0", 4),
+        ];
+
+        let (offsets, merged) = add_all(strings).assign_offsets(false);
+        assert_eq!(offsets, [0, 4, 12, 16]);
+        assert_eq!(merged.len, 18);
+        assert_eq!(merged.alignment, Alignment::new(4).unwrap());
+        assert_eq!(merged.strings.len(), 4);
+
+        // "bar" is stored within "foobar", but "ar" isn't, since it would be misaligned.
+        let (offsets, merged) = add_all(strings).assign_offsets(true);
+        assert_eq!(offsets, [5, 2, 12, 0]);
+        assert_eq!(merged.len, 15);
+        assert_eq!(
+            merged.strings,
+            [(0, &b"x\0"[..]), (2, b"foobar\0"), (12, b"ar\0")]
+        );
+    }
+}
@@ -68,12 +68,14 @@ impl SaveDir {
         }
     }
 
-    pub(crate) fn finish(&self) -> Result {
+    /// Writes a script that reruns the link. `args` should be our arguments, excluding the program
+    /// name, with any response files already expanded.
+    pub(crate) fn finish(&self, args: &[String]) -> Result {
         let Some(dir) = self.dir.as_ref() else {
             return Ok(());
         };
         let run_with_file = dir.join("run-with");
-        self.write_args_file(&run_with_file)
+        self.write_args_file(&run_with_file, args)
             .with_context(|| format!("Failed to write `{}`", run_with_file.display()))?;
         if std::env::var(SKIP_LINKING_ENV).is_ok() {
             std::process::exit(0);
@@ -81,19 +83,17 @@ impl SaveDir {
         Ok(())
     }
 
-    fn write_args_file(&self, run_file: &Path) -> Result {
+    fn write_args_file(&self, run_file: &Path, args: &[String]) -> Result {
         let mut file = std::fs::File::create(run_file)?;
         let mut out = BufWriter::new(&mut file);
-        let mut args = std::env::args();
         out.write_all(PRELUDE.as_bytes())?;
-        args.next();
         self.write_args(args, &mut out)?;
         drop(out);
         crate::fs::make_executable(&file)?;
         Ok(())
     }
 
-    fn write_args(&self, args: std::env::Args, out: &mut BufWriter<&mut std::fs::File>) -> Result {
+    fn write_args(&self, args: &[String], out: &mut BufWriter<&mut std::fs::File>) -> Result {
         let mut is_output_file = false;
         let mut original_output_file = None;
         for arg in args {
@@ -105,7 +105,7 @@ impl SaveDir {
                 continue;
             }
             is_output_file = arg == "-o";
-            if let Some(copied) = self.copied_paths.get(&arg) {
+            if let Some(copied) = self.copied_paths.get(arg) {
                 out.write_all(b"$D/")?;
                 out.write_all(copied.as_bytes())?;
             } else {
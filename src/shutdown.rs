@@ -2,10 +2,9 @@
 //! drop. The most expensive of these in the input data, which is expensive because the kernel takes
 //! time to unmap all the memory from our process. It might seem tempting to just leak these, but
 //! that doesn't actually help. Benchmarks indicate that this just shifts the time to when the
-//! process terminates and the total execution time remains unchanged. Probably the only way to
-//! mitigate this is to hide the shutdown time in a forked subprocess. i.e. fork on startup, do the
-//! work in the forked process then when we're done, signal the parent process that we're done, it
-//! can then exit while the forked process cleans up.
+//! process terminates and the total execution time remains unchanged. Instead, unless `--no-fork`
+//! is given, we hide the shutdown time by doing all our work in a forked subprocess. See
+//! `subprocess.rs`.
 
 #[tracing::instrument(skip_all, name = "Drop layout")]
 pub(crate) fn free_layout(d: crate::layout::Layout) {
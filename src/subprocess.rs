@@ -0,0 +1,92 @@
+//! Support for doing the link in a forked subprocess. Freeing all of our memory and in particular,
+//! unmapping our input files, takes a significant amount of time. By forking at startup, doing the
+//! link in the child and having the child tell the parent once the output file has been written,
+//! the parent can exit while the child cleans up. Whoever invoked us can then get on with whatever
+//! they were going to do with our output.
+
+use crate::error::Result;
+use anyhow::bail;
+use std::fs::File;
+use std::io::Read;
+use std::io::Write;
+use std::os::fd::AsRawFd;
+use std::os::fd::FromRawFd;
+
+/// Held by the child process. Used to tell the parent process that we're done.
+pub(crate) struct DoneSignal {
+    pipe: File,
+}
+
+/// Forks. In the parent, this waits for the child to finish, then exits with an appropriate status,
+/// so never returns. In the child, it returns a signal that should be used once the output file has
+/// been written.
+///
+/// This must be called before any threads are started, since only the calling thread is preserved
+/// in the child.
+pub(crate) fn run_in_subprocess() -> Result<DoneSignal> {
+    let mut fds: [libc::c_int; 2] = [0; 2];
+    // Safety: `fds` is a valid array of two file descriptors for `pipe` to fill in.
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        bail!("Failed to create pipe: {}", std::io::Error::last_os_error());
+    }
+    // Safety: We've only got one thread at this point, so the child's state is consistent.
+    let pid = unsafe { libc::fork() };
+    if pid < 0 {
+        bail!("Failed to fork: {}", std::io::Error::last_os_error());
+    }
+    // Safety: `pipe` gave us these file descriptors and nothing else owns them.
+    let (read_end, write_end) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+    if pid == 0 {
+        drop(read_end);
+        return Ok(DoneSignal { pipe: write_end });
+    }
+    drop(write_end);
+    wait_for_child(pid, read_end)
+}
+
+fn wait_for_child(pid: libc::pid_t, mut read_end: File) -> ! {
+    let mut buffer = [0_u8; 1];
+    if matches!(read_end.read(&mut buffer), Ok(1)) {
+        std::process::exit(0);
+    }
+    // The child exited without signalling us. Most likely, the link failed, in which case the child
+    // will already have reported why.
+    let mut status = 0;
+    // Safety: `status` is a valid place for `waitpid` to write to.
+    if unsafe { libc::waitpid(pid, &mut status, 0) } < 0 {
+        eprintln!(
+            "wild: error: Failed to wait for subprocess: {}",
+            std::io::Error::last_os_error()
+        );
+        std::process::exit(1);
+    }
+    if libc::WIFEXITED(status) {
+        std::process::exit(libc::WEXITSTATUS(status));
+    }
+    if libc::WIFSIGNALED(status) {
+        // Same as what a shell reports for a process that was killed by a signal.
+        std::process::exit(128 + libc::WTERMSIG(status));
+    }
+    std::process::exit(1);
+}
+
+impl DoneSignal {
+    /// Tells the parent process that the output file has been written, allowing it to exit. Callers
+    /// should first give back any jobserver tokens that they hold.
+    pub(crate) fn notify(mut self) {
+        // Whoever invoked our parent may have captured our stdout and stderr, in which case they'll
+        // wait until all copies of those pipes are closed, not just until our parent exits. So we
+        // flush anything that's still buffered, then point both at /dev/null.
+        let _ = std::io::stdout().flush();
+        let _ = std::io::stderr().flush();
+        if let Ok(dev_null) = File::options().write(true).open("/dev/null") {
+            // Safety: Both file descriptors are valid and `dup2` doesn't touch any memory.
+            unsafe {
+                libc::dup2(dev_null.as_raw_fd(), libc::STDOUT_FILENO);
+                libc::dup2(dev_null.as_raw_fd(), libc::STDERR_FILENO);
+            }
+        }
+        // If the parent has gone away, there's nobody left to tell, so ignore any error.
+        let _ = self.pipe.write_all(&[1]);
+    }
+}
@@ -3,20 +3,24 @@
 
 use crate::archive_splitter::InputBytes;
 use crate::args::Args;
+use crate::args::Defsym;
+use crate::args::DefsymValue;
 use crate::elf::File;
 use crate::error::Result;
 use crate::file_kind::FileKind;
 use crate::hash::PassThroughHashMap;
 use crate::input_data;
 use crate::input_data::FileId;
+use crate::input_data::InputData;
 use crate::input_data::InputRef;
 use crate::output_section_id;
 use crate::output_section_id::OutputSectionId;
 use crate::symbol;
 use crate::symbol::Symbol;
 use crate::symbol::SymbolName;
+use crate::version_script::VersionScript;
 use ahash::AHashMap;
-use anyhow::bail;
+use ahash::AHashSet;
 use anyhow::Context;
 use object::Object;
 use object::ObjectSymbol;
@@ -34,6 +38,22 @@ pub(crate) struct SymbolDb<'data> {
     symbols: Vec<Symbol>,
     symbol_names: Vec<SymbolName<'data>>,
     pub(crate) alternate_definitions: AHashMap<GlobalSymbolId, Vec<Symbol>>,
+
+    /// The number of our inputs that are shared objects.
+    pub(crate) num_dynamic_inputs: usize,
+
+    /// Which symbols to export when producing a shared object.
+    pub(crate) version_script: &'data VersionScript,
+
+    /// The symbol (or address) at which execution should start, if one was specified.
+    pub(crate) entry: Option<&'data str>,
+
+    /// Symbols that were passed to `--wrap`.
+    wrapped_symbols: AHashSet<&'data [u8]>,
+
+    /// Symbols that the dynamic loader binds at runtime, so references to them need to go via
+    /// .dynsym. Only populated when producing a shared object.
+    pub(crate) dynamic_symbols: AHashSet<GlobalSymbolId>,
 }
 
 /// A symbol that hasn't been given an ID yet.
@@ -65,17 +85,27 @@ pub(crate) enum InternalSymDefInfo {
     /// Defines a symbol that points at the non-inclusive end of the section. i.e. 1 byte past the
     /// last byte of the section.
     SectionEnd(OutputSectionId),
+
+    /// Defines a symbol from `--defsym`. The value is an index into `Args::defsym`.
+    Defsym(usize),
+
+    /// A symbol that nothing defines, but which is referenced when producing a shared object. It's
+    /// left undefined in .dynsym for the dynamic loader to resolve.
+    Undefined,
+
+    /// Like `Undefined`, but all references to the symbol are weak, so it's emitted as a weak
+    /// symbol and it's fine for the dynamic loader not to find a definition.
+    UndefinedWeak,
 }
 
 pub(crate) enum FileSymbols<'data> {
     Internal(InternalSymbols),
     Object(ObjectSymbols<'data>),
     ArchiveEntry(ObjectSymbols<'data>),
+    Dynamic(ObjectSymbols<'data>),
 }
 
 pub(crate) struct InternalSymbols {
-    // TODO: Use this - when we implement dynamic linking
-    #[allow(dead_code)]
     pub(crate) dynamic_linker: Option<CString>,
     pub(crate) symbol_definitions: Vec<InternalSymDefInfo>,
     pub(crate) defined: Vec<GlobalSymbolId>,
@@ -89,14 +119,17 @@ pub(crate) struct ObjectSymbols<'data> {
 }
 
 enum FileSymbolReader<'data> {
-    Internal(InternalSymbolReader),
+    Internal(InternalSymbolReader<'data>),
     Object(ObjectSymbolReader<'data>),
     Dynamic(ObjectSymbolReader<'data>),
 }
 
-struct InternalSymbolReader {
+struct InternalSymbolReader<'data> {
     dynamic_linker: Option<CString>,
     symbol_definitions: Vec<InternalSymDefInfo>,
+    defsym: &'data [Defsym],
+    /// Whether we need to define symbols for dynamic linking related sections such as `.dynamic`.
+    needs_dynamic: bool,
 }
 
 struct ObjectSymbolReader<'data> {
@@ -106,7 +139,8 @@ struct ObjectSymbolReader<'data> {
 
 enum SymbolReader<'data> {
     Object(ObjectSymbolReader<'data>),
-    Internal(InternalSymbolReader),
+    Internal(InternalSymbolReader<'data>),
+    Dynamic(ObjectSymbolReader<'data>),
 }
 
 struct SymbolLoadOutputs<'data> {
@@ -118,6 +152,7 @@ impl<'data> SymbolDb<'data> {
     #[tracing::instrument(skip_all, name = "Build symbol DB")]
     pub(crate) fn build(
         inputs: &'data [InputBytes],
+        input_data: &'data InputData<'data>,
         args: &'data Args,
     ) -> Result<(Self, Vec<FileSymbols<'data>>)> {
         // Reserve IDs for our reserved symbols, plus symbol 0, which is never used, but allows us
@@ -125,26 +160,51 @@ impl<'data> SymbolDb<'data> {
         let symbols = vec![symbol::PLACEHOLDER; NUM_RESERVED_SYMBOL_IDS + 1];
         let mut symbol_names = Vec::new();
         symbol_names.resize_with(NUM_RESERVED_SYMBOL_IDS + 1, SymbolName::placeholder);
+        let num_dynamic_inputs = inputs
+            .iter()
+            .filter(|i| i.kind == FileKind::ElfDynamic)
+            .count();
         let mut index = Self {
             args,
             symbol_ids: Default::default(),
             symbols,
             symbol_names,
             alternate_definitions: AHashMap::new(),
+            num_dynamic_inputs,
+            version_script: &input_data.version_script,
+            entry: input_data.entry.as_deref(),
+            wrapped_symbols: args.wrap.iter().map(|name| name.as_bytes()).collect(),
+            dynamic_symbols: AHashSet::new(),
         };
+        let needs_dynamic = index.needs_dynamic();
         let readers = inputs
             .par_iter()
-            .map(|f| FileSymbolReader::new(f, args))
+            .map(|f| FileSymbolReader::new(f, args, needs_dynamic))
             .collect::<Result<Vec<FileSymbolReader>>>()?;
         let per_file_symbols = index.load_symbols(readers)?;
         Ok((index, per_file_symbols))
     }
 
+    /// Returns the name that an undefined reference to `name` should be resolved to due to
+    /// `--wrap`, or None if the reference isn't affected.
+    pub(crate) fn wrapped_reference_name(&self, name: &[u8]) -> Option<Vec<u8>> {
+        if self.wrapped_symbols.is_empty() {
+            return None;
+        }
+        if self.wrapped_symbols.contains(name) {
+            return Some([b"__wrap_", name].concat());
+        }
+        let real_name = name.strip_prefix(b"__real_")?;
+        self.wrapped_symbols
+            .contains(real_name)
+            .then(|| real_name.to_owned())
+    }
+
     fn load_symbols(
         &mut self,
         readers: Vec<FileSymbolReader<'data>>,
     ) -> Result<Vec<FileSymbols<'data>>> {
-        let symbol_per_file = read_symbols(readers, self.args)?;
+        let symbol_per_file = read_symbols(readers)?;
         self.populate_symbol_db(symbol_per_file)
     }
 
@@ -166,7 +226,7 @@ impl<'data> SymbolDb<'data> {
                 let defined = self.add_symbols(pending.pending_symbols, file_id)?;
                 Ok(match pending.reader {
                     SymbolReader::Object(state) => {
-                        if state.is_from_archive() {
+                        if state.is_lazy() {
                             FileSymbols::ArchiveEntry(state.symbols_defined(file_id))
                         } else {
                             FileSymbols::Object(state.symbols_defined(file_id))
@@ -175,6 +235,9 @@ impl<'data> SymbolDb<'data> {
                     SymbolReader::Internal(state) => {
                         FileSymbols::Internal(state.symbols_defined(defined, file_id))
                     }
+                    SymbolReader::Dynamic(state) => {
+                        FileSymbols::Dynamic(state.symbols_defined(file_id))
+                    }
                 })
             })
             .collect()
@@ -218,7 +281,8 @@ impl<'data> SymbolDb<'data> {
         }
     }
 
-    pub(crate) fn add_start_stop_symbol(
+    /// Adds a symbol that's owned by our internal file, such as a start/stop symbol.
+    pub(crate) fn add_internal_symbol(
         &mut self,
         symbol_name: &'data [u8],
         local_index: object::SymbolIndex,
@@ -238,6 +302,67 @@ impl<'data> SymbolDb<'data> {
         self.symbol_names[symbol_id.as_usize()]
     }
 
+    /// Returns whether `symbol_id` was defined with `--defsym`. Such definitions take precedence
+    /// over any definitions in input files.
+    pub(crate) fn is_defsym(&self, symbol_id: GlobalSymbolId) -> bool {
+        self.symbol(symbol_id).file_id == input_data::INTERNAL_FILE_ID
+            && self
+                .args
+                .defsym_named(self.symbol_name(symbol_id).bytes())
+                .is_some()
+    }
+
+    /// Returns whether `symbol_id` was defined with `--defsym` as an absolute value, either directly
+    /// or via other `--defsym` aliases.
+    pub(crate) fn is_absolute_defsym(&self, symbol_id: GlobalSymbolId) -> bool {
+        if self.symbol(symbol_id).file_id != input_data::INTERNAL_FILE_ID {
+            return false;
+        }
+        let mut name = self.symbol_name(symbol_id).bytes();
+        // Each step follows one alias, so more steps than there are definitions means that there's
+        // a cycle, which gets reported when we resolve the aliases.
+        for _ in 0..=self.args.defsym.len() {
+            let Some(defsym) = self.args.defsym_named(name) else {
+                return false;
+            };
+            match &defsym.value {
+                DefsymValue::Value(_) => return true,
+                DefsymValue::Symbol(target, _) => name = target.as_bytes(),
+            }
+        }
+        false
+    }
+
+    /// Returns whether `symbol_id` gets bound at runtime by the dynamic loader rather than by us.
+    pub(crate) fn is_dynamic(&self, symbol_id: GlobalSymbolId) -> bool {
+        self.dynamic_symbols.contains(&symbol_id)
+    }
+
+    /// Returns whether `sym` should be exported via .dynsym when we're producing a shared object.
+    pub(crate) fn is_exported(&self, sym: &crate::elf::Symbol) -> bool {
+        sym.is_definition()
+            && sym.scope() == object::SymbolScope::Dynamic
+            && sym
+                .name_bytes()
+                .is_ok_and(|name| self.version_script.is_global(name))
+    }
+
+    /// Returns whether references from within our shared object to the exported symbol `sym` need
+    /// to go via the dynamic loader, since an earlier definition, e.g. in the executable, can take
+    /// precedence over ours. That's not the case for protected symbols, nor if we were passed
+    /// `-Bsymbolic`. We also always bind ifuncs locally, since they're called via our own PLT.
+    pub(crate) fn is_interposable(&self, sym: &crate::elf::Symbol) -> bool {
+        const STV_PROTECTED: u8 = 3;
+        const STT_GNU_IFUNC: u8 = 10;
+        let object::SymbolFlags::Elf { st_info, st_other } = sym.flags() else {
+            return false;
+        };
+        !self.args.b_symbolic
+            && self.is_exported(sym)
+            && st_other & 0x3 != STV_PROTECTED
+            && st_info & 0xf != STT_GNU_IFUNC
+    }
+
     pub(crate) fn replace_symbol(&mut self, symbol_id: GlobalSymbolId, replacement: Symbol) {
         self.symbols[symbol_id.as_usize()] = replacement;
     }
@@ -245,18 +370,24 @@ impl<'data> SymbolDb<'data> {
     pub(crate) fn num_symbols(&self) -> usize {
         self.symbols.len()
     }
+
+    /// Returns whether the output file needs the sections used by the dynamic loader - `.dynamic`,
+    /// `.dynsym` etc. This is the case if we're producing relocatable output or if we're linking
+    /// against any shared objects.
+    pub(crate) fn needs_dynamic(&self) -> bool {
+        self.args.is_relocatable() || self.num_dynamic_inputs > 0
+    }
 }
 
 #[tracing::instrument(skip_all, name = "Read symbols")]
 fn read_symbols<'data>(
     readers: Vec<FileSymbolReader<'data>>,
-    args: &Args,
 ) -> Result<Vec<Vec<SymbolLoadOutputs<'data>>>, anyhow::Error> {
     let symbol_per_file = readers
         .into_par_iter()
         .map(|reader| {
             let filename = reader.filename();
-            load_symbols_from_file(reader, args)
+            load_symbols_from_file(reader)
                 .with_context(|| format!("Failed to load symbols from `{}`", filename.display()))
         })
         .collect::<Result<Vec<Vec<SymbolLoadOutputs>>>>()?;
@@ -265,11 +396,10 @@ fn read_symbols<'data>(
 
 fn load_symbols_from_file<'data>(
     reader: FileSymbolReader<'data>,
-    args: &Args,
 ) -> Result<Vec<SymbolLoadOutputs<'data>>> {
     Ok(match reader {
         FileSymbolReader::Object(s) => vec![s.load_symbols()?],
-        FileSymbolReader::Internal(s) => vec![s.load_symbols(args)?],
+        FileSymbolReader::Internal(s) => vec![s.load_symbols()?],
         FileSymbolReader::Dynamic(s) => vec![s.load_dynamic_symbols()?],
     })
 }
@@ -286,8 +416,13 @@ impl<'data> ObjectSymbolReader<'data> {
         })
     }
 
-    fn is_from_archive(&self) -> bool {
-        self.input.entry_filename.is_some()
+    /// Returns whether this object should only be loaded if it defines a symbol that's referenced.
+    /// This is the case for archive members, unless they're inside a `--whole-archive` region, and
+    /// for objects between `--start-lib` and `--end-lib`.
+    fn is_lazy(&self) -> bool {
+        let modifiers = self.input.file.modifiers;
+        modifiers.archive_semantics
+            || (self.input.entry_filename.is_some() && !modifiers.whole_archive)
     }
 
     fn filename(&self) -> &'data Path {
@@ -336,7 +471,7 @@ impl<'data> ObjectSymbolReader<'data> {
         }
         Ok(SymbolLoadOutputs {
             pending_symbols: symbols,
-            reader: SymbolReader::Object(self),
+            reader: SymbolReader::Dynamic(self),
         })
     }
 
@@ -349,13 +484,12 @@ impl<'data> ObjectSymbolReader<'data> {
     }
 }
 
-impl InternalSymbolReader {
-    fn load_symbols(mut self, args: &Args) -> Result<SymbolLoadOutputs<'static>> {
+impl<'data> InternalSymbolReader<'data> {
+    fn load_symbols(mut self) -> Result<SymbolLoadOutputs<'data>> {
         let mut symbols = Vec::new();
         for section_id in output_section_id::built_in_section_ids() {
-            // If we're not producing a relocatable output, then don't define any symbols for the
-            // .dynamic section.
-            if section_id == output_section_id::DYNAMIC && !args.is_relocatable() {
+            // If we're not going to have a .dynamic section, then don't define any symbols for it.
+            if section_id == output_section_id::DYNAMIC && !self.needs_dynamic {
                 continue;
             }
             let def = section_id.built_in_details();
@@ -378,6 +512,15 @@ impl InternalSymbolReader {
                     .push(InternalSymDefInfo::SectionEnd(section_id));
             }
         }
+        for (index, defsym) in self.defsym.iter().enumerate() {
+            symbols.push(PendingSymbol::new(
+                input_data::INTERNAL_FILE_ID,
+                object::SymbolIndex(self.symbol_definitions.len()),
+                defsym.name.as_bytes(),
+            ));
+            self.symbol_definitions
+                .push(InternalSymDefInfo::Defsym(index));
+        }
         Ok(SymbolLoadOutputs {
             pending_symbols: symbols,
             reader: SymbolReader::Internal(self),
@@ -421,18 +564,13 @@ impl TryFrom<usize> for GlobalSymbolId {
 }
 
 impl<'data> FileSymbolReader<'data> {
-    fn new(input: &'data InputBytes, args: &'data Args) -> Result<Self> {
+    fn new(input: &'data InputBytes, args: &'data Args, needs_dynamic: bool) -> Result<Self> {
         Ok(match input.kind {
             FileKind::ElfObject | FileKind::Archive => {
                 Self::Object(ObjectSymbolReader::new(input)?)
             }
-            FileKind::Internal => Self::Internal(InternalSymbolReader::new(args)?),
-            FileKind::ElfDynamic => {
-                if true {
-                    bail!("Dynamic linking is not yet implemented");
-                }
-                Self::Dynamic(ObjectSymbolReader::new(input)?)
-            }
+            FileKind::Internal => Self::Internal(InternalSymbolReader::new(args, needs_dynamic)?),
+            FileKind::ElfDynamic => Self::Dynamic(ObjectSymbolReader::new(input)?),
             FileKind::Text => unreachable!("Should have been handled earlier"),
         })
     }
@@ -446,8 +584,8 @@ impl<'data> FileSymbolReader<'data> {
     }
 }
 
-impl InternalSymbolReader {
-    fn new(args: &Args) -> Result<Self> {
+impl<'data> InternalSymbolReader<'data> {
+    fn new(args: &'data Args, needs_dynamic: bool) -> Result<Self> {
         Ok(Self {
             dynamic_linker: args
                 .dynamic_linker
@@ -455,6 +593,8 @@ impl InternalSymbolReader {
                 .map(|p| CString::new(p.as_os_str().as_encoded_bytes()))
                 .transpose()?,
             symbol_definitions: Default::default(),
+            defsym: &args.defsym,
+            needs_dynamic,
         })
     }
 
@@ -476,10 +616,16 @@ impl<'data> std::fmt::Display for ObjectSymbols<'data> {
 }
 
 impl InternalSymDefInfo {
-    pub(crate) fn section_id(self) -> OutputSectionId {
+    /// Returns the section that the symbol is defined relative to. None for symbols defined with
+    /// `--defsym`, since their value doesn't depend on any particular section of ours, and for
+    /// undefined symbols.
+    pub(crate) fn section_id(self) -> Option<OutputSectionId> {
         match self {
-            InternalSymDefInfo::SectionStart(i) => i,
-            InternalSymDefInfo::SectionEnd(i) => i,
+            InternalSymDefInfo::SectionStart(i) => Some(i),
+            InternalSymDefInfo::SectionEnd(i) => Some(i),
+            InternalSymDefInfo::Defsym(_)
+            | InternalSymDefInfo::Undefined
+            | InternalSymDefInfo::UndefinedWeak => None,
         }
     }
 }
@@ -0,0 +1,230 @@
+//! Parsing of version scripts, which control which symbols a shared object exports. We don't
+//! currently support symbol versioning, so version names and dependencies are parsed, but ignored.
+//! All we take from the script is which symbols are global and which are local.
+
+use crate::error::Result;
+use anyhow::anyhow;
+use anyhow::bail;
+use anyhow::Context;
+use std::path::Path;
+
+#[derive(Default, Debug)]
+pub(crate) struct VersionScript {
+    global: Vec<Pattern>,
+    local: Vec<Pattern>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Pattern {
+    Exact(Box<[u8]>),
+    /// A pattern containing '*' and/or '?'.
+    Glob(Box<[u8]>),
+}
+
+impl VersionScript {
+    pub(crate) fn from_file(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read version script `{}`", path.display()))?;
+        Self::parse(&text)
+            .with_context(|| format!("Failed to parse version script `{}`", path.display()))
+    }
+
+    fn parse(text: &str) -> Result<Self> {
+        let mut script = VersionScript::default();
+        let mut tokens = Tokeniser { text };
+        while let Some(token) = tokens.next() {
+            // Blocks are either anonymous or preceded by a version name.
+            if token != "{" {
+                tokens.expect("{")?;
+            }
+            script.parse_block(&mut tokens)?;
+            // After the closing brace, there may be the names of versions that this version
+            // depends on.
+            loop {
+                match tokens.next() {
+                    Some(";") => break,
+                    Some(_) => {}
+                    None => bail!("Got end of script, expected ';'"),
+                }
+            }
+        }
+        Ok(script)
+    }
+
+    fn parse_block(&mut self, tokens: &mut Tokeniser) -> Result {
+        let mut is_global = true;
+        loop {
+            let token = tokens
+                .next()
+                .ok_or_else(|| anyhow!("Got end of script, expected '}}'"))?;
+            match token {
+                "}" => return Ok(()),
+                "global:" => is_global = true,
+                "local:" => is_global = false,
+                "extern" => bail!("`extern` blocks in version scripts are not supported"),
+                _ => {
+                    tokens.expect(";")?;
+                    let pattern = Pattern::new(token);
+                    if is_global {
+                        self.global.push(pattern);
+                    } else {
+                        self.local.push(pattern);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns whether the symbol named `name` should be exported. Exact matches take precedence
+    /// over glob patterns. Symbols that don't match anything are exported.
+    pub(crate) fn is_global(&self, name: &[u8]) -> bool {
+        let exact = |patterns: &[Pattern]| {
+            patterns
+                .iter()
+                .any(|p| matches!(p, Pattern::Exact(exact) if **exact == *name))
+        };
+        let glob = |patterns: &[Pattern]| {
+            patterns
+                .iter()
+                .any(|p| matches!(p, Pattern::Glob(glob) if glob_matches(glob, name)))
+        };
+        if exact(&self.global) {
+            return true;
+        }
+        if exact(&self.local) {
+            return false;
+        }
+        if glob(&self.global) {
+            return true;
+        }
+        !glob(&self.local)
+    }
+}
+
+impl Pattern {
+    fn new(token: &str) -> Self {
+        if let Some(quoted) = token
+            .strip_prefix('"')
+            .and_then(|rest| rest.strip_suffix('"'))
+        {
+            return Pattern::Exact(Box::from(quoted.as_bytes()));
+        }
+        if token.contains(['*', '?']) {
+            Pattern::Glob(Box::from(token.as_bytes()))
+        } else {
+            Pattern::Exact(Box::from(token.as_bytes()))
+        }
+    }
+}
+
+/// Returns whether `name` matches `pattern`, where '*' matches any sequence of bytes and '?' matches
+/// any single byte.
+pub(crate) fn glob_matches(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|skip| glob_matches(rest, &name[skip..])),
+        Some((&p, rest)) => match name.split_first() {
+            Some((&n, name_rest)) => (p == b'?' || p == n) && glob_matches(rest, name_rest),
+            None => false,
+        },
+    }
+}
+
+struct Tokeniser<'a> {
+    text: &'a str,
+}
+
+impl<'a> Tokeniser<'a> {
+    fn next(&mut self) -> Option<&'a str> {
+        loop {
+            self.text = self.text.trim_start();
+            if let Some(rest) = self.text.strip_prefix("/*") {
+                self.text = rest.find("*/").map(|end| &rest[end + 2..]).unwrap_or("");
+                continue;
+            }
+            if let Some(rest) = self.text.strip_prefix('#') {
+                self.text = rest.find('\n').map(|end| &rest[end..]).unwrap_or("");
+                continue;
+            }
+            if self.text.is_empty() {
+                return None;
+            }
+            let len = if let Some(label) = ["global:", "local:"]
+                .into_iter()
+                .find(|label| self.text.starts_with(label))
+            {
+                label.len()
+            } else if self.text.starts_with('"') {
+                self.text[1..]
+                    .find('"')
+                    .map(|end| end + 2)
+                    .unwrap_or(self.text.len())
+            } else {
+                self.text
+                    .find(|ch: char| ch.is_whitespace() || matches!(ch, '{' | '}' | ';'))
+                    .unwrap_or(self.text.len())
+                    .max(1)
+            };
+            let token = &self.text[..len];
+            self.text = &self.text[len..];
+            return Some(token);
+        }
+    }
+
+    fn expect(&mut self, expected: &str) -> Result {
+        let token = self
+            .next()
+            .ok_or_else(|| anyhow!("Expected token '{expected}', got end of input"))?;
+        if token != expected {
+            bail!("Expected token '{expected}', got '{token}'");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rustc_proc_macro_script() {
+        let script = VersionScript::parse(
+            "{\n  global:\n    __rustc_proc_macro_decls_9f0fe6c5a3a3b1a1__;\n  local:\n    *;\n};\n",
+        )
+        .unwrap();
+        assert!(script.is_global(b"__rustc_proc_macro_decls_9f0fe6c5a3a3b1a1__"));
+        assert!(!script.is_global(b"rust_metadata_foo"));
+        assert!(!script.is_global(b"main"));
+    }
+
+    #[test]
+    fn test_named_versions() {
+        let script = VersionScript::parse(
+            r#"
+            # A comment
+            VERS_1.0 {
+                global: foo*; "bar"; /* another comment */
+                local: foo_internal;
+            };
+            VERS_2.0 { baz; } VERS_1.0;
+            "#,
+        )
+        .unwrap();
+        assert!(script.is_global(b"foo"));
+        assert!(script.is_global(b"foo_public"));
+        assert!(!script.is_global(b"foo_internal"));
+        assert!(script.is_global(b"bar"));
+        assert!(script.is_global(b"baz"));
+        // Symbols that aren't mentioned at all are exported.
+        assert!(script.is_global(b"other"));
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches(b"*", b""));
+        assert!(glob_matches(b"a?c", b"abc"));
+        assert!(glob_matches(b"a*c*", b"abbbcdd"));
+        assert!(!glob_matches(b"a*c", b"abcd"));
+        assert!(!glob_matches(b"abc", b"ab"));
+    }
+}
@@ -19,6 +19,7 @@ use anyhow::Context;
 use object::Object;
 use object::ObjectSection;
 use object::ObjectSymbol;
+use object::ObjectSymbolTable;
 use std::fmt::Display;
 use std::path::Path;
 use std::path::PathBuf;
@@ -321,23 +322,29 @@ impl<'a> Program<'a> {
         self.assertions
             .check(&self.link_output)
             .context("Output binary assertions failed")?;
-        let mut child = Command::new(&self.link_output.binary).spawn()?;
-        let status = match child.wait_timeout(std::time::Duration::from_millis(500))? {
-            Some(s) => s,
-            None => {
-                child.kill()?;
-                bail!("Binary ran for too long");
-            }
-        };
-        let exit_code = status
-            .code()
-            .ok_or_else(|| anyhow!("Binary exited with signal"))?;
-        if exit_code != 42 {
-            bail!("Binary exited with unexpected exit code {exit_code}");
-        }
+        run_binary(&self.link_output.binary)
+    }
+}
 
-        Ok(())
+/// Runs `binary` and checks that it exits with 42, which is what all our test programs do if they
+/// pass.
+fn run_binary(binary: &Path) -> Result {
+    let mut child = Command::new(binary).spawn()?;
+    let status = match child.wait_timeout(std::time::Duration::from_millis(500))? {
+        Some(s) => s,
+        None => {
+            child.kill()?;
+            bail!("Binary ran for too long");
+        }
+    };
+    let exit_code = status
+        .code()
+        .ok_or_else(|| anyhow!("Binary exited with signal"))?;
+    if exit_code != 42 {
+        bail!("Binary exited with unexpected exit code {exit_code}");
     }
+
+    Ok(())
 }
 
 impl<'a> Display for Program<'a> {
@@ -351,6 +358,155 @@ impl<'a> Display for Program<'a> {
     }
 }
 
+/// A link that we only do with wild. Used for testing things like diagnostics and reports, where
+/// there's no equivalent output from other linkers to compare against.
+struct WildLink {
+    command: Command,
+    binary: PathBuf,
+}
+
+struct WildLinkOutput {
+    binary: PathBuf,
+    status: std::process::ExitStatus,
+    stdout: String,
+    stderr: String,
+    command: String,
+}
+
+/// The dynamic loader that our dynamically linked test executables request.
+const DYNAMIC_LINKER: &str = "/lib64/ld-linux-x86-64.so.2";
+
+/// The system libc, for tests that link against shared objects.
+const LIBC: &str = "/lib/x86_64-linux-gnu/libc.so.6";
+
+impl WildLink {
+    fn new(name: &str) -> Self {
+        let mut link = Self::new_dynamic(name);
+        link.arg("-static");
+        link
+    }
+
+    /// Like `new`, but without `-static`, for links that produce or use shared objects.
+    fn new_dynamic(name: &str) -> Self {
+        let binary = build_dir().join(format!("{name}.wild-only"));
+        let mut command = Command::new(wild_path());
+        command.arg("--gc-sections");
+        command.arg("-o").arg(&binary);
+        Self { command, binary }
+    }
+
+    fn arg(&mut self, arg: impl AsRef<std::ffi::OsStr>) -> &mut Self {
+        self.command.arg(arg);
+        self
+    }
+
+    fn args(&mut self, args: &[&str]) -> &mut Self {
+        self.command.args(args);
+        self
+    }
+
+    /// Adds an input built from `source` with the default compilation variant.
+    fn input(&mut self, source: &str, input_type: InputType) -> Result<&mut Self> {
+        self.input_with_comp_args(source, input_type, ArgumentSet::default_for_compiling())
+    }
+
+    fn input_with_comp_args(
+        &mut self,
+        source: &str,
+        input_type: InputType,
+        compiler_args: ArgumentSet,
+    ) -> Result<&mut Self> {
+        let variant = Variant {
+            input_type,
+            compilation: CompilationVariant {
+                variant_num: 0,
+                compiler_args,
+            },
+            linker_args: ArgumentSet::default_for_linking(),
+        };
+        self.command.arg(build_linker_input(
+            source,
+            &variant,
+            FilePlacement::Primary,
+        )?);
+        Ok(self)
+    }
+
+    fn link(&mut self) -> Result<WildLinkOutput> {
+        let command = format!("cargo run -- {}", display_args(&self.command));
+        let output = self.command.output()?;
+        Ok(WildLinkOutput {
+            binary: self.binary.clone(),
+            status: output.status,
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            command,
+        })
+    }
+}
+
+impl WildLinkOutput {
+    /// Checks that the link succeeded, then runs the output.
+    fn run(&self) -> Result {
+        self.expect_success()?;
+        run_binary(&self.binary).with_context(|| format!("Relink with:\n{}", self.command))
+    }
+
+    fn expect_success(&self) -> Result {
+        if !self.status.success() {
+            bail!(
+                "Linker failed. Relink with:\n{}\n{}",
+                self.command,
+                self.stderr
+            );
+        }
+        Ok(())
+    }
+
+    fn expect_failure(&self) -> Result {
+        if self.status.success() {
+            bail!(
+                "Link unexpectedly succeeded. Relink with:\n{}",
+                self.command
+            );
+        }
+        Ok(())
+    }
+
+    fn expect_stdout(&self, expected: &str) -> Result {
+        if !self.stdout.contains(expected) {
+            bail!(
+                "Expected linker stdout to contain:\n{expected}\nGot:\n{}Relink with:\n{}",
+                self.stdout,
+                self.command
+            );
+        }
+        Ok(())
+    }
+
+    fn expect_stderr(&self, expected: &str) -> Result {
+        if !self.stderr.contains(expected) {
+            bail!(
+                "Expected linker stderr to contain:\n{expected}\nGot:\n{}Relink with:\n{}",
+                self.stderr,
+                self.command
+            );
+        }
+        Ok(())
+    }
+}
+
+fn display_args(command: &Command) -> String {
+    command
+        .get_args()
+        .map(|a| a.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Held while building linker inputs, since tests run in parallel and may need the same inputs.
+static BUILD_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
 /// Creates a linker input from a source file. This will be either an object file or an archive.
 fn build_linker_input(
     filename: &str,
@@ -360,6 +516,9 @@ fn build_linker_input(
     if filename.ends_with(".a") {
         return Ok(src_path(filename));
     }
+    // If another test panicked while holding the lock, whatever it was building will just get
+    // rebuilt, so there's no harm in continuing.
+    let _lock = BUILD_LOCK.lock().unwrap_or_else(|e| e.into_inner());
     let obj_path = build_obj(filename, variant, placement)?;
 
     match variant.input_type {
@@ -797,11 +956,38 @@ fn integration_test() -> Result {
                 "exit.c",
             ],
         )?,
+        ProgramInputs::new(
+            "string_merging_wide",
+            &[
+                "string_merging_wide.c",
+                "string_merging_wide1.s",
+                "string_merging_wide2.s",
+                "exit.c",
+            ],
+        )?,
+        ProgramInputs::new(
+            "string_merging_section_refs",
+            &[
+                "string_merging_section_refs.c",
+                "string_merging_section_refs1.s",
+                "exit.c",
+            ],
+        )?,
         ProgramInputs::new(
             "comments",
             &["comments.c", "comments0.c", "comments1.c", "exit.c"],
         )?,
         ProgramInputs::new("eh_frame", &["eh_frame.c", "eh_frame_end.c", "exit.c"])?,
+        ProgramInputs::new("comdat", &["comdat.s", "comdat1.s", "exit.c"])?,
+        ProgramInputs::new("entry", &["entry.c", "exit.c"])?,
+        ProgramInputs::new("wrap", &["wrap.c", "wrap1.c", "wrap2.c", "exit.c"])?,
+        ProgramInputs::new("defsym", &["defsym.c", "exit.c"])?,
+        ProgramInputs::new("undefined", &["undefined.c", "undefined1.c", "exit.c"])?,
+        ProgramInputs::new("gc_sections", &["gc_sections.c", "exit.c"])?,
+        ProgramInputs::new(
+            "whole_archive",
+            &["whole_archive.c", "whole_archive1.c", "exit.c"],
+        )?,
         ProgramInputs::new(
             "pie",
             &[
@@ -857,3 +1043,519 @@ fn integration_test() -> Result {
 
     Ok(())
 }
+
+/// By default, wild does the link in a forked subprocess. Checks that a failed link is still
+/// reported via our exit status and that a successful link doesn't leave our caller waiting for
+/// the subprocess to close our stdout and stderr.
+#[test]
+fn fork_exit_status() -> Result {
+    // Without exit.c, `exit_syscall` is undefined.
+    let failed = WildLink::new("fork-failure")
+        .input("trivial.c", InputType::Object)?
+        .link()?;
+    failed.expect_failure()?;
+    failed.expect_stderr("undefined symbol: exit_syscall")?;
+
+    WildLink::new("fork-success")
+        .input("trivial.c", InputType::Object)?
+        .input("exit.c", InputType::Object)?
+        .link()?
+        .run()
+}
+
+/// Checks that `--defsym` definitions that refer to each other in a cycle are rejected.
+#[test]
+fn defsym_cycle() -> Result {
+    let output = WildLink::new("defsym-cycle")
+        .arg("--defsym=first=second")
+        .arg("--defsym=second=first+1")
+        .input("trivial.c", InputType::Object)?
+        .input("exit.c", InputType::Object)?
+        .link()?;
+    output.expect_failure()?;
+    output.expect_stderr("refers back to itself")
+}
+
+/// Checks that two strong definitions of a symbol are an error unless multiple definitions are
+/// allowed, in which case the first definition is used.
+#[test]
+fn multiple_definition() -> Result {
+    let link = |name: &str, extra_args: &[&str]| {
+        WildLink::new(name)
+            .args(extra_args)
+            .input("multiple_definition.c", InputType::Object)?
+            .input("multiple_definition1.c", InputType::Object)?
+            .input("exit.c", InputType::Object)?
+            .link()
+    };
+    let rejected = link("multiple-definition", &[])?;
+    rejected.expect_failure()?;
+    rejected.expect_stderr("multiple definition of `value`; first defined in")?;
+
+    link(
+        "allow-multiple-definition",
+        &["--allow-multiple-definition"],
+    )?
+    .run()?;
+    link("muldefs", &["-z", "muldefs"])?.run()
+}
+
+/// Checks that objects between `--start-lib` and `--end-lib` are only loaded if they define a
+/// symbol that's referenced. exit.c is needed for `exit_syscall`, whereas multiple_definition1.c
+/// only defines `value`, which is already defined, so loading it would be an error.
+#[test]
+fn start_lib() -> Result {
+    WildLink::new("start-lib")
+        .input("multiple_definition.c", InputType::Object)?
+        .arg("--start-lib")
+        .input("exit.c", InputType::Object)?
+        .input("multiple_definition1.c", InputType::Object)?
+        .arg("--end-lib")
+        .link()?
+        .run()
+}
+
+/// Checks that `--why-live` follows the chain of references from a GC root through each section to
+/// the one that defines the symbol and that `-t` says why an archive member was loaded.
+#[test]
+fn why_live() -> Result {
+    let output = WildLink::new("why-live")
+        .arg("--why-live=leaf")
+        .arg("--why-live=live_string")
+        .arg("-t")
+        .input_with_comp_args(
+            "why_live.c",
+            InputType::Object,
+            ArgumentSet::parse("sections:-ffunction-sections")?,
+        )?
+        .input("why_live1.s", InputType::Object)?
+        .input("exit.c", InputType::Archive)?
+        .link()?;
+    output.run()?;
+    for expected in [
+        "`leaf` is live because:",
+        ":(.text.leaf) is referenced via `leaf` by ",
+        ":(.text.middle) is referenced via `middle` by ",
+        ":(.text._start) is a GC root",
+        "`live_string` is live because:",
+        ":(.rodata.str1.1) is referenced via `live_string` by ",
+        "exit.0-default.a @ exit.0-default.o (needed for `exit_syscall`, referenced by ",
+    ] {
+        output.expect_stdout(expected)?;
+    }
+    Ok(())
+}
+
+/// Checks that `--trace-symbol` reports which definition we selected and why, both when choosing a
+/// strong definition over a weak one and when choosing the largest common definition.
+#[test]
+fn trace_symbol() -> Result {
+    let weak_and_strong = WildLink::new("trace-symbol-weak")
+        .arg("--trace-symbol=value")
+        .input("trace_symbol.c", InputType::Object)?
+        .input("multiple_definition1.c", InputType::Object)?
+        .input("exit.c", InputType::Object)?
+        .link()?;
+    weak_and_strong.run()?;
+    for expected in [
+        "trace_symbol.0-default.o: weak definition of value",
+        "multiple_definition1.0-default.o: definition of value",
+        "value: 2 candidate definitions: ",
+        "multiple_definition1.0-default.o because it's the first strong definition",
+    ] {
+        weak_and_strong.expect_stdout(expected)?;
+    }
+
+    let common = ArgumentSet::parse("common:-fcommon")?;
+    let commons = WildLink::new("trace-symbol-common")
+        .arg("--trace-symbol=data")
+        .input_with_comp_args("common_section.c", InputType::Object, common.clone())?
+        // The larger definition comes first, so we need to keep it rather than switching to the
+        // smaller one that comes later.
+        .input_with_comp_args("common_section1.c", InputType::Object, common.clone())?
+        .input_with_comp_args("common_section0.c", InputType::Object, common)?
+        .input("exit.c", InputType::Object)?
+        .link()?;
+    commons.run()?;
+    for expected in [
+        "common_section0.0-common.o: common definition of data",
+        "common_section1.0-common.o: common definition of data",
+        "(common, size 40)",
+        "(common, size 4000)",
+        "common_section1.0-common.o because there's no strong definition and it's the largest \
+         common definition",
+    ] {
+        commons.expect_stdout(expected)?;
+    }
+    Ok(())
+}
+
+/// Checks that the map file written by `-Map` lists input sections, the symbols that they define,
+/// merged strings and .eh_frame data, and that contributions to .bss don't claim a file offset.
+#[test]
+fn map_file() -> Result {
+    let map_path = build_dir().join("map_file.map");
+    WildLink::new("map_file")
+        .arg(format!("-Map={}", map_path.display()))
+        .input_with_comp_args(
+            "map_file.c",
+            InputType::Object,
+            ArgumentSet::parse("merge-constants:-fmerge-constants")?,
+        )?
+        .input("exit.c", InputType::Object)?
+        .link()?
+        .run()?;
+    let map = std::fs::read_to_string(&map_path)?;
+    for expected in [
+        "map_file.0-merge-constants.o:(.text)",
+        "get_message",
+        "<merged strings>",
+        "exit.0-default.o:(.eh_frame)",
+    ] {
+        if !map.contains(expected) {
+            bail!("Expected map file to contain `{expected}`. Got:\n{map}");
+        }
+    }
+    let bss = map
+        .lines()
+        .find(|line| line.ends_with(":(.bss)"))
+        .with_context(|| format!("Missing .bss contribution in map file:\n{map}"))?;
+    // Address, size, alignment and description, but no file offset.
+    if bss.split_whitespace().count() != 4 {
+        bail!("Expected no file offset for .bss contribution `{bss}`");
+    }
+    Ok(())
+}
+
+/// Checks that `--print-gc-sections` reports both discarded sections and discarded strings.
+#[test]
+fn print_gc_sections() -> Result {
+    let output = WildLink::new("print_gc_sections")
+        .arg("--print-gc-sections")
+        .input_with_comp_args(
+            "gc_sections.c",
+            InputType::Object,
+            ArgumentSet::parse("sections:-ffunction-sections -fmerge-constants")?,
+        )?
+        .input("exit.c", InputType::Object)?
+        .link()?;
+    output.run()?;
+    output.expect_stderr("removing unused section '.text.unreferenced' in file")?;
+    output.expect_stderr("removing unused section '.rodata.str1.1' in file")?;
+    Ok(())
+}
+
+/// Checks that when strings are referenced via the section symbol plus an addend, each referenced
+/// string is kept and a string referenced only from a discarded function is dropped. Includes
+/// PC-relative references, whose addend is negative for the first string in a section.
+#[test]
+fn string_merging_section_refs() -> Result {
+    let output = WildLink::new("string_merging_section_refs")
+        .arg("--print-gc-sections")
+        .input_with_comp_args(
+            "string_merging_section_refs.c",
+            InputType::Object,
+            ArgumentSet::parse("merge-constants:-ffunction-sections -fmerge-constants")?,
+        )?
+        .input("string_merging_section_refs1.s", InputType::Object)?
+        .input("exit.c", InputType::Object)?
+        .link()?;
+    output.run()?;
+    output.expect_stderr("removing unused strings from section '.rodata.str1.1' in file")?;
+    let binary = std::fs::read(&output.binary)?;
+    if binary
+        .windows(b"Discarded".len())
+        .any(|w| w == b"Discarded")
+    {
+        bail!("String referenced only from a discarded function was kept");
+    }
+    Ok(())
+}
+
+/// Checks that when producing a shared object, references to symbols that nothing defines are left
+/// undefined in .dynsym for the dynamic loader to resolve, rather than being reported as errors.
+/// Symbols that are only referenced weakly should be weak in .dynsym.
+#[test]
+fn shared_undefined() -> Result {
+    let shared = WildLink::new_dynamic("libshared_undefined.so")
+        .arg("-shared")
+        .input_with_comp_args(
+            "shared_undefined.c",
+            InputType::Object,
+            ArgumentSet::parse("pic:-fPIC")?,
+        )?
+        .link()?;
+    shared.expect_success()?;
+    let bytes = std::fs::read(&shared.binary)?;
+    let obj = object::File::parse(bytes.as_slice())?;
+    for (name, weak) in [
+        ("strlen", false),
+        ("stdout", false),
+        ("not_defined_anywhere", true),
+    ] {
+        if !obj
+            .dynamic_symbols()
+            .any(|sym| sym.is_undefined() && sym.is_weak() == weak && sym.name().ok() == Some(name))
+        {
+            let binding = if weak { "weak" } else { "global" };
+            bail!("Expected `{name}` to be an undefined {binding} symbol in .dynsym");
+        }
+    }
+
+    WildLink::new_dynamic("shared_undefined")
+        .args(&["-dynamic-linker", DYNAMIC_LINKER])
+        .input("shared_undefined_main.c", InputType::Object)?
+        .input("exit.c", InputType::Object)?
+        .arg(&shared.binary)
+        .arg(LIBC)
+        .link()?
+        .run()
+}
+
+/// Checks that references from within a shared object to its own exported symbols go via dynamic
+/// relocations, so that definitions in a shared object that's loaded earlier take precedence, and
+/// that `-Bsymbolic` binds those references directly instead.
+#[test]
+fn shared_exports_are_interposable() -> Result {
+    let pic = ArgumentSet::parse("pic:-fPIC")?;
+    let first = WildLink::new_dynamic("libshared_interpose_first.so")
+        .arg("-shared")
+        .input_with_comp_args("shared_interpose_first.c", InputType::Object, pic.clone())?
+        .link()?;
+    first.expect_success()?;
+    let shared = WildLink::new_dynamic("libshared_interpose.so")
+        .arg("-shared")
+        .input_with_comp_args("shared_interpose.c", InputType::Object, pic.clone())?
+        .link()?;
+    shared.expect_success()?;
+    let symbolic = WildLink::new_dynamic("libshared_interpose_symbolic.so")
+        .args(&["-shared", "-Bsymbolic"])
+        .input_with_comp_args("shared_interpose.c", InputType::Object, pic)?
+        .link()?;
+    symbolic.expect_success()?;
+
+    for name in ["interposed_value", "interposed_function"] {
+        if !dynamic_relocation_symbols(&shared.binary)?.contains(&name.to_owned()) {
+            bail!("Expected a dynamic relocation against `{name}`");
+        }
+        if dynamic_relocation_symbols(&symbolic.binary)?.contains(&name.to_owned()) {
+            bail!("Expected no dynamic relocation against `{name}` with -Bsymbolic");
+        }
+    }
+
+    WildLink::new_dynamic("shared_exports_are_interposable")
+        .args(&["-dynamic-linker", DYNAMIC_LINKER])
+        .input("shared_interpose_main.c", InputType::Object)?
+        .input("exit.c", InputType::Object)?
+        .arg(&first.binary)
+        .arg(&shared.binary)
+        .link()?
+        .run()
+}
+
+/// Checks that when a non-PIE executable takes the address of a function from a shared object, the
+/// executable's PLT entry becomes the function's address everywhere, including within the shared
+/// object.
+#[test]
+fn canonical_plt_address() -> Result {
+    let shared = WildLink::new_dynamic("libcanonical_plt.so")
+        .arg("-shared")
+        .input_with_comp_args(
+            "canonical_plt_lib.c",
+            InputType::Object,
+            ArgumentSet::parse("pic:-fPIC")?,
+        )?
+        .link()?;
+    shared.expect_success()?;
+
+    WildLink::new_dynamic("canonical_plt")
+        .args(&["-dynamic-linker", DYNAMIC_LINKER])
+        .input_with_comp_args(
+            "canonical_plt.c",
+            InputType::Object,
+            ArgumentSet::parse("nopie:-fno-pie")?,
+        )?
+        .input("exit.c", InputType::Object)?
+        .arg(&shared.binary)
+        .link()?
+        .run()
+}
+
+/// Checks that a shared object can access its own TLS variables with the general dynamic model,
+/// both when the variables can be interposed and with `-Bsymbolic`, where we fill in the offsets
+/// ourselves.
+#[test]
+fn shared_tls_general_dynamic() -> Result {
+    for (name, extra_args) in [
+        ("libshared_tls.so", &[][..]),
+        ("libshared_tls_symbolic.so", &["-Bsymbolic"][..]),
+    ] {
+        let shared = WildLink::new_dynamic(name)
+            .arg("-shared")
+            .args(extra_args)
+            .input_with_comp_args(
+                "shared_tls.c",
+                InputType::Object,
+                ArgumentSet::parse("pic:-fPIC")?,
+            )?
+            .link()?;
+        shared.expect_success()?;
+
+        WildLink::new_dynamic(&format!("shared_tls_main-{name}"))
+            .args(&["-dynamic-linker", DYNAMIC_LINKER])
+            .input("shared_tls_main.c", InputType::Object)?
+            .input("exit.c", InputType::Object)?
+            .arg(&shared.binary)
+            .arg(LIBC)
+            .link()?
+            .run()?;
+    }
+    Ok(())
+}
+
+/// Checks that an executable can use what shared objects define: functions, which we call via the
+/// PLT, a data object and a TLS variable. Code that isn't position-independent references the data
+/// object directly, so we need to copy it with a COPY relocation and accesses the TLS variable with
+/// the initial-exec model. Position-independent code uses the GOT and the general dynamic model.
+#[test]
+fn dynamic_imports() -> Result {
+    let lib = WildLink::new_dynamic("libdynamic_imports.so")
+        .arg("-shared")
+        .input_with_comp_args(
+            "dynamic_imports_lib.c",
+            InputType::Object,
+            ArgumentSet::parse("pic:-fPIC")?,
+        )?
+        .link()?;
+    lib.expect_success()?;
+
+    for compiler_args in [
+        ArgumentSet::parse("nopic:-fno-pic")?,
+        ArgumentSet::parse("pic:-fPIC")?,
+    ] {
+        let expect_copy = compiler_args.name == "nopic";
+        let exe = WildLink::new_dynamic(&format!("dynamic_imports-{}", compiler_args.name))
+            .args(&["-dynamic-linker", DYNAMIC_LINKER])
+            .input_with_comp_args("dynamic_imports.c", InputType::Object, compiler_args)?
+            .input("exit.c", InputType::Object)?
+            .arg(&lib.binary)
+            .arg(LIBC)
+            // Provides __tls_get_addr for the general dynamic model.
+            .arg(DYNAMIC_LINKER)
+            .link()?;
+        exe.expect_success()?;
+        if has_dynamic_relocation(&exe.binary, object::elf::R_X86_64_COPY)? != expect_copy {
+            bail!("Expected COPY relocation: {expect_copy}");
+        }
+        exe.run()?;
+    }
+    Ok(())
+}
+
+/// Returns whether `path` has a dynamic relocation of type `r_type`.
+fn has_dynamic_relocation(path: &Path, r_type: u32) -> Result<bool> {
+    let bytes = std::fs::read(path)?;
+    let obj = object::File::parse(bytes.as_slice())?;
+    Ok(obj.dynamic_relocations().is_some_and(|mut relocations| {
+        relocations.any(|(_, rel)| {
+            matches!(rel.flags(), object::RelocationFlags::Elf { r_type: t } if t == r_type)
+        })
+    }))
+}
+
+/// Links a shared object the way rustc links a proc-macro crate: against another shared object,
+/// with `-z relro -z now` and a version script that limits what gets exported.
+#[test]
+fn proc_macro_style_shared_object() -> Result {
+    let pic = ArgumentSet::parse("pic:-fPIC")?;
+    let shared = WildLink::new_dynamic("libproc_macro.so")
+        .args(&["-shared", "-z", "relro", "-z", "now"])
+        .arg("--version-script")
+        .arg(src_path("proc_macro_lib.ver"))
+        .input_with_comp_args("proc_macro_lib.c", InputType::Object, pic.clone())?
+        .arg(LIBC)
+        .link()?;
+    shared.expect_success()?;
+
+    let bytes = std::fs::read(&shared.binary)?;
+    let obj = object::File::parse(bytes.as_slice())?;
+    let exports: Vec<&str> = obj
+        .dynamic_symbols()
+        .filter(|sym| !sym.is_undefined())
+        .filter_map(|sym| sym.name().ok())
+        .collect();
+    for name in [
+        "__rustc_proc_macro_decls_0123abcd__",
+        "rust_metadata_proc_macro_0123abcd",
+    ] {
+        if !exports.contains(&name) {
+            bail!("Expected `{name}` to be exported. Exports: {exports:?}");
+        }
+    }
+    if exports.contains(&"proc_macro_helper") {
+        bail!("`proc_macro_helper` should have been made local by the version script");
+    }
+    let elf = object::read::elf::ElfFile64::<object::Endianness>::parse(bytes.as_slice())?;
+    let has_relro = elf.elf_program_headers().iter().any(|header| {
+        object::read::elf::ProgramHeader::p_type(header, elf.endian()) == object::elf::PT_GNU_RELRO
+    });
+    if !has_relro {
+        bail!("Missing PT_GNU_RELRO");
+    }
+
+    WildLink::new_dynamic("proc_macro_style_shared_object")
+        .args(&["-dynamic-linker", DYNAMIC_LINKER])
+        .input_with_comp_args("proc_macro_main.c", InputType::Object, pic)?
+        .input("exit.c", InputType::Object)?
+        .arg(&shared.binary)
+        .arg(LIBC)
+        .link()?
+        .run()
+}
+
+/// Returns the names of the symbols referenced by the dynamic relocations in `path`.
+fn dynamic_relocation_symbols(path: &Path) -> Result<Vec<String>> {
+    let bytes = std::fs::read(path)?;
+    let obj = object::File::parse(bytes.as_slice())?;
+    let (Some(relocations), Some(dynsym)) = (obj.dynamic_relocations(), obj.dynamic_symbol_table())
+    else {
+        return Ok(Vec::new());
+    };
+    let mut names = Vec::new();
+    for (_, rel) in relocations {
+        if let object::RelocationTarget::Symbol(index) = rel.target() {
+            names.push(dynsym.symbol_by_index(index)?.name()?.to_owned());
+        }
+    }
+    Ok(names)
+}
+
+/// Checks that a shared object that reads a variable defined by another shared object references
+/// the variable via dynamic relocations against it, rather than copying it with a COPY relocation,
+/// which only executables can have.
+#[test]
+fn shared_reads_shared_data() -> Result {
+    let pic = ArgumentSet::parse("pic:-fPIC")?;
+    let lib = WildLink::new_dynamic("libshared_data_lib.so")
+        .arg("-shared")
+        .input_with_comp_args("shared_data_lib.c", InputType::Object, pic.clone())?
+        .link()?;
+    lib.expect_success()?;
+    let shared = WildLink::new_dynamic("libshared_data.so")
+        .arg("-shared")
+        .input_with_comp_args("shared_data.c", InputType::Object, pic)?
+        .arg(&lib.binary)
+        .link()?;
+    shared.expect_success()?;
+    if has_dynamic_relocation(&shared.binary, object::elf::R_X86_64_COPY)? {
+        bail!("Shared object contains a COPY relocation");
+    }
+
+    WildLink::new_dynamic("shared_reads_shared_data")
+        .args(&["-dynamic-linker", DYNAMIC_LINKER])
+        .input("shared_data_main.c", InputType::Object)?
+        .input("exit.c", InputType::Object)?
+        .arg(&shared.binary)
+        .link()?
+        .run()
+}
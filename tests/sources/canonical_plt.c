@@ -0,0 +1,14 @@
+// Compiled without -fPIE, so we take the address of `canonical_fn` with an absolute relocation
+// rather than via the GOT. That makes our PLT entry the function's canonical address.
+
+#include "exit.h"
+
+int canonical_fn(void);
+void *canonical_fn_address(void);
+
+void _start(void) {
+    if ((void *)&canonical_fn != canonical_fn_address()) {
+        exit_syscall(1);
+    }
+    exit_syscall(canonical_fn());
+}
@@ -0,0 +1,10 @@
+// Built into a shared object that returns the address of one of its own functions. If an executable
+// takes the address of the same function directly, then both need to get the same address.
+
+int canonical_fn(void) {
+    return 42;
+}
+
+void *canonical_fn_address(void) {
+    return (void *)&canonical_fn;
+}
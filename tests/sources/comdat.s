@@ -0,0 +1,36 @@
+// Both this file and comdat1.s define the COMDAT group `get_a` and the legacy linkonce section
+// `.gnu.linkonce.t.get_b`. The symbols are strong in both files, so if we failed to discard the
+// copies from comdat1.s, we'd either get duplicate definitions or use the wrong values.
+
+.section .text.get_a,"axG",@progbits,get_a,comdat
+.globl get_a
+.type get_a, @function
+get_a:
+    .cfi_startproc
+    mov     $10, %eax
+    ret
+    .cfi_endproc
+
+.section .gnu.linkonce.t.get_b,"ax",@progbits
+.globl get_b
+.type get_b, @function
+get_b:
+    .cfi_startproc
+    mov     $20, %eax
+    ret
+    .cfi_endproc
+
+.section .text._start,"ax",@progbits
+.globl _start
+.type _start, @function
+_start:
+    endbr64
+    call    get_a
+    mov     %eax, %ebx
+    call    get_b
+    add     %eax, %ebx
+    // get_c is only defined in comdat1.s. It calls get_a, which should resolve to our copy.
+    call    get_c
+    add     %ebx, %eax
+    mov     %eax, %edi
+    call    exit_syscall
@@ -0,0 +1,27 @@
+.section .text.get_a,"axG",@progbits,get_a,comdat
+.globl get_a
+.type get_a, @function
+get_a:
+    .cfi_startproc
+    mov     $100, %eax
+    ret
+    .cfi_endproc
+
+.section .gnu.linkonce.t.get_b,"ax",@progbits
+.globl get_b
+.type get_b, @function
+get_b:
+    .cfi_startproc
+    mov     $200, %eax
+    ret
+    .cfi_endproc
+
+.section .text.get_c,"ax",@progbits
+.globl get_c
+.type get_c, @function
+get_c:
+    .cfi_startproc
+    call    get_a
+    add     $2, %eax
+    ret
+    .cfi_endproc
@@ -0,0 +1,41 @@
+//#LinkArgs:defsym:--defsym=thirty=30 --defsym=get_alias=get_twelve --defsym=table_second=table+4 --defsym=alias_first=alias_second+4 --defsym=alias_second=table-4
+//#LinkArgs:defsym-pie:--defsym=thirty=30 --defsym=get_alias=get_twelve --defsym=table_second=table+4 --defsym=alias_second=table-4 --defsym=alias_first=alias_second+4 -pie --no-dynamic-linker
+
+// The PIE variant doesn't apply its own dynamic relocations, so anything that needs one won't have
+// the right value. Symbols defined as absolute values shouldn't need them. ld only gets aliases of
+// aliases right in PIE output if they're defined in dependency order, so the PIE variant defines
+// them in that order.
+
+#include "exit.h"
+
+extern char thirty[];
+extern int table_second;
+// Defined in terms of another --defsym symbol that, in the non-PIE variant, is only defined later on
+// the command line.
+extern int alias_first;
+int get_alias(void);
+
+// We reference this directly as well as via --defsym, otherwise ld would discard it.
+int table[] = {5, 7};
+
+// An absolute address, so doesn't need a dynamic relocation, even in a position-independent
+// executable. This isn't const, otherwise the compiler would use a PC-relative reference to
+// `thirty`, which can't work for an absolute symbol in a position-independent executable.
+const char* thirty_pointer = thirty;
+
+int get_twelve(void) {
+    return 12;
+}
+
+void _start(void) {
+    if (table[0] != 5) {
+        exit_syscall(4);
+    }
+    if (table_second != 7) {
+        exit_syscall(1);
+    }
+    if (alias_first != 5) {
+        exit_syscall(2);
+    }
+    exit_syscall((int)(long)thirty_pointer + get_alias());
+}
@@ -0,0 +1,29 @@
+// Linked against a shared object and libc. Checks that we can call functions that they define, read
+// and write a data object that they define and access a TLS variable that they define.
+
+#include "exit.h"
+
+#include <string.h>
+
+extern int imported_data[4];
+extern __thread int imported_tls;
+
+int read_imported_data(void);
+
+// Volatile so that the compiler can't compute the length itself.
+const char *volatile text = "abc";
+
+void _start(void) {
+    // strlen is an ifunc in libc, so the dynamic loader picks an implementation, then binds our PLT
+    // entry to it.
+    if (strlen(text) != 3) {
+        exit_syscall(1);
+    }
+    // We reference `imported_data` directly, so it gets copied into our .bss with a COPY
+    // relocation. The shared object then needs to see our copy.
+    imported_data[3] += 2;
+    if (read_imported_data() != 32) {
+        exit_syscall(2);
+    }
+    exit_syscall(imported_data[3] + imported_tls * 2);
+}
@@ -0,0 +1,9 @@
+// Built into a shared object whose function, data and TLS variable are used by dynamic_imports.c.
+
+int imported_data[4] = {1, 2, 3, 30};
+
+__thread int imported_tls = 5;
+
+int read_imported_data(void) {
+    return imported_data[3];
+}
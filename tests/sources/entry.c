@@ -0,0 +1,14 @@
+// Checks that we can use a symbol other than `_start` as our entry point.
+
+#include "exit.h"
+
+void _start(void) {
+    exit_syscall(10);
+}
+
+void custom_start(void) {
+    exit_syscall(42);
+}
+
+//#LinkArgs:entry:-e custom_start
+//#LinkArgs:entry-long:--entry=custom_start
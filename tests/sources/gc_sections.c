@@ -0,0 +1,21 @@
+//#CompArgs:sections:-ffunction-sections -fmerge-constants
+//#LinkArgs:no-gc:--gc-sections --no-gc-sections
+
+#include "exit.h"
+
+// Nothing references this function, so it'd normally be discarded. With --no-gc-sections, it
+// should be kept.
+int unreferenced(void) {
+    return 7;
+}
+
+// Likewise, the string that this returns is only referenced from a discarded section.
+const char* unreferenced_string(void) {
+    return "unreferenced";
+}
+
+void _start(void) {
+    exit_syscall(42);
+}
+
+//#ExpectSym: unreferenced
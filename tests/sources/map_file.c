@@ -0,0 +1,18 @@
+// Used by the `map_file` test, which checks the map file that we write when linking this. Compiled
+// with -fmerge-constants so that our string literal goes in a string-merge section.
+
+#include "exit.h"
+
+int zeroed[16];
+
+const char* get_message(void) {
+    return "Hello";
+}
+
+void _start(void) {
+    if (get_message()[0] != 'H') {
+        exit_syscall(101);
+    }
+    zeroed[1] = 42;
+    exit_syscall(zeroed[1]);
+}
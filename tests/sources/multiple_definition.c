@@ -0,0 +1,11 @@
+// Used by the `multiple_definition` and `start_lib` tests. Both this file and
+// multiple_definition1.c have strong definitions of `value`. When multiple definitions are allowed,
+// the first one should be used.
+
+#include "exit.h"
+
+int value = 42;
+
+void _start(void) {
+    exit_syscall(value);
+}
@@ -0,0 +1,3 @@
+// A second strong definition of `value`. See multiple_definition.c.
+
+int value = 7;
@@ -0,0 +1,21 @@
+// Built like a proc-macro crate: a shared object that links against another shared object, with libc
+// standing in for libstd, and that only exports what its version script lists.
+
+#include <string.h>
+
+struct ProcMacroDecls {
+    int (*helper)(void);
+    int value;
+};
+
+// Volatile so that the compiler can't compute the length itself.
+const char *volatile proc_macro_name = "derive";
+
+// Global, but made local by the version script.
+int proc_macro_helper(void) {
+    return strlen(proc_macro_name);
+}
+
+const struct ProcMacroDecls __rustc_proc_macro_decls_0123abcd__ = {proc_macro_helper, 36};
+
+const char rust_metadata_proc_macro_0123abcd[] = "rust";
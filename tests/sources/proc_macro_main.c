@@ -0,0 +1,16 @@
+// Looks up the declarations exported by the shared object built from proc_macro_lib.c, much like
+// rustc does when it loads a proc macro.
+
+#include "exit.h"
+
+struct ProcMacroDecls {
+    int (*helper)(void);
+    int value;
+};
+
+extern const struct ProcMacroDecls __rustc_proc_macro_decls_0123abcd__;
+
+void _start(void) {
+    exit_syscall(__rustc_proc_macro_decls_0123abcd__.helper() +
+                 __rustc_proc_macro_decls_0123abcd__.value);
+}
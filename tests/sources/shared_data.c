@@ -0,0 +1,16 @@
+// Built into a shared object that reads a variable defined by another shared object. Since we're
+// producing a shared object, the variable can't be copied into our .bss, so references to it need
+// to go via dynamic relocations against the variable.
+
+extern int imported_value;
+
+// Needs an absolute relocation against `imported_value`.
+int *value_ptr = &imported_value;
+
+int read_imported(void) {
+    if (value_ptr != &imported_value) {
+        return 1;
+    }
+    // Read via the GOT.
+    return imported_value + 2;
+}
@@ -0,0 +1,3 @@
+// Built into a shared object that defines a variable for another shared object to read.
+
+int imported_value = 40;
@@ -0,0 +1,13 @@
+// Built into a shared object whose exported definitions are interposed by those in an earlier shared
+// object. References from within this shared object should then see the earlier definitions,
+// unless we're linked with -Bsymbolic.
+
+int interposed_value = 1;
+
+int interposed_function(void) {
+    return 40;
+}
+
+int read_interposed(void) {
+    return interposed_function() + interposed_value;
+}
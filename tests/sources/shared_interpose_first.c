@@ -0,0 +1,8 @@
+// Built into a shared object that's loaded before the one built from shared_interpose.c, so its
+// definitions take precedence over those in that shared object.
+
+int interposed_value = 42;
+
+int interposed_function(void) {
+    return 0;
+}
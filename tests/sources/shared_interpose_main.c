@@ -0,0 +1,7 @@
+#include "exit.h"
+
+int read_interposed(void);
+
+void _start(void) {
+    exit_syscall(read_interposed());
+}
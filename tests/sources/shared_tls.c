@@ -0,0 +1,10 @@
+// Built into a shared object that accesses its own TLS variables with the general dynamic model.
+// Since `tls_value` isn't at the start of our TLS block, this checks that the offsets that we pass
+// to __tls_get_addr are relative to the start of the block.
+
+__thread long tls_padding[4] = {1, 2, 3, 4};
+__thread int tls_value = 40;
+
+int read_tls(void) {
+    return tls_value + tls_padding[1];
+}
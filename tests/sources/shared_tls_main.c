@@ -0,0 +1,7 @@
+#include "exit.h"
+
+int read_tls(void);
+
+void _start(void) {
+    exit_syscall(read_tls());
+}
@@ -0,0 +1,32 @@
+// Built into a shared object without libc as an input, so the references to libc are left
+// undefined for the dynamic loader to resolve once libc has been loaded by the executable.
+
+#include <stdio.h>
+#include <string.h>
+
+char message[] = "forty-two";
+
+// Nothing defines this, so it should be left as a weak undefined symbol that the dynamic loader
+// resolves to null.
+extern int not_defined_anywhere(void) __attribute__((weak));
+
+// Needs an absolute relocation against `strlen`.
+size_t (*strlen_ptr)(const char *) = strlen;
+
+int call_libc(void) {
+    // Called via the PLT.
+    if (strlen(message) != 9) {
+        return 1;
+    }
+    if (strlen_ptr("abc") != 3) {
+        return 2;
+    }
+    // Read via the GOT.
+    if (stdout == NULL) {
+        return 3;
+    }
+    if (not_defined_anywhere) {
+        return 4;
+    }
+    return 42;
+}
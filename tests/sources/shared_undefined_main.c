@@ -0,0 +1,7 @@
+#include "exit.h"
+
+int call_libc(void);
+
+void _start(void) {
+    exit_syscall(call_libc());
+}
@@ -0,0 +1,39 @@
+//#CompArgs:merge-constants:-ffunction-sections -fmerge-constants
+
+// Compiled with -fmerge-constants so that our string literals go in a string-merge section. The
+// assembler turns absolute references to the string literals into references to the section symbol
+// plus an addend, so which string is referenced depends on the addend.
+
+#include "exit.h"
+
+// This isn't const, so the compiler can't fold loads from it. This means that the pointers in it
+// are the only references to the strings.
+const char* messages[] = {"Hello", "World", "Wild"};
+
+// Defined in string_merging_section_refs1.s.
+const char* get_first(void);
+const char* get_second(void);
+
+// The string literal here is only referenced from a section that gets garbage collected.
+const char* unreferenced_string(void) {
+    return "Discarded";
+}
+
+void _start(void) {
+    if (messages[0][0] != 'H' || messages[0][4] != 'o') {
+        exit_syscall(101);
+    }
+    if (messages[1][0] != 'W' || messages[1][4] != 'd') {
+        exit_syscall(102);
+    }
+    if (messages[2][0] != 'W' || messages[2][3] != 'd' || messages[2][4] != 0) {
+        exit_syscall(103);
+    }
+    if (get_first()[0] != 'F' || get_first()[4] != 't') {
+        exit_syscall(104);
+    }
+    if (get_second()[0] != 'S' || get_second()[5] != 'd') {
+        exit_syscall(105);
+    }
+    exit_syscall(42);
+}
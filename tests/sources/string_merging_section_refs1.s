@@ -0,0 +1,22 @@
+// References strings via RIP-relative `lea`. The assembler turns these into references to the
+// section symbol with an addend that includes -4 for the distance from the relocation to the end of
+// the instruction. For the first string, that makes the addend negative. For `.Lsecond`, the addend
+// points into "Hello", which gets deduplicated with the copy in string_merging_section_refs.c, so
+// if we didn't take the -4 back off, we'd resolve to that copy instead.
+
+.section .rodata.str1.1, "aMS", @progbits, 1
+.Lfirst: .string "First"
+.string "Hello"
+.Lsecond: .string "Second"
+
+.section .text.get_first, "ax", @progbits
+.globl get_first
+get_first:
+    leaq .Lfirst(%rip), %rax
+    ret
+
+.section .text.get_second, "ax", @progbits
+.globl get_second
+get_second:
+    leaq .Lsecond(%rip), %rax
+    ret
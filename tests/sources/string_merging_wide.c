@@ -0,0 +1,57 @@
+//#LinkArgs:default:
+//#LinkArgs:tail-merge:-O2
+
+// Checks merging of strings from sections with alignment greater than 1 and with characters wider
+// than a byte. With -O2, strings that are a suffix of another string may share its storage, so we
+// check the contents of each string, not just which strings are identical.
+
+#include "exit.h"
+
+extern const char foobar1[];
+extern const char foobar2[];
+extern const char bar[];
+extern const char aligned1[];
+extern const char aligned2[];
+extern const int wide1[];
+extern const int wide2[];
+extern const int wide_tail[];
+extern const short half[];
+
+static int equal(const char* a, const char* b) {
+    while (*a == *b) {
+        if (*a == 0) {
+            return 1;
+        }
+        a++;
+        b++;
+    }
+    return 0;
+}
+
+void _start(void) {
+    if (foobar1 != foobar2) {
+        exit_syscall(101);
+    }
+    if (!equal(foobar1, "foobar") || !equal(bar, "bar")) {
+        exit_syscall(102);
+    }
+    if (aligned1 != aligned2) {
+        exit_syscall(103);
+    }
+    if (((unsigned long)aligned1 & 15) != 0 || !equal(aligned1, "aligned")) {
+        exit_syscall(104);
+    }
+    if (wide1 != wide2) {
+        exit_syscall(105);
+    }
+    if (((unsigned long)wide1 & 3) != 0 || wide1[0] != 'W' || wide1[1] != 'x' || wide1[2] != 0) {
+        exit_syscall(106);
+    }
+    if (((unsigned long)wide_tail & 3) != 0 || wide_tail[0] != 'x' || wide_tail[1] != 0) {
+        exit_syscall(107);
+    }
+    if (((unsigned long)half & 1) != 0 || half[0] != 'H' || half[1] != 0) {
+        exit_syscall(108);
+    }
+    exit_syscall(42);
+}
@@ -0,0 +1,22 @@
+.section .rodata.str1.1, "aMS", @progbits, 1
+
+.globl foobar1
+foobar1: .asciz "foobar"
+
+.section .rodata.str1.16, "aMS", @progbits, 1
+.p2align 4
+
+.globl aligned1
+aligned1: .asciz "aligned"
+
+.section .rodata.str4.4, "aMS", @progbits, 4
+.p2align 2
+
+.globl wide1
+wide1: .long 'W', 'x', 0
+
+.section .rodata.str2.2, "aMS", @progbits, 2
+.p2align 1
+
+.globl half
+half: .short 'H', 0
@@ -0,0 +1,22 @@
+.section .rodata.str1.1, "aMS", @progbits, 1
+
+.globl bar
+bar: .asciz "bar"
+
+.globl foobar2
+foobar2: .asciz "foobar"
+
+.section .rodata.str1.16, "aMS", @progbits, 1
+.p2align 4
+
+.globl aligned2
+aligned2: .asciz "aligned"
+
+.section .rodata.str4.4, "aMS", @progbits, 4
+.p2align 2
+
+.globl wide2
+wide2: .long 'W', 'x', 0
+
+.globl wide_tail
+wide_tail: .long 'x', 0
@@ -0,0 +1,13 @@
+// Used by the `trace_symbol` test. Our definition of `value` is weak, so the strong definition in
+// multiple_definition1.c should be selected.
+
+#include "exit.h"
+
+int __attribute__ ((weak)) value = 1;
+
+void _start(void) {
+    if (value != 7) {
+        exit_syscall(101);
+    }
+    exit_syscall(42);
+}
@@ -2,6 +2,8 @@
 
 //#LinkArgs:static:--cc=clang -static
 // //#LinkArgs:static-pie:--cc=clang -static-pie
+//#LinkArgs:dynamic:--cc=clang -no-pie
+//#LinkArgs:dynamic-pie:--cc=clang -pie
 
 int main() {
     return 42;
@@ -0,0 +1,19 @@
+//#InputType: Archive
+//#LinkArgs:undefined:-u register_plugin
+//#LinkArgs:require-defined:--require-defined=register_plugin
+
+#include "exit.h"
+
+// Nothing references anything in undefined1.c, so the archive member containing it will only be
+// loaded if we honour -u / --require-defined. If it is loaded, its strong definition of this
+// function overrides ours.
+__attribute__ ((weak)) int is_plugin_loaded() {
+    return 0;
+}
+
+void _start(void) {
+    if (!is_plugin_loaded()) {
+        exit_syscall(101);
+    }
+    exit_syscall(42);
+}
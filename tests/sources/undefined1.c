@@ -0,0 +1,5 @@
+int is_plugin_loaded() {
+    return 1;
+}
+
+void register_plugin(void) {}
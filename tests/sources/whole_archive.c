@@ -0,0 +1,17 @@
+//#InputType: Archive
+//#LinkArgs:whole-archive:--whole-archive
+
+#include "exit.h"
+
+// Nothing references whole_archive1.c, so its archive member only gets loaded due to
+// --whole-archive. If it is loaded, its strong definition of this function overrides ours.
+__attribute__ ((weak)) int is_member_loaded() {
+    return 0;
+}
+
+void _start(void) {
+    if (!is_member_loaded()) {
+        exit_syscall(101);
+    }
+    exit_syscall(42);
+}
@@ -0,0 +1,3 @@
+int is_member_loaded() {
+    return 1;
+}
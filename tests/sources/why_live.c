@@ -0,0 +1,20 @@
+// Used by the `why_live` test, which checks that we can explain why `leaf` was kept, which requires
+// following references through several sections. Compiled with -ffunction-sections so that each
+// function is in its own section. Also checks `live_string`, which is in a string-merge section.
+
+#include "exit.h"
+
+// Defined in why_live1.s.
+extern const char live_string[];
+
+int leaf(void) {
+    return 42;
+}
+
+int middle(void) {
+    return leaf() + live_string[0] - 'L';
+}
+
+void _start(void) {
+    exit_syscall(middle());
+}
@@ -0,0 +1,4 @@
+.section .rodata.str1.1, "aMS", @progbits, 1
+
+.globl live_string
+live_string: .string "Live"
@@ -0,0 +1,21 @@
+//#LinkArgs:wrap:--wrap=get_value --wrap=get_other
+//#InputType: Object, Archive
+
+// When linking as archives, `get_value` is in an archive member that's only loaded because of the
+// reference to `__real_get_value`, while `__wrap_get_other` is in an archive member that's only
+// loaded because of our reference to `get_other`.
+
+#include "exit.h"
+
+int get_value(void);
+int __real_get_value(void);
+// Never defined. References to this get redirected to `__wrap_get_other`.
+int get_other(void);
+
+int __wrap_get_value(void) {
+    return __real_get_value() + 32;
+}
+
+void _start(void) {
+    exit_syscall(get_value() + get_other());
+}
@@ -0,0 +1,3 @@
+int get_value(void) {
+    return 10;
+}
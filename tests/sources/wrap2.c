@@ -0,0 +1,3 @@
+int __wrap_get_other(void) {
+    return 0;
+}